@@ -1,8 +1,12 @@
-use cxx_qt_build::{CxxQtBuilder, QmlModule};
-
 fn main() {
-    CxxQtBuilder::new_qml_module(QmlModule::new("osc.chatbox").qml_file("qml/main.qml"))
-        .qt_module("Network")
-        .files(["src/cxxqt_object.rs"])
-        .build();
+    // Headless builds skip Qt entirely
+    #[cfg(feature = "gui")]
+    {
+        use cxx_qt_build::{CxxQtBuilder, QmlModule};
+
+        CxxQtBuilder::new_qml_module(QmlModule::new("osc.chatbox").qml_file("qml/main.qml"))
+            .qt_module("Network")
+            .files(["src/cxxqt_object.rs"])
+            .build();
+    }
 }
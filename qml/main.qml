@@ -1,6 +1,7 @@
 import QtQuick 2.15
 import QtQuick.Controls 2.15
 import QtQuick.Layouts 1.15
+import QtQuick.Dialogs
 import Qt.labs.settings 1.0
 import osc.chatbox 1.0
 
@@ -18,7 +19,14 @@ ApplicationWindow {
     onHeightChanged: settings.windowHeight = height
     onXChanged: settings.windowX = x
     onYChanged: settings.windowY = y
+    onClosing: {
+        if (saveTimer.running) {
+            saveTimer.stop()
+            controller.saveConfig()
+        }
+    }
 
+    // Only window geometry lives here; everything else is in the config file
     Settings {
         id: settings
         category: "osc_chatbox"
@@ -26,28 +34,6 @@ ApplicationWindow {
         property int windowHeight: 720
         property int windowX: 100
         property int windowY: 100
-
-        property string oscAddress: "127.0.0.1:9000"
-        property bool statusEnabled: false
-        property string statusLine1: ""
-        property string statusLine2: ""
-        property string statusLine3: ""
-        property string statusLine4: ""
-        property string statusLine5: ""
-        property string statusLine6: ""
-        property bool timeEnabled: false
-        property string timeFormat: "%H:%M"
-        property bool statsEnabled: false
-        property bool statsShowCpu: true
-        property bool statsShowRam: true
-        property bool networkEnabled: false
-        property bool mediaEnabled: false
-        property bool afkEnabled: false
-        property int afkTimeoutSecs: 300
-        property string afkText: "AFK"
-        property bool systemDetailsEnabled: false
-        property bool heartrateEnabled: false
-        property string heartrateToken: ""
     }
 
     property bool loading: true
@@ -58,65 +44,53 @@ ApplicationWindow {
 
     function loadSettings() {
         loading = true
-        controller.osc_address = settings.oscAddress
-        controller.status_enabled = settings.statusEnabled
-        controller.status_line1 = settings.statusLine1
-        controller.status_line2 = settings.statusLine2
-        controller.status_line3 = settings.statusLine3
-        controller.status_line4 = settings.statusLine4
-        controller.status_line5 = settings.statusLine5
-        controller.status_line6 = settings.statusLine6
-        controller.time_enabled = settings.timeEnabled
-        controller.time_format = settings.timeFormat
-        controller.stats_enabled = settings.statsEnabled
-        controller.stats_show_cpu = settings.statsShowCpu
-        controller.stats_show_ram = settings.statsShowRam
-        controller.network_enabled = settings.networkEnabled
-        controller.media_enabled = settings.mediaEnabled
-        controller.afk_enabled = settings.afkEnabled
-        controller.afk_timeout_secs = settings.afkTimeoutSecs
-        controller.afk_text = settings.afkText
-        controller.system_details_enabled = settings.systemDetailsEnabled
-        controller.heartrate_enabled = settings.heartrateEnabled
-        controller.heartrate_token = settings.heartrateToken
+        controller.loadConfig()
+        loading = false
+    }
+
+    // Switching replaces every setting at once, so the controls must not
+    // echo each change back while the profile is loaded
+    function switchProfile(name) {
+        if (name === controller.active_profile) return
+        saveTimer.stop()
+        loading = true
+        controller.switchProfile(name)
         loading = false
     }
 
+    property var profileNames: controller.profile_names.length > 0
+                               ? controller.profile_names.split("\n") : []
+
+    FileDialog {
+        id: importDialog
+        title: "Import Profile"
+        nameFilters: ["Profiles (*.toml)"]
+        onAccepted: controller.importProfile(selectedFile.toString())
+    }
+
+    FileDialog {
+        id: exportDialog
+        title: "Export Profile"
+        fileMode: FileDialog.SaveFile
+        defaultSuffix: "toml"
+        nameFilters: ["Profiles (*.toml)"]
+        onAccepted: controller.exportProfile(controller.active_profile, selectedFile.toString())
+    }
+
     Component.onCompleted: {
         loadSettings()
     }
 
+    // Edits arrive per keystroke, so writes to disk are batched
     function saveSettings() {
         if (loading) return
-        settings.oscAddress = controller.osc_address
-        settings.statusEnabled = controller.status_enabled
-        settings.statusLine1 = controller.status_line1
-        settings.statusLine2 = controller.status_line2
-        settings.statusLine3 = controller.status_line3
-        settings.statusLine4 = controller.status_line4
-        settings.statusLine5 = controller.status_line5
-        settings.statusLine6 = controller.status_line6
-        settings.timeEnabled = controller.time_enabled
-        settings.timeFormat = controller.time_format
-        settings.statsEnabled = controller.stats_enabled
-        settings.statsShowCpu = controller.stats_show_cpu
-        settings.statsShowRam = controller.stats_show_ram
-        settings.networkEnabled = controller.network_enabled
-        settings.mediaEnabled = controller.media_enabled
-        settings.afkEnabled = controller.afk_enabled
-        settings.afkTimeoutSecs = controller.afk_timeout_secs
-        settings.afkText = controller.afk_text
-        settings.systemDetailsEnabled = controller.system_details_enabled
-        settings.heartrateEnabled = controller.heartrate_enabled
-        settings.heartrateToken = controller.heartrate_token
+        saveTimer.restart()
     }
 
     Timer {
-        id: tickTimer
-        interval: 1500
-        repeat: true
-        running: controller.running
-        onTriggered: controller.tick()
+        id: saveTimer
+        interval: 500
+        onTriggered: controller.saveConfig()
     }
 
     ScrollView {
@@ -159,6 +133,30 @@ ApplicationWindow {
                         }
                     }
 
+                    RowLayout {
+                        Label { text: "Listen Address:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        TextField {
+                            text: controller.osc_listen_address
+                            placeholderText: "127.0.0.1:9001 (empty to disable)"
+                            Layout.fillWidth: true
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            onTextChanged: { controller.osc_listen_address = text; saveSettings() }
+                        }
+                    }
+
+                    RowLayout {
+                        Label { text: "Interval (ms):"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        SpinBox {
+                            value: controller.send_interval_ms
+                            from: 100
+                            to: 10000
+                            stepSize: 100
+                            editable: true
+                            onValueChanged: { controller.send_interval_ms = value; controller.applySettings(); saveSettings() }
+                        }
+                    }
+
                     RowLayout {
                         spacing: 8
                         Button {
@@ -168,7 +166,6 @@ ApplicationWindow {
                                 if (controller.running) {
                                     controller.stop()
                                 } else {
-                                    loadSettings()
                                     controller.start()
                                 }
                             }
@@ -182,6 +179,108 @@ ApplicationWindow {
                             color: "#c0c0c0"
                         }
                     }
+
+                    Label {
+                        visible: controller.running && controller.last_composed.length > 0
+                        text: controller.last_composed
+                        color: "#c0c0c0"
+                        wrapMode: Text.Wrap
+                        Layout.fillWidth: true
+                    }
+
+                    Label {
+                        visible: controller.running && controller.last_sent_at.length > 0
+                        text: "Last sent at " + controller.last_sent_at
+                            + (controller.last_composed.length > 0 && controller.last_composed !== controller.last_output ? ", update held back by the rate limit" : "")
+                        color: "#808080"
+                        Layout.fillWidth: true
+                    }
+
+                    Label {
+                        visible: controller.error_count > 0
+                        text: controller.error_count + (controller.error_count === 1 ? " error" : " errors")
+                            + ", last: " + controller.last_error
+                        color: "#ff4444"
+                        wrapMode: Text.Wrap
+                        Layout.fillWidth: true
+                    }
+
+                    Label {
+                        visible: controller.config_error.length > 0
+                        text: "Settings not saved: " + controller.config_error
+                        color: "#ff4444"
+                        wrapMode: Text.Wrap
+                        Layout.fillWidth: true
+                    }
+                }
+            }
+
+            GroupBox {
+                title: "Profiles"
+                Layout.fillWidth: true
+                background: Rectangle { color: "transparent" }
+                label: Label { text: parent.title; color: "#e94560"; font.bold: true; padding: 4 }
+
+                ColumnLayout {
+                    width: parent.width
+                    spacing: 6
+
+                    RowLayout {
+                        Label { text: "Profile:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        ComboBox {
+                            model: root.profileNames
+                            currentIndex: root.profileNames.indexOf(controller.active_profile)
+                            displayText: currentIndex >= 0 ? currentText : "(none)"
+                            Layout.fillWidth: true
+                            onActivated: (index) => root.switchProfile(root.profileNames[index])
+                        }
+                        Button {
+                            text: "Delete"
+                            enabled: controller.active_profile.length > 0
+                            onClicked: controller.deleteProfile(controller.active_profile)
+                        }
+                    }
+
+                    Label {
+                        visible: controller.rule_profile.length > 0
+                        text: "Rules switched to \"" + controller.rule_profile + "\""
+                        color: "#808080"
+                        Layout.fillWidth: true
+                    }
+
+                    RowLayout {
+                        TextField {
+                            id: profileName
+                            placeholderText: "New profile name"
+                            Layout.fillWidth: true
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            Keys.onReturnPressed: saveProfileBtn.clicked()
+                        }
+                        Button {
+                            id: saveProfileBtn
+                            text: "Save As"
+                            enabled: profileName.text.trim().length > 0
+                            onClicked: {
+                                saveTimer.stop()
+                                controller.saveProfile(profileName.text)
+                                profileName.text = ""
+                            }
+                        }
+                    }
+
+                    RowLayout {
+                        spacing: 8
+                        Button {
+                            text: "Import..."
+                            onClicked: importDialog.open()
+                        }
+                        Button {
+                            text: "Export..."
+                            enabled: controller.active_profile.length > 0
+                            onClicked: exportDialog.open()
+                        }
+                    }
                 }
             }
 
@@ -220,6 +319,42 @@ ApplicationWindow {
                             }
                         }
                     }
+                    CheckBox {
+                        text: "Show page numbers on long messages"
+                        checked: controller.chat_page_indicator
+                        onCheckedChanged: { controller.chat_page_indicator = checked; controller.applySettings(); saveSettings() }
+                        palette.text: "#c0c0c0"
+                    }
+                }
+            }
+
+            GroupBox {
+                title: "Layout"
+                Layout.fillWidth: true
+                background: Rectangle { color: "transparent" }
+                label: Label { text: parent.title; color: "#e94560"; font.bold: true; padding: 4 }
+
+                ColumnLayout {
+                    width: parent.width
+                    spacing: 6
+
+                    TextArea {
+                        text: controller.layout_template
+                        placeholderText: "{time} | {stats.cpu|superscript}\n{?media}{media}{/}"
+                        wrapMode: TextEdit.Wrap
+                        Layout.fillWidth: true
+                        color: "#e0e0e0"
+                        background: Rectangle { color: "#0f3460"; radius: 4 }
+                        onTextChanged: { controller.layout_template = text; controller.applySettings(); saveSettings() }
+                    }
+                    Label {
+                        text: controller.layout_error.length > 0
+                              ? controller.layout_error
+                              : "Leave empty to stack all enabled modules"
+                        color: controller.layout_error.length > 0 ? "#ff4444" : "#808080"
+                        wrapMode: Text.Wrap
+                        Layout.fillWidth: true
+                    }
                 }
             }
 
@@ -287,6 +422,16 @@ ApplicationWindow {
                         background: Rectangle { color: "#0f3460"; radius: 4 }
                         onTextChanged: { controller.status_line6 = text; controller.applySettings(); saveSettings() }
                     }
+                    RowLayout {
+                        Label { text: "Rotate every:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        SpinBox {
+                            value: controller.status_rotation_secs
+                            from: 1
+                            to: 600
+                            onValueChanged: { controller.status_rotation_secs = value; controller.applySettings(); saveSettings() }
+                        }
+                        Label { text: "seconds"; color: "#808080" }
+                    }
                 }
             }
 
@@ -335,62 +480,389 @@ ApplicationWindow {
                         onCheckedChanged: { controller.stats_enabled = checked; controller.applySettings(); saveSettings() }
                         palette.text: "#c0c0c0"
                     }
+                    RowLayout {
+                        Label { text: "Metrics:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        TextField {
+                            text: controller.stats_metrics
+                            placeholderText: "cpu, ram gib, disk /home percent"
+                            Layout.fillWidth: true
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            onTextChanged: { controller.stats_metrics = text; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                    Label {
+                        text: "cpu, cores, ram, swap, load, disk [mount], disk_io, top; options mib, gib, percent, label=Name"
+                        color: "#808080"
+                        wrapMode: Text.Wrap
+                        Layout.fillWidth: true
+                    }
+                    RowLayout {
+                        Label { text: "Labels:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        ComboBox {
+                            model: ["upper", "smallcaps", "none"]
+                            currentIndex: Math.max(0, model.indexOf(controller.stats_label_style))
+                            onActivated: (index) => { controller.stats_label_style = model[index]; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                }
+            }
+
+            GroupBox {
+                title: "Network Stats"
+                Layout.fillWidth: true
+                background: Rectangle { color: "transparent" }
+                label: Label { text: parent.title; color: "#e94560"; font.bold: true; padding: 4 }
+
+                ColumnLayout {
+                    width: parent.width
+                    spacing: 6
+
+                    Switch {
+                        text: "Enabled"
+                        checked: controller.network_enabled
+                        onCheckedChanged: { controller.network_enabled = checked; controller.applySettings(); saveSettings() }
+                        palette.text: "#c0c0c0"
+                    }
+                    RowLayout {
+                        Label { text: "Units:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        ComboBox {
+                            model: ["bytes", "bits"]
+                            currentIndex: Math.max(0, model.indexOf(controller.network_unit))
+                            onActivated: (index) => { controller.network_unit = model[index]; controller.applySettings(); saveSettings() }
+                        }
+                        ComboBox {
+                            model: ["si", "iec"]
+                            displayText: currentText === "iec" ? "KiB (1024)" : "kB (1000)"
+                            currentIndex: Math.max(0, model.indexOf(controller.network_prefix))
+                            onActivated: (index) => { controller.network_prefix = model[index]; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                    RowLayout {
+                        Label { text: "Smoothing %:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        SpinBox {
+                            value: controller.network_smoothing
+                            from: 0
+                            to: 95
+                            stepSize: 5
+                            onValueChanged: { controller.network_smoothing = value; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                    RowLayout {
+                        Label { text: "Interfaces:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        TextField {
+                            text: controller.network_interfaces
+                            placeholderText: "all, or e.g. eth, wlan"
+                            Layout.fillWidth: true
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            onTextChanged: { controller.network_interfaces = text; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                    RowLayout {
+                        Label { text: "Exclude:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        TextField {
+                            text: controller.network_exclude
+                            placeholderText: "lo, docker, wg"
+                            Layout.fillWidth: true
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            onTextChanged: { controller.network_exclude = text; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                }
+            }
+
+            GroupBox {
+                title: "GPU Stats"
+                Layout.fillWidth: true
+                background: Rectangle { color: "transparent" }
+                label: Label { text: parent.title; color: "#e94560"; font.bold: true; padding: 4 }
+
+                ColumnLayout {
+                    width: parent.width
+                    spacing: 6
+
+                    Switch {
+                        text: "Enabled"
+                        checked: controller.gpu_enabled
+                        onCheckedChanged: { controller.gpu_enabled = checked; controller.applySettings(); saveSettings() }
+                        palette.text: "#c0c0c0"
+                    }
+                    RowLayout {
+                        Label { text: "Source:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        ComboBox {
+                            model: ["auto", "sysfs", "nvidia-smi"]
+                            currentIndex: Math.max(0, model.indexOf(controller.gpu_backend))
+                            onActivated: (index) => { controller.gpu_backend = model[index]; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                    RowLayout {
+                        Label { text: "Card:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        TextField {
+                            text: controller.gpu_card
+                            placeholderText: "first with stats, or e.g. card1"
+                            Layout.fillWidth: true
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            onTextChanged: { controller.gpu_card = text; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                }
+            }
+
+            GroupBox {
+                title: "Sensors"
+                Layout.fillWidth: true
+                background: Rectangle { color: "transparent" }
+                label: Label { text: parent.title; color: "#e94560"; font.bold: true; padding: 4 }
+
+                ColumnLayout {
+                    width: parent.width
+                    spacing: 6
+
+                    Switch {
+                        text: "Enabled"
+                        checked: controller.sensors_enabled
+                        onCheckedChanged: { controller.sensors_enabled = checked; controller.applySettings(); saveSettings() }
+                        palette.text: "#c0c0c0"
+                    }
+                    RowLayout {
+                        Label { text: "Sensors:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        TextField {
+                            text: controller.sensors_labels
+                            placeholderText: "cpu, nvme/Composite"
+                            Layout.fillWidth: true
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            onTextChanged: { controller.sensors_labels = text; controller.applySettings(); saveSettings() }
+                        }
+                    }
                     RowLayout {
                         CheckBox {
-                            text: "CPU"
-                            checked: controller.stats_show_cpu
-                            onCheckedChanged: { controller.stats_show_cpu = checked; controller.applySettings(); saveSettings() }
+                            text: "Fans"
+                            checked: controller.sensors_fans
+                            onCheckedChanged: { controller.sensors_fans = checked; controller.applySettings(); saveSettings() }
                             palette.text: "#c0c0c0"
                         }
                         CheckBox {
-                            text: "RAM"
-                            checked: controller.stats_show_ram
-                            onCheckedChanged: { controller.stats_show_ram = checked; controller.applySettings(); saveSettings() }
+                            text: "Fahrenheit"
+                            checked: controller.sensors_fahrenheit
+                            onCheckedChanged: { controller.sensors_fahrenheit = checked; controller.applySettings(); saveSettings() }
                             palette.text: "#c0c0c0"
                         }
                     }
+                    RowLayout {
+                        Label { text: "Warn at °C:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        SpinBox {
+                            value: controller.sensors_warn_above
+                            from: 0
+                            to: 120
+                            onValueChanged: { controller.sensors_warn_above = value; controller.applySettings(); saveSettings() }
+                        }
+                        Label { text: "0 never warns"; color: "#808080" }
+                    }
                 }
             }
 
             GroupBox {
-                title: "Network Stats"
+                title: "System Details"
                 Layout.fillWidth: true
                 background: Rectangle { color: "transparent" }
                 label: Label { text: parent.title; color: "#e94560"; font.bold: true; padding: 4 }
 
-                Switch {
-                    text: "Enabled"
-                    checked: controller.network_enabled
-                    onCheckedChanged: { controller.network_enabled = checked; controller.applySettings(); saveSettings() }
-                    palette.text: "#c0c0c0"
+                ColumnLayout {
+                    width: parent.width
+                    spacing: 6
+
+                    Switch {
+                        text: "Enabled"
+                        checked: controller.system_details_enabled
+                        onCheckedChanged: { controller.system_details_enabled = checked; controller.applySettings(); saveSettings() }
+                        palette.text: "#c0c0c0"
+                    }
+                    RowLayout {
+                        Label { text: "Sections:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        TextField {
+                            text: controller.system_details_sections
+                            placeholderText: "os, kernel, cpu, gpu, uptime label=Up"
+                            Layout.fillWidth: true
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            onTextChanged: { controller.system_details_sections = text; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                    Label {
+                        text: "os, kernel, hostname, desktop, resolution, uptime, cpu, cores, gpu, ram; option label=Name"
+                        color: "#808080"
+                        wrapMode: Text.Wrap
+                        Layout.fillWidth: true
+                    }
+                    RowLayout {
+                        Label { text: "Style:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        ComboBox {
+                            model: ["superscript", "smallcaps", "plain"]
+                            currentIndex: Math.max(0, model.indexOf(controller.system_details_style))
+                            onActivated: (index) => { controller.system_details_style = model[index]; controller.applySettings(); saveSettings() }
+                        }
+                        CheckBox {
+                            text: "Lowercase"
+                            checked: controller.system_details_lowercase
+                            onCheckedChanged: { controller.system_details_lowercase = checked; controller.applySettings(); saveSettings() }
+                            palette.text: "#c0c0c0"
+                        }
+                    }
+                    RowLayout {
+                        Label { text: "Rotate every:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        SpinBox {
+                            value: controller.system_details_rotation_secs
+                            from: 1
+                            to: 600
+                            onValueChanged: { controller.system_details_rotation_secs = value; controller.applySettings(); saveSettings() }
+                        }
+                        Label { text: "seconds"; color: "#808080" }
+                    }
                 }
             }
 
             GroupBox {
-                title: "System Details"
+                title: "Media / Music"
                 Layout.fillWidth: true
                 background: Rectangle { color: "transparent" }
                 label: Label { text: parent.title; color: "#e94560"; font.bold: true; padding: 4 }
 
-                Switch {
-                    text: "Enabled"
-                    checked: controller.system_details_enabled
-                    onCheckedChanged: { controller.system_details_enabled = checked; controller.applySettings(); saveSettings() }
-                    palette.text: "#c0c0c0"
+                ColumnLayout {
+                    width: parent.width
+                    spacing: 6
+
+                    Switch {
+                        text: "Enabled"
+                        checked: controller.media_enabled
+                        onCheckedChanged: { controller.media_enabled = checked; controller.applySettings(); saveSettings() }
+                        palette.text: "#c0c0c0"
+                    }
+                    RowLayout {
+                        Label { text: "Prefer:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        TextField {
+                            text: controller.media_players
+                            placeholderText: "spotify, mpv"
+                            Layout.fillWidth: true
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            onTextChanged: { controller.media_players = text; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                    RowLayout {
+                        Label { text: "Ignore:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        TextField {
+                            text: controller.media_blocklist
+                            placeholderText: "chromium, kdeconnect"
+                            Layout.fillWidth: true
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            onTextChanged: { controller.media_blocklist = text; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                    Label { text: "Template:"; color: "#c0c0c0" }
+                    TextArea {
+                        text: controller.media_template
+                        wrapMode: TextEdit.Wrap
+                        Layout.fillWidth: true
+                        color: "#e0e0e0"
+                        background: Rectangle { color: "#0f3460"; radius: 4 }
+                        onTextChanged: { controller.media_template = text; controller.applySettings(); saveSettings() }
+                    }
+                    Label { text: "Per-player templates:"; color: "#c0c0c0" }
+                    TextArea {
+                        text: controller.media_templates
+                        placeholderText: "firefox: {icon} {title}"
+                        wrapMode: TextEdit.Wrap
+                        Layout.fillWidth: true
+                        color: "#e0e0e0"
+                        background: Rectangle { color: "#0f3460"; radius: 4 }
+                        onTextChanged: { controller.media_templates = text; controller.applySettings(); saveSettings() }
+                    }
+                    RowLayout {
+                        Label { text: "Bar:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        SpinBox {
+                            value: controller.media_bar_length
+                            from: 1
+                            to: 40
+                            onValueChanged: { controller.media_bar_length = value; controller.applySettings(); saveSettings() }
+                        }
+                        TextField {
+                            text: controller.media_bar_filled
+                            Layout.preferredWidth: 40
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            onTextChanged: { controller.media_bar_filled = text; controller.applySettings(); saveSettings() }
+                        }
+                        TextField {
+                            text: controller.media_bar_empty
+                            Layout.preferredWidth: 40
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            onTextChanged: { controller.media_bar_empty = text; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                    RowLayout {
+                        Label { text: "Max title:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        SpinBox {
+                            value: controller.media_title_max
+                            from: 0
+                            to: 144
+                            onValueChanged: { controller.media_title_max = value; controller.applySettings(); saveSettings() }
+                        }
+                        Label { text: "Max artist:"; color: "#c0c0c0" }
+                        SpinBox {
+                            value: controller.media_artist_max
+                            from: 0
+                            to: 144
+                            onValueChanged: { controller.media_artist_max = value; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                    Label {
+                        text: "Playing players win over paused ones. 0 keeps titles whole. Fields: {player} {artist} {title} {album} {track} {status} {icon} {position} {length} {remaining} {bar} {volume} {shuffle} {loop}"
+                        color: "#808080"
+                        wrapMode: Text.Wrap
+                        Layout.fillWidth: true
+                    }
                 }
             }
 
             GroupBox {
-                title: "Media / Music"
+                title: "Lyrics"
                 Layout.fillWidth: true
                 background: Rectangle { color: "transparent" }
                 label: Label { text: parent.title; color: "#e94560"; font.bold: true; padding: 4 }
 
-                Switch {
-                    text: "Enabled"
-                    checked: controller.media_enabled
-                    onCheckedChanged: { controller.media_enabled = checked; controller.applySettings(); saveSettings() }
-                    palette.text: "#c0c0c0"
+                ColumnLayout {
+                    width: parent.width
+                    spacing: 6
+
+                    Switch {
+                        text: "Enabled"
+                        checked: controller.lyrics_enabled
+                        onCheckedChanged: { controller.lyrics_enabled = checked; controller.applySettings(); saveSettings() }
+                        palette.text: "#c0c0c0"
+                    }
+                    RowLayout {
+                        Label { text: "Folder:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        TextField {
+                            text: controller.lyrics_dir
+                            placeholderText: "~/.config/osc-chatbox/lyrics"
+                            Layout.fillWidth: true
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            onTextChanged: { controller.lyrics_dir = text; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                    Label {
+                        text: "Shows the current line of \"Artist - Title.lrc\" or \"Title.lrc\" while media plays, or lyrics sent by the player. Needs Media enabled. Fields: {line} {next}"
+                        color: "#808080"
+                        wrapMode: Text.Wrap
+                        Layout.fillWidth: true
+                    }
                 }
             }
 
@@ -430,6 +902,27 @@ ApplicationWindow {
                             onTextChanged: { controller.afk_text = text; controller.applySettings(); saveSettings() }
                         }
                     }
+                    Label { text: "Activity sources:"; color: "#c0c0c0" }
+                    RowLayout {
+                        CheckBox {
+                            text: "VRChat AFK"
+                            checked: controller.afk_source_osc
+                            onCheckedChanged: { controller.afk_source_osc = checked; controller.applySettings(); saveSettings() }
+                            palette.text: "#c0c0c0"
+                        }
+                        CheckBox {
+                            text: "Chat"
+                            checked: controller.afk_source_chat
+                            onCheckedChanged: { controller.afk_source_chat = checked; controller.applySettings(); saveSettings() }
+                            palette.text: "#c0c0c0"
+                        }
+                        CheckBox {
+                            text: "Desktop idle"
+                            checked: controller.afk_source_idle
+                            onCheckedChanged: { controller.afk_source_idle = checked; controller.applySettings(); saveSettings() }
+                            palette.text: "#c0c0c0"
+                        }
+                    }
                 }
             }
 
@@ -457,9 +950,102 @@ ApplicationWindow {
                             Layout.fillWidth: true
                             color: "#e0e0e0"
                             background: Rectangle { color: "#0f3460"; radius: 4 }
-                            onTextChanged: { controller.heartrate_token = text; controller.applySettings(); saveSettings() }
+                            onEditingFinished: { controller.heartrate_token = text; controller.applySettings(); saveSettings() }
                         }
                     }
+                    RowLayout {
+                        Label { text: "URL:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        TextField {
+                            text: controller.heartrate_url
+                            placeholderText: "wss://dev.pulsoid.net/api/v1/data/real_time"
+                            Layout.fillWidth: true
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            onEditingFinished: { controller.heartrate_url = text; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                    RowLayout {
+                        Label { text: "Alert above:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        SpinBox {
+                            value: controller.heartrate_alert_above
+                            from: 0
+                            to: 250
+                            stepSize: 5
+                            onValueChanged: { controller.heartrate_alert_above = value; controller.applySettings(); saveSettings() }
+                        }
+                        Label { text: "below:"; color: "#c0c0c0" }
+                        SpinBox {
+                            value: controller.heartrate_alert_below
+                            from: 0
+                            to: 250
+                            stepSize: 5
+                            onValueChanged: { controller.heartrate_alert_below = value; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                }
+            }
+
+            GroupBox {
+                title: "Events"
+                Layout.fillWidth: true
+                background: Rectangle { color: "transparent" }
+                label: Label { text: parent.title; color: "#e94560"; font.bold: true; padding: 4 }
+
+                ColumnLayout {
+                    width: parent.width
+                    spacing: 6
+
+                    Switch {
+                        text: "Enabled"
+                        checked: controller.events_enabled
+                        onCheckedChanged: { controller.events_enabled = checked; controller.applySettings(); saveSettings() }
+                        palette.text: "#c0c0c0"
+                    }
+                    RowLayout {
+                        Label { text: "Show for (s):"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        SpinBox {
+                            value: controller.events_duration_secs
+                            from: 1
+                            to: 60
+                            onValueChanged: { controller.events_duration_secs = value; controller.applySettings(); saveSettings() }
+                        }
+                        Label { text: "Cooldown (s):"; color: "#c0c0c0" }
+                        SpinBox {
+                            value: controller.events_cooldown_secs
+                            from: 0
+                            to: 3600
+                            stepSize: 10
+                            onValueChanged: { controller.events_cooldown_secs = value; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                    Switch {
+                        text: "Announce new tracks"
+                        checked: controller.events_media
+                        onCheckedChanged: { controller.events_media = checked; controller.applySettings(); saveSettings() }
+                        palette.text: "#c0c0c0"
+                    }
+                    RowLayout {
+                        Label { text: "Template:"; color: "#c0c0c0"; Layout.preferredWidth: 100 }
+                        TextField {
+                            text: controller.events_now_playing
+                            Layout.fillWidth: true
+                            color: "#e0e0e0"
+                            background: Rectangle { color: "#0f3460"; radius: 4 }
+                            onTextChanged: { controller.events_now_playing = text; controller.applySettings(); saveSettings() }
+                        }
+                    }
+                    Switch {
+                        text: "Announce AFK"
+                        checked: controller.events_afk
+                        onCheckedChanged: { controller.events_afk = checked; controller.applySettings(); saveSettings() }
+                        palette.text: "#c0c0c0"
+                    }
+                    Label {
+                        text: "Events replace the modules for a few seconds. Heart rate alerts are set in its section."
+                        color: "#808080"
+                        wrapMode: Text.Wrap
+                        Layout.fillWidth: true
+                    }
                 }
             }
 
@@ -0,0 +1,365 @@
+//! Fitting module output into VRChat's chatbox limits.
+//!
+//! VRChat counts message length in UTF-16 code units, so characters outside
+//! the Basic Multilingual Plane (most emoji) take two of the available slots.
+
+const ELLIPSIS: char = '\u{2026}';
+
+/// How a module's output competes for space in the chatbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleBudget {
+    /// Higher priority modules keep their space when the message is too long.
+    pub priority: u8,
+    /// Output abbreviated below this length is dropped instead.
+    pub min_len: usize,
+    /// Output longer than this is always abbreviated.
+    pub max_len: usize,
+}
+
+impl ModuleBudget {
+    pub const fn new(priority: u8, min_len: usize, max_len: usize) -> Self {
+        Self {
+            priority,
+            min_len,
+            max_len,
+        }
+    }
+}
+
+/// Room available in a message, in chatbox units and lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub chars: usize,
+    pub lines: usize,
+}
+
+impl Limits {
+    pub fn fits(&self, text: &str) -> bool {
+        text_len(text) <= self.chars && line_count(text) <= self.lines
+    }
+
+    /// What is left after `text` has been placed, followed by a line break
+    /// if anything else comes after it.
+    pub fn after(&self, text: &str) -> Limits {
+        if text.is_empty() {
+            return *self;
+        }
+        Limits {
+            chars: self.chars.saturating_sub(text_len(text) + 1),
+            lines: self.lines.saturating_sub(line_count(text)),
+        }
+    }
+}
+
+/// Length of `text` as counted by the chatbox.
+pub fn text_len(text: &str) -> usize {
+    text.chars().map(char::len_utf16).sum()
+}
+
+pub fn line_count(text: &str) -> usize {
+    if text.is_empty() {
+        0
+    } else {
+        text.lines().count()
+    }
+}
+
+/// Characters that attach to the preceding one and must not be separated
+/// from it: combining marks, variation selectors and zero-width joiners.
+fn is_attached(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{200D}'
+        | '\u{E0100}'..='\u{E01EF}'
+        | '\u{1F3FB}'..='\u{1F3FF}')
+}
+
+/// Byte index at which `text` can be cut so the kept prefix is at most `max`
+/// units long without splitting a character from its combining marks.
+fn cut_index(text: &str, max: usize) -> usize {
+    let mut used = 0;
+    let mut cut = 0;
+
+    for (i, c) in text.char_indices() {
+        let next = used + c.len_utf16();
+        if next > max {
+            break;
+        }
+        used = next;
+        if !text[i + c.len_utf8()..]
+            .chars()
+            .next()
+            .is_some_and(is_attached)
+        {
+            cut = i + c.len_utf8();
+        }
+    }
+
+    // A trailing zero-width joiner would glue the ellipsis onto an emoji
+    text[..cut].trim_end_matches('\u{200D}').len()
+}
+
+/// Hard cut to at most `max` units, used only as a last resort.
+pub fn truncate(text: &str, max: usize) -> &str {
+    if text_len(text) <= max {
+        text
+    } else {
+        &text[..cut_index(text, max)]
+    }
+}
+
+/// Shortens `text` to at most `max` units, preferring a word boundary and
+/// marking the cut with an ellipsis. Returns `None` if nothing meaningful
+/// fits.
+pub fn abbreviate(text: &str, max: usize) -> Option<String> {
+    if text_len(text) <= max {
+        return Some(text.to_string());
+    }
+    if max <= ELLIPSIS.len_utf16() {
+        return None;
+    }
+
+    let cut = cut_index(text, max - ELLIPSIS.len_utf16());
+    let kept = &text[..cut];
+
+    // Back up to the last whitespace if that doesn't throw away too much
+    let kept = match kept.rfind(char::is_whitespace) {
+        Some(ws) if text_len(&kept[..ws]) * 2 >= text_len(kept) => &kept[..ws],
+        _ => kept,
+    };
+    let kept = kept.trim_end();
+
+    if kept.is_empty() {
+        None
+    } else {
+        Some(format!("{kept}{ELLIPSIS}"))
+    }
+}
+
+/// One module's output competing for space.
+pub struct Entry {
+    pub budget: ModuleBudget,
+    pub text: Option<String>,
+    /// Units taken off `text` to make room. Layout placeholders showing a
+    /// part of the output give up the same amount through [`shrink`].
+    pub shrunk_by: usize,
+}
+
+impl Entry {
+    pub fn new(budget: ModuleBudget, text: Option<String>) -> Self {
+        Self {
+            budget,
+            text,
+            shrunk_by: 0,
+        }
+    }
+}
+
+/// Abbreviates `text` by `by` units, or returns it as is when `by` is 0.
+pub fn shrink(text: &str, by: usize) -> Option<String> {
+    if by == 0 {
+        return Some(text.to_string());
+    }
+    abbreviate(text, text_len(text).saturating_sub(by))
+}
+
+/// Makes room for the composed message by shrinking the lowest priority
+/// entries first. `compose` renders the message from the current entries;
+/// entries are abbreviated down to their minimum length, then dropped whole,
+/// until the result fits `limits`.
+pub fn fit<F>(entries: &mut [Entry], limits: Limits, compose: F) -> String
+where
+    F: Fn(&[Entry]) -> String,
+{
+    for entry in entries.iter_mut() {
+        if let Some(text) = entry.text.take() {
+            entry.text = abbreviate(&text, entry.budget.max_len)
+                .filter(|t| text_len(t) >= entry.budget.min_len.min(text_len(&text)));
+            if let Some(shortened) = &entry.text {
+                entry.shrunk_by += text_len(&text) - text_len(shortened);
+            }
+        }
+    }
+
+    loop {
+        let composed = compose(entries);
+        if limits.fits(&composed) {
+            return composed;
+        }
+
+        let victim = entries
+            .iter_mut()
+            .filter(|e| e.text.is_some())
+            .min_by_key(|e| e.budget.priority);
+        let Some(victim) = victim else {
+            return composed;
+        };
+
+        let text = victim.text.take().unwrap_or_default();
+        if line_count(&composed) > limits.lines {
+            // Shortening can't remove line breaks, so give up the whole entry
+            continue;
+        }
+
+        let overflow = text_len(&composed) - limits.chars;
+        let target = text_len(&text).saturating_sub(overflow);
+        victim.text = abbreviate(&text, target)
+            .filter(|t| t != &text && text_len(t) >= victim.budget.min_len);
+        if let Some(shortened) = &victim.text {
+            victim.shrunk_by += text_len(&text) - text_len(shortened);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAMILY: &str = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+
+    fn entry(priority: u8, min_len: usize, text: &str) -> Entry {
+        Entry::new(
+            ModuleBudget::new(priority, min_len, 100),
+            Some(text.to_string()),
+        )
+    }
+
+    fn stack(entries: &[Entry]) -> String {
+        entries
+            .iter()
+            .filter_map(|e| e.text.as_deref())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn counts_utf16_units() {
+        assert_eq!(text_len("abc"), 3);
+        assert_eq!(text_len("\u{1F600}"), 2);
+        assert_eq!(text_len("e\u{0301}"), 2);
+        assert_eq!(text_len(FAMILY), 8);
+        assert_eq!(line_count(""), 0);
+        assert_eq!(line_count("a\nb"), 2);
+    }
+
+    #[test]
+    fn cuts_between_whole_characters() {
+        // Never half of a surrogate pair
+        assert_eq!(truncate("a\u{1F600}b", 2), "a");
+        // Combining marks stay with their base character
+        assert_eq!(truncate("ae\u{0301}x", 2), "a");
+        assert_eq!(truncate("ae\u{0301}x", 3), "ae\u{0301}");
+        // Skin tones and variation selectors too
+        assert_eq!(truncate("\u{1F44D}\u{1F3FD}!", 3), "");
+        assert_eq!(truncate("\u{2764}\u{FE0F}!", 2), "\u{2764}\u{FE0F}");
+        // A ZWJ sequence can only break at a joiner, which is dropped
+        assert_eq!(truncate(FAMILY, 5), "\u{1F468}");
+        assert_eq!(truncate(FAMILY, 8), FAMILY);
+        assert_eq!(cut_index(FAMILY, 1), 0);
+    }
+
+    #[test]
+    fn abbreviates_at_word_boundaries() {
+        assert_eq!(abbreviate("short", 10).as_deref(), Some("short"));
+        assert_eq!(
+            abbreviate("the quick brown fox", 12).as_deref(),
+            Some("the quick\u{2026}")
+        );
+        // Too little would be left before the space, so the word is cut
+        assert_eq!(
+            abbreviate("a verylongword", 10).as_deref(),
+            Some("a verylon\u{2026}")
+        );
+        assert_eq!(
+            abbreviate("\u{1F600}\u{1F600}\u{1F600}", 4).as_deref(),
+            Some("\u{1F600}\u{2026}")
+        );
+        assert_eq!(
+            abbreviate(&format!("{FAMILY} family"), 4).as_deref(),
+            Some("\u{1F468}\u{2026}")
+        );
+        assert_eq!(abbreviate("abc def", 1), None);
+        assert_eq!(shrink("hello world", 0).as_deref(), Some("hello world"));
+        assert_eq!(shrink("hello world", 4).as_deref(), Some("hello\u{2026}"));
+    }
+
+    #[test]
+    fn fit_shortens_lowest_priority_first() {
+        let mut entries = vec![
+            entry(90, 4, "important status line"),
+            entry(10, 4, "some long song title here"),
+        ];
+        let limits = Limits {
+            chars: 40,
+            lines: 9,
+        };
+        let text = fit(&mut entries, limits, stack);
+        assert!(limits.fits(&text));
+        assert_eq!(entries[0].text.as_deref(), Some("important status line"));
+        assert_eq!(entries[1].text.as_deref(), Some("some long song\u{2026}"));
+        assert_eq!(entries[1].shrunk_by, 10);
+    }
+
+    #[test]
+    fn fit_drops_entries_below_min_len() {
+        let mut entries = vec![entry(90, 4, "keep me"), entry(10, 20, "drop this one")];
+        let text = fit(
+            &mut entries,
+            Limits {
+                chars: 12,
+                lines: 9,
+            },
+            stack,
+        );
+        assert_eq!(text, "keep me");
+        assert_eq!(entries[1].text, None);
+    }
+
+    #[test]
+    fn fit_drops_whole_entries_over_the_line_limit() {
+        let mut entries = vec![
+            entry(90, 1, "one"),
+            entry(50, 1, "two\nthree"),
+            entry(10, 1, "four"),
+        ];
+        let text = fit(
+            &mut entries,
+            Limits {
+                chars: 100,
+                lines: 2,
+            },
+            stack,
+        );
+        // Shortening can't remove a line, so the lowest priority goes first
+        assert_eq!(text, "one");
+        assert_eq!(entries[2].text, None);
+        assert_eq!(entries[1].text, None);
+    }
+
+    #[test]
+    fn fit_applies_max_len_first() {
+        let mut entries = vec![Entry::new(
+            ModuleBudget::new(50, 4, 8),
+            Some("\u{1F3B5} long title".to_string()),
+        )];
+        let text = fit(
+            &mut entries,
+            Limits {
+                chars: 100,
+                lines: 9,
+            },
+            stack,
+        );
+        assert_eq!(text, "\u{1F3B5} long\u{2026}");
+        assert_eq!(entries[0].shrunk_by, 5);
+        assert_eq!(
+            shrink("\u{1F3B5} long title", entries[0].shrunk_by).as_deref(),
+            Some("\u{1F3B5} long\u{2026}")
+        );
+    }
+}
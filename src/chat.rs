@@ -0,0 +1,251 @@
+//! Queue of user chat messages, split into pages that each stay on screen
+//! long enough to be read.
+
+use crate::budget::{text_len, truncate};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const MIN_PAGE_DURATION: Duration = Duration::from_secs(8);
+const MAX_PAGE_DURATION: Duration = Duration::from_secs(20);
+const BASE_READING_TIME: Duration = Duration::from_secs(3);
+/// Roughly 15 characters per second of comfortable reading.
+const READING_TIME_PER_CHAR: Duration = Duration::from_millis(70);
+/// Room reserved for the longest indicator we expect, e.g. " (10/12)".
+const INDICATOR_RESERVE: usize = 8;
+
+struct PagedMessage {
+    pages: Vec<String>,
+    index: usize,
+    shown_at: Instant,
+}
+
+impl PagedMessage {
+    fn duration(&self) -> Duration {
+        reading_time(&self.pages[self.index])
+    }
+}
+
+pub struct ChatQueue {
+    queue: VecDeque<String>,
+    current: Option<PagedMessage>,
+    page_len: usize,
+    show_indicator: bool,
+    /// Set when a message starts showing, until `take_started`.
+    started: bool,
+}
+
+impl ChatQueue {
+    pub fn new(page_len: usize) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            current: None,
+            page_len,
+            show_indicator: true,
+            started: false,
+        }
+    }
+
+    /// Whether multi-page messages end with a "(1/3)" style indicator.
+    pub fn set_show_indicator(&mut self, show: bool) {
+        self.show_indicator = show;
+    }
+
+    pub fn push(&mut self, text: String) {
+        let text = text.trim();
+        if !text.is_empty() {
+            self.queue.push_back(text.to_string());
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.current = None;
+        self.started = false;
+    }
+
+    /// Whether a new message started showing since the last call.
+    pub fn take_started(&mut self) -> bool {
+        std::mem::take(&mut self.started)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.current.is_none() && self.queue.is_empty()
+    }
+
+    /// The page to show right now, advancing to the next page or message once
+    /// the current one has been up for its reading time.
+    pub fn current(&mut self) -> Option<String> {
+        if let Some(msg) = &mut self.current {
+            if msg.shown_at.elapsed() >= msg.duration() {
+                msg.index += 1;
+                msg.shown_at = Instant::now();
+                if msg.index >= msg.pages.len() {
+                    self.current = None;
+                }
+            }
+        }
+
+        if self.current.is_none() {
+            let text = self.queue.pop_front()?;
+            self.current = Some(PagedMessage {
+                pages: self.paginate(&text),
+                index: 0,
+                shown_at: Instant::now(),
+            });
+            self.started = true;
+        }
+
+        let msg = self.current.as_ref()?;
+        let page = &msg.pages[msg.index];
+        if self.show_indicator && msg.pages.len() > 1 {
+            Some(format!("{page} ({}/{})", msg.index + 1, msg.pages.len()))
+        } else {
+            Some(page.clone())
+        }
+    }
+
+    fn paginate(&self, text: &str) -> Vec<String> {
+        if text_len(text) <= self.page_len {
+            return vec![text.to_string()];
+        }
+
+        let limit = if self.show_indicator {
+            self.page_len.saturating_sub(INDICATOR_RESERVE).max(1)
+        } else {
+            self.page_len
+        };
+        split_pages(text, limit)
+    }
+}
+
+/// Splits `text` into pages of at most `limit` units, breaking between words
+/// and only inside a word when it doesn't fit on a page by itself.
+fn split_pages(text: &str, limit: usize) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::new();
+
+    for word in text.split_whitespace() {
+        let sep = usize::from(!page.is_empty());
+        if text_len(&page) + sep + text_len(word) <= limit {
+            if sep == 1 {
+                page.push(' ');
+            }
+            page.push_str(word);
+            continue;
+        }
+
+        if !page.is_empty() {
+            pages.push(std::mem::take(&mut page));
+        }
+
+        let mut rest = word;
+        while text_len(rest) > limit {
+            let head = truncate(rest, limit);
+            if head.is_empty() {
+                break;
+            }
+            pages.push(head.to_string());
+            rest = &rest[head.len()..];
+        }
+        page.push_str(rest);
+    }
+
+    if !page.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+fn reading_time(page: &str) -> Duration {
+    let chars = text_len(page) as u32;
+    (BASE_READING_TIME + READING_TIME_PER_CHAR * chars).clamp(MIN_PAGE_DURATION, MAX_PAGE_DURATION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Moves the current page's start past any reading time.
+    fn wait(queue: &mut ChatQueue) {
+        if let Some(msg) = queue.current.as_mut() {
+            msg.shown_at -= MAX_PAGE_DURATION;
+        }
+    }
+
+    #[test]
+    fn splits_between_words() {
+        assert_eq!(
+            split_pages("the quick brown fox jumps", 10),
+            ["the quick", "brown fox", "jumps"]
+        );
+        assert_eq!(split_pages("  spaced   out  ", 10), ["spaced out"]);
+    }
+
+    #[test]
+    fn splits_words_longer_than_a_page() {
+        assert_eq!(
+            split_pages("a supercalifragilistic word", 8),
+            ["a", "supercal", "ifragili", "stic", "word"]
+        );
+        // Surrogate pairs count as two units and aren't cut in half
+        assert_eq!(split_pages("😀😀😀", 3), ["😀", "😀", "😀"]);
+    }
+
+    #[test]
+    fn counts_the_indicator_in_the_page_length() {
+        let mut queue = ChatQueue::new(20);
+        queue.push("one two three four five six seven eight nine".to_string());
+
+        let mut pages = Vec::new();
+        while let Some(page) = queue.current() {
+            assert!(text_len(&page) <= 20, "{page:?} is too long");
+            pages.push(page);
+            wait(&mut queue);
+            if pages.len() > 10 {
+                break;
+            }
+        }
+        assert_eq!(pages.len(), 5);
+        assert_eq!(pages[0], "one two (1/5)");
+        assert_eq!(pages[4], "nine (5/5)");
+
+        queue.set_show_indicator(false);
+        queue.push("one two three four five six seven eight nine".to_string());
+        assert_eq!(queue.current().as_deref(), Some("one two three four"));
+    }
+
+    #[test]
+    fn plays_messages_in_order() {
+        let mut queue = ChatQueue::new(20);
+        queue.push("first".to_string());
+        queue.push("   ".to_string());
+        queue.push("second one that spans pages".to_string());
+        assert!(!queue.take_started());
+
+        assert_eq!(queue.current().as_deref(), Some("first"));
+        assert!(queue.take_started());
+        assert!(!queue.take_started());
+
+        wait(&mut queue);
+        assert_eq!(queue.current().as_deref(), Some("second one (1/3)"));
+        assert!(queue.take_started());
+
+        // Later pages of the same message don't count as a new message
+        wait(&mut queue);
+        assert_eq!(queue.current().as_deref(), Some("that spans (2/3)"));
+        assert!(!queue.take_started());
+        wait(&mut queue);
+        assert_eq!(queue.current().as_deref(), Some("pages (3/3)"));
+
+        wait(&mut queue);
+        assert_eq!(queue.current(), None);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn reading_time_grows_with_length() {
+        assert_eq!(reading_time("hi"), MIN_PAGE_DURATION);
+        assert_eq!(reading_time(&"a".repeat(100)), Duration::from_secs(10));
+        assert_eq!(reading_time(&"a".repeat(500)), MAX_PAGE_DURATION);
+    }
+}
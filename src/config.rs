@@ -0,0 +1,1192 @@
+//! Settings shared by the GUI and the headless runner, stored as TOML under
+//! `$XDG_CONFIG_HOME/osc-chatbox/`.
+
+use chrono::format::{Item, StrftimeItems};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use crate::budget::ModuleBudget;
+use crate::modules::gpu::GpuBackend;
+use crate::modules::network::{self, RateUnit, UnitPrefix};
+use crate::modules::stats::{self, LabelStyle, Metric};
+use crate::modules::system_details::{self, Section, TextStyle};
+use crate::modules::{heartrate, media, sensors, status};
+use crate::orchestrator;
+use crate::osc::receiver;
+use crate::profiles;
+use crate::rules::Rule;
+use crate::template::Template;
+use crate::worker;
+
+pub const APP_DIR: &str = "osc-chatbox";
+const CONFIG_FILE: &str = "config.toml";
+/// Settings written by the QML `Settings` block before the config file existed.
+const QT_SETTINGS_FILE: &str = "osc-chatbox.conf";
+const QT_SETTINGS_GROUP: &str = "osc_chatbox";
+
+/// Bumped whenever a stored field changes meaning; see [`migrate`].
+pub const CONFIG_VERSION: u32 = 2;
+pub const STATUS_LINES: usize = 6;
+/// A longer bar wouldn't leave room for anything else in the chatbox.
+const MAX_BAR_LENGTH: usize = 40;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        message: String,
+    },
+    Serialize(String),
+    UnsupportedVersion(u32),
+    Invalid {
+        field: &'static str,
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            ConfigError::Parse { path, message } => write!(f, "{}: {message}", path.display()),
+            ConfigError::Serialize(message) => write!(f, "failed to write config: {message}"),
+            ConfigError::UnsupportedVersion(v) => write!(
+                f,
+                "config version {v} is newer than this build supports ({CONFIG_VERSION})"
+            ),
+            ConfigError::Invalid { field, message } => write!(f, "{field}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub version: u32,
+    pub osc: OscConfig,
+    /// Name of the profile the settings below were last switched to or saved
+    /// as; empty when no profile is in use.
+    pub active_profile: String,
+    pub automation: AutomationConfig,
+    pub logging: LoggingConfig,
+    #[serde(flatten)]
+    pub profile: Profile,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// One of error, warn, info, debug or trace. Messages sent to VRChat
+    /// are logged at debug.
+    pub level: String,
+    /// Also log to a daily rotated file in `$XDG_STATE_HOME/osc-chatbox`.
+    pub file: bool,
+    /// Log chat messages as `[chat]` instead of what was typed.
+    pub redact_chat: bool,
+}
+
+/// Rules that switch to another profile while their conditions hold. The
+/// settings above apply whenever no rule matches.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutomationConfig {
+    pub enabled: bool,
+    /// How long a different rule has to keep matching before switching.
+    pub hold_secs: u64,
+    pub rules: Vec<RuleConfig>,
+}
+
+/// A profile to switch to and the conditions that must all hold for it.
+/// Conditions left out are not checked; the first matching rule wins.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleConfig {
+    pub profile: String,
+    /// Local time range such as `22:00-06:00`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_playing: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub afk: Option<bool>,
+    /// VRChat avatar ID, e.g. `avtr_...`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+    /// Name of a running process, e.g. `obs`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process: Option<String>,
+}
+
+/// Everything that differs between setups such as "streaming" or "quiet".
+/// Stored inline in the config file and as named files under `profiles/`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// Layout template; empty stacks every enabled module.
+    pub layout: String,
+    pub chat: ChatConfig,
+    pub status: StatusConfig,
+    pub time: TimeConfig,
+    pub stats: StatsConfig,
+    pub network: NetworkConfig,
+    pub gpu: GpuConfig,
+    pub sensors: SensorsConfig,
+    pub media: MediaConfig,
+    pub lyrics: LyricsConfig,
+    pub afk: AfkConfig,
+    pub system_details: SystemDetailsConfig,
+    pub heartrate: HeartrateConfig,
+    pub events: EventsConfig,
+    /// Overrides of how much room each module gets, by module id.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub budgets: BTreeMap<String, BudgetConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OscConfig {
+    pub target: String,
+    /// Where VRChat's OSC output is received; empty disables listening.
+    pub listen: String,
+    /// Milliseconds between chatbox updates.
+    pub interval_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatConfig {
+    pub page_indicator: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusConfig {
+    pub enabled: bool,
+    pub lines: Vec<String>,
+    /// Seconds each line stays up before the next one.
+    pub rotation_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeConfig {
+    pub enabled: bool,
+    pub format: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatsConfig {
+    pub enabled: bool,
+    /// Stats in display order, each a name with options, e.g. `cpu`,
+    /// `ram gib`, `disk /home percent` or `load label=Load`.
+    pub metrics: Vec<String>,
+    /// `upper`, `smallcaps` or `none`.
+    pub label_style: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    pub enabled: bool,
+    /// `bytes` or `bits` per second.
+    pub unit: String,
+    /// `si` for kB (1000) or `iec` for KiB (1024).
+    pub prefix: String,
+    /// Weight of the previous average against each new sample, 0 to just
+    /// below 1; 0 shows raw samples.
+    pub smoothing: f64,
+    /// Interfaces to count, matched by prefix; empty counts all of them.
+    pub interfaces: Vec<String>,
+    /// Interfaces never counted, matched by prefix.
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GpuConfig {
+    pub enabled: bool,
+    /// `auto`, `sysfs` or `nvidia-smi`.
+    pub backend: String,
+    /// Where sysfs is mounted; empty uses `/sys`.
+    pub sysfs_root: String,
+    /// DRM card such as `card1`; empty uses the first one with stats.
+    pub card: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensorsConfig {
+    pub enabled: bool,
+    /// Where sysfs is mounted; empty uses `/sys`.
+    pub sysfs_root: String,
+    /// Sensors to show as `label` or `chip/label`, e.g. `Tctl` or
+    /// `nvme/Composite`; `cpu` is the CPU package. Empty shows the CPU.
+    pub labels: Vec<String>,
+    /// Show every spinning fan.
+    pub fans: bool,
+    pub fahrenheit: bool,
+    /// Degrees Celsius at which a temperature is highlighted; 0 never does.
+    pub warn_above: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MediaConfig {
+    pub enabled: bool,
+    /// D-Bus address to find MPRIS players on; empty uses the session bus.
+    pub bus_address: String,
+    /// Player names in order of preference, matched by prefix. A playing
+    /// player always wins over paused ones.
+    pub players: Vec<String>,
+    /// Players that are never shown, matched by prefix.
+    pub blocklist: Vec<String>,
+    /// Output template; empty uses the default two-line layout.
+    pub template: String,
+    /// Output templates for specific players; the first match is used.
+    pub templates: Vec<PlayerTemplate>,
+    pub bar_length: usize,
+    pub bar_filled: String,
+    pub bar_empty: String,
+    /// Titles and artists longer than this are abbreviated; 0 never does.
+    pub title_max_len: usize,
+    pub artist_max_len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerTemplate {
+    pub player: String,
+    pub template: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AfkConfig {
+    pub enabled: bool,
+    pub timeout_secs: u64,
+    pub text: String,
+    pub source_osc: bool,
+    pub source_chat: bool,
+    pub source_idle: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SystemDetailsConfig {
+    pub enabled: bool,
+    /// Sections in rotation order, e.g. `os`, `kernel`, `uptime label=Up`.
+    pub sections: Vec<String>,
+    /// `superscript`, `smallcaps` or `plain`.
+    pub style: String,
+    pub lowercase: bool,
+    /// Seconds each section stays up before the next one.
+    pub rotation_secs: u64,
+    /// Where sysfs is mounted; empty uses `/sys`.
+    pub sysfs_root: String,
+    /// PCI ID database naming GPUs; empty searches the usual places.
+    pub pci_ids: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LyricsConfig {
+    pub enabled: bool,
+    /// Directory of `.lrc` files; empty uses `lyrics` in the config directory.
+    pub dir: String,
+}
+
+/// Unset values keep the module's default budget.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    /// Higher priority modules keep their space when the chatbox is full.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u8>,
+    /// Output that would be shortened below this length is dropped instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_len: Option<usize>,
+    /// Output longer than this is always shortened.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_len: Option<usize>,
+}
+
+impl BudgetConfig {
+    pub fn apply(&self, budget: ModuleBudget) -> ModuleBudget {
+        ModuleBudget::new(
+            self.priority.unwrap_or(budget.priority),
+            self.min_len.unwrap_or(budget.min_len),
+            self.max_len.unwrap_or(budget.max_len),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeartrateConfig {
+    pub enabled: bool,
+    pub token: String,
+    pub url: String,
+    /// BPM above which an event is raised; 0 disables it.
+    pub alert_above: u32,
+    /// BPM below which an event is raised; 0 disables it.
+    pub alert_below: u32,
+}
+
+/// Announcements that take over the chatbox for a few seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventsConfig {
+    pub enabled: bool,
+    /// Seconds an event is shown for.
+    pub duration_secs: u64,
+    /// Seconds before the same event can be shown again.
+    pub cooldown_secs: u64,
+    /// Announce new tracks.
+    pub media: bool,
+    /// Template for new tracks; empty uses the default.
+    pub now_playing: String,
+    /// Announce going AFK and coming back.
+    pub afk: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            osc: OscConfig::default(),
+            active_profile: String::new(),
+            automation: AutomationConfig::default(),
+            logging: LoggingConfig::default(),
+            profile: Profile::default(),
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            file: true,
+            redact_chat: true,
+        }
+    }
+}
+
+impl Default for AutomationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hold_secs: 10,
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl Default for OscConfig {
+    fn default() -> Self {
+        Self {
+            target: "127.0.0.1:9000".to_string(),
+            listen: receiver::DEFAULT_LISTEN_ADDR.to_string(),
+            interval_ms: 1500,
+        }
+    }
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            page_indicator: true,
+        }
+    }
+}
+
+impl Default for TimeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            format: "%H:%M".to_string(),
+        }
+    }
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            metrics: stats::DEFAULT_METRICS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            label_style: "upper".to_string(),
+        }
+    }
+}
+
+impl Default for SystemDetailsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sections: system_details::DEFAULT_SECTIONS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            style: "superscript".to_string(),
+            lowercase: true,
+            rotation_secs: system_details::DEFAULT_ROTATION.as_secs(),
+            sysfs_root: String::new(),
+            pci_ids: String::new(),
+        }
+    }
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lines: Vec::new(),
+            rotation_secs: status::DEFAULT_ROTATION.as_secs(),
+        }
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            unit: "bytes".to_string(),
+            prefix: "si".to_string(),
+            smoothing: network::DEFAULT_SMOOTHING,
+            interfaces: Vec::new(),
+            exclude: network::DEFAULT_EXCLUDE
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: "auto".to_string(),
+            sysfs_root: String::new(),
+            card: String::new(),
+        }
+    }
+}
+
+impl Default for SensorsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sysfs_root: String::new(),
+            labels: Vec::new(),
+            fans: false,
+            fahrenheit: false,
+            warn_above: sensors::DEFAULT_WARN_ABOVE,
+        }
+    }
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bus_address: String::new(),
+            players: vec!["spotify".to_string(), "spotifyd".to_string()],
+            blocklist: Vec::new(),
+            template: media::DEFAULT_TEMPLATE.to_string(),
+            templates: Vec::new(),
+            bar_length: media::DEFAULT_BAR_LENGTH,
+            bar_filled: media::DEFAULT_BAR_FILLED.to_string(),
+            bar_empty: media::DEFAULT_BAR_EMPTY.to_string(),
+            title_max_len: 0,
+            artist_max_len: 0,
+        }
+    }
+}
+
+impl Default for AfkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_secs: 300,
+            text: "AFK".to_string(),
+            source_osc: true,
+            source_chat: true,
+            source_idle: false,
+        }
+    }
+}
+
+impl Default for HeartrateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            token: String::new(),
+            url: heartrate::DEFAULT_URL.to_string(),
+            alert_above: 0,
+            alert_below: 0,
+        }
+    }
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            duration_secs: 5,
+            cooldown_secs: 60,
+            media: true,
+            now_playing: media::DEFAULT_ANNOUNCE_TEMPLATE.to_string(),
+            afk: true,
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/osc-chatbox`, falling back to `~/.config/osc-chatbox`.
+pub fn config_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join(APP_DIR)
+}
+
+impl Config {
+    pub fn default_path() -> PathBuf {
+        config_dir().join(CONFIG_FILE)
+    }
+
+    /// Reads, migrates and validates a config file.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let parse_error = |message: String| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let value: toml::Table = toml::from_str(&text).map_err(|e| parse_error(e.to_string()))?;
+        let config: Config = migrate(value)?
+            .try_into()
+            .map_err(|e: toml::de::Error| parse_error(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Loads the config at `path`. When it doesn't exist yet, settings saved
+    /// by older versions of the GUI are imported, otherwise defaults are used.
+    pub fn load_or_default(path: &Path) -> Result<Self, ConfigError> {
+        if path.exists() {
+            return Self::load(path);
+        }
+
+        let qt_settings = path
+            .parent()
+            .map(|dir| dir.join(QT_SETTINGS_FILE))
+            .and_then(|p| fs::read_to_string(p).ok());
+        Ok(qt_settings
+            .map(|text| from_qt_settings(&text))
+            .unwrap_or_default())
+    }
+
+    /// Validates and writes the config, replacing the file atomically.
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        self.validate()?;
+
+        let io_error = |source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        let text =
+            toml::to_string_pretty(self).map_err(|e| ConfigError::Serialize(e.to_string()))?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let tmp = path.with_extension("toml.tmp");
+        fs::write(&tmp, text).map_err(io_error)?;
+        fs::rename(&tmp, path).map_err(io_error)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |field, message: String| Err(ConfigError::Invalid { field, message });
+
+        if let Err(message) = validate_target(&self.osc.target) {
+            return invalid("osc.target", message);
+        }
+        let listen = self.osc.listen.trim();
+        if !listen.is_empty() && listen.parse::<SocketAddr>().is_err() {
+            return invalid(
+                "osc.listen",
+                format!("'{listen}' is not an address like 127.0.0.1:9001"),
+            );
+        }
+        if self.osc.interval_ms < worker::MIN_INTERVAL.as_millis() as u64 {
+            return invalid(
+                "osc.interval_ms",
+                format!("must be at least {} ms", worker::MIN_INTERVAL.as_millis()),
+            );
+        }
+        for rule in &self.automation.rules {
+            if let Err(message) = profiles::validate_name(&rule.profile) {
+                return invalid("automation.rules.profile", message);
+            }
+            if let Err(message) = Rule::from_config(rule) {
+                return invalid("automation.rules", message);
+            }
+        }
+        if self.logging.level.trim().parse::<tracing::Level>().is_err() {
+            return invalid(
+                "logging.level",
+                format!(
+                    "'{}' is not one of error, warn, info, debug or trace",
+                    self.logging.level
+                ),
+            );
+        }
+        self.profile.validate()
+    }
+}
+
+impl Profile {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |field, message: String| Err(ConfigError::Invalid { field, message });
+
+        if !self.layout.trim().is_empty() {
+            if let Err(e) = Template::parse(&self.layout) {
+                return invalid("layout", e.to_string());
+            }
+        }
+        if self.status.lines.len() > STATUS_LINES {
+            return invalid(
+                "status.lines",
+                format!("at most {STATUS_LINES} lines are supported"),
+            );
+        }
+        if self.status.rotation_secs == 0 {
+            return invalid(
+                "status.rotation_secs",
+                "must be at least 1 second".to_string(),
+            );
+        }
+        if self.system_details.rotation_secs == 0 {
+            return invalid(
+                "system_details.rotation_secs",
+                "must be at least 1 second".to_string(),
+            );
+        }
+        if StrftimeItems::new(&self.time.format).any(|item| matches!(item, Item::Error)) {
+            return invalid(
+                "time.format",
+                format!("'{}' is not a valid strftime format", self.time.format),
+            );
+        }
+        for metric in &self.stats.metrics {
+            if let Err(message) = Metric::parse(metric) {
+                return invalid("stats.metrics", message);
+            }
+        }
+        if LabelStyle::parse(&self.stats.label_style).is_none() {
+            return invalid(
+                "stats.label_style",
+                format!(
+                    "'{}' is not one of upper, smallcaps or none",
+                    self.stats.label_style
+                ),
+            );
+        }
+        if RateUnit::parse(&self.network.unit).is_none() {
+            return invalid(
+                "network.unit",
+                format!("'{}' is not bytes or bits", self.network.unit),
+            );
+        }
+        if UnitPrefix::parse(&self.network.prefix).is_none() {
+            return invalid(
+                "network.prefix",
+                format!("'{}' is not si or iec", self.network.prefix),
+            );
+        }
+        if !(0.0..1.0).contains(&self.network.smoothing) {
+            return invalid(
+                "network.smoothing",
+                "must be at least 0 and below 1".to_string(),
+            );
+        }
+        if GpuBackend::parse(&self.gpu.backend).is_none() {
+            return invalid(
+                "gpu.backend",
+                format!(
+                    "'{}' is not one of auto, sysfs or nvidia-smi",
+                    self.gpu.backend
+                ),
+            );
+        }
+        if self.sensors.warn_above < 0.0 {
+            return invalid("sensors.warn_above", "can't be negative".to_string());
+        }
+        for section in &self.system_details.sections {
+            if let Err(message) = Section::parse(section) {
+                return invalid("system_details.sections", message);
+            }
+        }
+        if TextStyle::parse(&self.system_details.style).is_none() {
+            return invalid(
+                "system_details.style",
+                format!(
+                    "'{}' is not one of superscript, smallcaps or plain",
+                    self.system_details.style
+                ),
+            );
+        }
+        if let Err(e) = Template::parse(&self.media.template) {
+            return invalid("media.template", e.to_string());
+        }
+        if !(1..=MAX_BAR_LENGTH).contains(&self.media.bar_length) {
+            return invalid(
+                "media.bar_length",
+                format!("must be between 1 and {MAX_BAR_LENGTH}"),
+            );
+        }
+        if self.media.bar_filled.is_empty() || self.media.bar_empty.is_empty() {
+            return invalid("media.bar_filled", "bar glyphs can't be empty".to_string());
+        }
+        for player in &self.media.templates {
+            if player.player.trim().is_empty() {
+                return invalid("media.templates", "player name can't be empty".to_string());
+            }
+            if let Err(e) = Template::parse(&player.template) {
+                return invalid("media.templates", format!("{}: {e}", player.player));
+            }
+        }
+        if self.afk.timeout_secs == 0 {
+            return invalid("afk.timeout_secs", "must be at least 1 second".to_string());
+        }
+        if !self.heartrate.url.trim().is_empty() {
+            match url::Url::parse(self.heartrate.url.trim()) {
+                Ok(url) if matches!(url.scheme(), "ws" | "wss") => {}
+                Ok(url) => {
+                    return invalid(
+                        "heartrate.url",
+                        format!("unsupported scheme '{}', expected ws or wss", url.scheme()),
+                    )
+                }
+                Err(e) => return invalid("heartrate.url", e.to_string()),
+            }
+        }
+        let (above, below) = (self.heartrate.alert_above, self.heartrate.alert_below);
+        if above > 0 && below >= above {
+            return invalid(
+                "heartrate.alert_below",
+                "must be lower than alert_above".to_string(),
+            );
+        }
+        if self.events.duration_secs == 0 {
+            return invalid(
+                "events.duration_secs",
+                "must be at least 1 second".to_string(),
+            );
+        }
+        if let Err(e) = Template::parse(&self.events.now_playing) {
+            return invalid("events.now_playing", e.to_string());
+        }
+        for (id, overrides) in &self.budgets {
+            let Some(budget) = orchestrator::default_budget(id) else {
+                return invalid("budgets", format!("'{id}' is not a module"));
+            };
+            let budget = overrides.apply(budget);
+            if budget.min_len > budget.max_len {
+                return invalid("budgets", format!("{id}: min_len can't be above max_len"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_target(target: &str) -> Result<(), String> {
+    let (host, port) = target
+        .trim()
+        .rsplit_once(':')
+        .ok_or_else(|| format!("'{target}' is missing a port, e.g. 127.0.0.1:9000"))?;
+    if host.is_empty() {
+        return Err(format!("'{target}' is missing a host"));
+    }
+    port.parse::<u16>()
+        .map(|_| ())
+        .map_err(|_| format!("'{port}' is not a valid port"))
+}
+
+/// Upgrades a parsed config table to [`CONFIG_VERSION`]. Files without a
+/// version predate versioning and share the version 1 layout.
+fn migrate(mut value: toml::Table) -> Result<toml::Table, ConfigError> {
+    let version = value
+        .get("version")
+        .and_then(toml::Value::as_integer)
+        .unwrap_or(1);
+    let version = u32::try_from(version).map_err(|_| ConfigError::Invalid {
+        field: "version",
+        message: format!("'{version}' is not a valid version"),
+    })?;
+
+    if version > CONFIG_VERSION {
+        return Err(ConfigError::UnsupportedVersion(version));
+    }
+    if version < 2 {
+        migrate_profile(&mut value);
+    }
+
+    value.insert(
+        "version".to_string(),
+        toml::Value::from(CONFIG_VERSION as i64),
+    );
+    Ok(value)
+}
+
+/// Upgrades settings stored in a profile, which the config file also holds
+/// at its top level. Profile files aren't versioned, so this only touches
+/// settings in an old layout.
+pub(crate) fn migrate_profile(profile: &mut toml::Table) {
+    // Version 2 replaced the stats `show_cpu` and `show_ram` switches with
+    // a list of metrics
+    if let Some(toml::Value::Table(stats)) = profile.get_mut("stats") {
+        let shown = [
+            ("cpu", stats.remove("show_cpu")),
+            ("ram", stats.remove("show_ram")),
+        ];
+        if shown.iter().any(|(_, show)| show.is_some()) && !stats.contains_key("metrics") {
+            let metrics = shown
+                .into_iter()
+                .filter(|(_, show)| show.as_ref().and_then(toml::Value::as_bool) != Some(false))
+                .map(|(metric, _)| toml::Value::from(metric))
+                .collect();
+            stats.insert("metrics".to_string(), toml::Value::Array(metrics));
+        }
+    }
+}
+
+/// Builds a config from the INI file Qt's `Settings` type wrote for the GUI
+/// before settings moved to the config file. Unknown or malformed entries
+/// keep their defaults; the result is validated when it is next saved.
+fn from_qt_settings(text: &str) -> Config {
+    let mut values: HashMap<&str, String> = HashMap::new();
+    let mut in_group = false;
+
+    for line in text.lines().map(str::trim) {
+        if let Some(group) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_group = group == QT_SETTINGS_GROUP;
+        } else if let Some((key, value)) = line.split_once('=') {
+            if in_group {
+                values.insert(key.trim(), unquote_qt_value(value.trim()));
+            }
+        }
+    }
+
+    let mut config = Config::default();
+    let string = |key: &str, target: &mut String| {
+        if let Some(v) = values.get(key) {
+            *target = v.clone();
+        }
+    };
+    let boolean = |key: &str, target: &mut bool| {
+        if let Some(v) = values.get(key).and_then(|v| v.parse().ok()) {
+            *target = v;
+        }
+    };
+
+    string("oscAddress", &mut config.osc.target);
+    string("oscListenAddress", &mut config.osc.listen);
+    string("layoutTemplate", &mut config.profile.layout);
+    boolean("chatPageIndicator", &mut config.profile.chat.page_indicator);
+    boolean("statusEnabled", &mut config.profile.status.enabled);
+    config.profile.status.lines = (1..=STATUS_LINES)
+        .map(|i| {
+            values
+                .get(format!("statusLine{i}").as_str())
+                .cloned()
+                .unwrap_or_default()
+        })
+        .collect();
+    boolean("timeEnabled", &mut config.profile.time.enabled);
+    string("timeFormat", &mut config.profile.time.format);
+    boolean("statsEnabled", &mut config.profile.stats.enabled);
+    let mut show = |key: &str, metric: &str| {
+        if values.get(key).is_some_and(|v| v == "false") {
+            config.profile.stats.metrics.retain(|m| m != metric);
+        }
+    };
+    show("statsShowCpu", "cpu");
+    show("statsShowRam", "ram");
+    boolean("networkEnabled", &mut config.profile.network.enabled);
+    boolean("mediaEnabled", &mut config.profile.media.enabled);
+    boolean("afkEnabled", &mut config.profile.afk.enabled);
+    if let Some(v) = values.get("afkTimeoutSecs").and_then(|v| v.parse().ok()) {
+        config.profile.afk.timeout_secs = v;
+    }
+    string("afkText", &mut config.profile.afk.text);
+    boolean("afkSourceOsc", &mut config.profile.afk.source_osc);
+    boolean("afkSourceChat", &mut config.profile.afk.source_chat);
+    boolean("afkSourceIdle", &mut config.profile.afk.source_idle);
+    boolean(
+        "systemDetailsEnabled",
+        &mut config.profile.system_details.enabled,
+    );
+    boolean("heartrateEnabled", &mut config.profile.heartrate.enabled);
+    string("heartrateToken", &mut config.profile.heartrate.token);
+    string("heartrateUrl", &mut config.profile.heartrate.url);
+
+    config
+}
+
+/// Undoes the quoting and backslash escapes QSettings applies to INI values.
+fn unquote_qt_value(value: &str) -> String {
+    let inner = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Fixture;
+
+    const V1_CONFIG: &str = r#"
+[osc]
+target = "192.168.1.20:9000"
+
+[stats]
+enabled = true
+show_cpu = false
+show_ram = true
+
+[time]
+enabled = true
+format = "%I:%M %p"
+"#;
+
+    const QT_SETTINGS: &str = r#"
+[General]
+oscAddress=10.0.0.1:1
+
+[osc_chatbox]
+oscAddress=192.168.1.20:9000
+layoutTemplate="{time}\\n{media}"
+statusEnabled=true
+statusLine1="Hello, world"
+statusLine3=\"quoted\"
+timeFormat=%H:%M:%S
+statsEnabled=true
+statsShowCpu=false
+afkTimeoutSecs=120
+afkText=brb
+afkSourceIdle=true
+heartrateToken=abc
+"#;
+
+    fn invalid_field(config: &Config) -> Option<&'static str> {
+        match config.validate() {
+            Err(ConfigError::Invalid { field, .. }) => Some(field),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn migrates_v1_config() {
+        let dir = Fixture::new("config-v1", &[("config.toml", V1_CONFIG)]);
+        let config = Config::load(&dir.join("config.toml")).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.osc.target, "192.168.1.20:9000");
+        assert!(config.profile.stats.enabled);
+        assert_eq!(config.profile.stats.metrics, ["ram"]);
+        assert_eq!(config.profile.time.format, "%I:%M %p");
+    }
+
+    #[test]
+    fn migration_keeps_existing_metrics() {
+        let mut profile: toml::Table =
+            toml::from_str("[stats]\nshow_cpu = false\nmetrics = [\"load\"]\n").unwrap();
+        migrate_profile(&mut profile);
+        let stats = profile["stats"].as_table().unwrap();
+        assert!(!stats.contains_key("show_cpu"));
+        assert_eq!(stats["metrics"].as_array().unwrap().len(), 1);
+
+        let mut profile: toml::Table = toml::from_str("[stats]\nshow_ram = true\n").unwrap();
+        migrate_profile(&mut profile);
+        let metrics: Vec<&str> = profile["stats"]["metrics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(toml::Value::as_str)
+            .collect();
+        assert_eq!(metrics, ["cpu", "ram"]);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let table: toml::Table = toml::from_str("version = 99").unwrap();
+        assert!(matches!(
+            migrate(table),
+            Err(ConfigError::UnsupportedVersion(99))
+        ));
+        let table: toml::Table = toml::from_str("version = -1").unwrap();
+        assert!(matches!(
+            migrate(table),
+            Err(ConfigError::Invalid {
+                field: "version",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn round_trips_through_the_file() {
+        let dir = Fixture::new("config-save", &[]);
+        let path = dir.join("nested/config.toml");
+
+        let mut config = Config {
+            active_profile: "Streaming".to_string(),
+            ..Config::default()
+        };
+        config.profile.layout = "{time} | {stats.disk_home}".to_string();
+        config.profile.status.lines =
+            vec!["line one".to_string(), "ünïcode \"quotes\"".to_string()];
+        config.profile.stats.metrics = vec!["disk /home percent".to_string(), "top".to_string()];
+        config.profile.system_details.sections = vec!["uptime label=Up".to_string()];
+        config.profile.budgets.insert(
+            "media".to_string(),
+            BudgetConfig {
+                max_len: Some(40),
+                ..BudgetConfig::default()
+            },
+        );
+        config.save(&path).unwrap();
+
+        assert!(!path.with_extension("toml.tmp").exists());
+        assert_eq!(Config::load(&path).unwrap(), config);
+        // Defaults stay out of the way of what was written
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("priority"));
+    }
+
+    #[test]
+    fn validates_fields() {
+        assert_eq!(invalid_field(&Config::default()), None);
+
+        let mut config = Config::default();
+        config.osc.target = "localhost".to_string();
+        assert_eq!(invalid_field(&config), Some("osc.target"));
+
+        let mut config = Config::default();
+        config.profile.layout = "{unclosed".to_string();
+        assert_eq!(invalid_field(&config), Some("layout"));
+
+        let mut config = Config::default();
+        config.profile.stats.metrics = vec!["disk /home tib".to_string()];
+        assert_eq!(invalid_field(&config), Some("stats.metrics"));
+
+        let mut config = Config::default();
+        config.profile.network.smoothing = 1.0;
+        assert_eq!(invalid_field(&config), Some("network.smoothing"));
+
+        let mut config = Config::default();
+        config.profile.status.rotation_secs = 0;
+        assert_eq!(invalid_field(&config), Some("status.rotation_secs"));
+
+        let mut config = Config::default();
+        config.profile.budgets.insert(
+            "media".to_string(),
+            BudgetConfig {
+                min_len: Some(100),
+                max_len: Some(10),
+                ..BudgetConfig::default()
+            },
+        );
+        assert_eq!(invalid_field(&config), Some("budgets"));
+
+        // Saving never writes an invalid file
+        let dir = Fixture::new("config-invalid", &[]);
+        assert!(config.save(&dir.join("config.toml")).is_err());
+        assert!(!dir.join("config.toml").exists());
+    }
+
+    #[test]
+    fn imports_qt_settings() {
+        let dir = Fixture::new("config-qt", &[(QT_SETTINGS_FILE, QT_SETTINGS)]);
+        let config = Config::load_or_default(&dir.join("config.toml")).unwrap();
+
+        assert_eq!(config.osc.target, "192.168.1.20:9000");
+        assert_eq!(config.profile.layout, "{time}\\n{media}");
+        assert!(config.profile.status.enabled);
+        assert_eq!(
+            config.profile.status.lines,
+            ["Hello, world", "", "\"quoted\"", "", "", ""]
+        );
+        assert_eq!(config.profile.time.format, "%H:%M:%S");
+        assert_eq!(config.profile.stats.metrics, ["ram"]);
+        assert_eq!(config.profile.afk.timeout_secs, 120);
+        assert_eq!(config.profile.afk.text, "brb");
+        assert!(config.profile.afk.source_idle);
+        assert!(config.profile.afk.source_osc);
+        assert_eq!(config.profile.heartrate.token, "abc");
+        assert_eq!(config.validate().ok(), Some(()));
+
+        let empty = Fixture::new("config-none", &[]);
+        assert_eq!(
+            Config::load_or_default(&empty.join("config.toml")).unwrap(),
+            Config::default()
+        );
+    }
+
+    #[test]
+    fn unquotes_qt_values() {
+        assert_eq!(unquote_qt_value("plain"), "plain");
+        assert_eq!(unquote_qt_value("\"a, b\""), "a, b");
+        assert_eq!(unquote_qt_value(r"line\none\ttab"), "line\none\ttab");
+        assert_eq!(
+            unquote_qt_value(r#"\"quoted\" \\ back"#),
+            "\"quoted\" \\ back"
+        );
+        assert_eq!(unquote_qt_value("trailing\\"), "trailing");
+    }
+}
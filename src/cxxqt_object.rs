@@ -9,6 +9,8 @@ pub mod qobject {
         #[qobject]
         #[qml_element]
         #[qproperty(QString, osc_address)]
+        #[qproperty(QString, osc_listen_address)]
+        #[qproperty(i32, send_interval_ms)]
         #[qproperty(bool, status_enabled)]
         #[qproperty(QString, status_line1)]
         #[qproperty(QString, status_line2)]
@@ -16,20 +18,72 @@ pub mod qobject {
         #[qproperty(QString, status_line4)]
         #[qproperty(QString, status_line5)]
         #[qproperty(QString, status_line6)]
+        #[qproperty(i32, status_rotation_secs)]
         #[qproperty(bool, time_enabled)]
         #[qproperty(QString, time_format)]
         #[qproperty(bool, stats_enabled)]
-        #[qproperty(bool, stats_show_cpu)]
-        #[qproperty(bool, stats_show_ram)]
+        #[qproperty(QString, stats_metrics)]
+        #[qproperty(QString, stats_label_style)]
         #[qproperty(bool, network_enabled)]
+        #[qproperty(QString, network_unit)]
+        #[qproperty(QString, network_prefix)]
+        #[qproperty(i32, network_smoothing)]
+        #[qproperty(QString, network_interfaces)]
+        #[qproperty(QString, network_exclude)]
+        #[qproperty(bool, gpu_enabled)]
+        #[qproperty(QString, gpu_backend)]
+        #[qproperty(QString, gpu_card)]
+        #[qproperty(bool, sensors_enabled)]
+        #[qproperty(QString, sensors_labels)]
+        #[qproperty(bool, sensors_fans)]
+        #[qproperty(bool, sensors_fahrenheit)]
+        #[qproperty(i32, sensors_warn_above)]
         #[qproperty(bool, media_enabled)]
+        #[qproperty(QString, media_players)]
+        #[qproperty(QString, media_blocklist)]
+        #[qproperty(QString, media_template)]
+        #[qproperty(QString, media_templates)]
+        #[qproperty(i32, media_bar_length)]
+        #[qproperty(QString, media_bar_filled)]
+        #[qproperty(QString, media_bar_empty)]
+        #[qproperty(i32, media_title_max)]
+        #[qproperty(i32, media_artist_max)]
+        #[qproperty(bool, lyrics_enabled)]
+        #[qproperty(QString, lyrics_dir)]
         #[qproperty(bool, afk_enabled)]
         #[qproperty(i32, afk_timeout_secs)]
         #[qproperty(QString, afk_text)]
+        #[qproperty(bool, afk_source_osc)]
+        #[qproperty(bool, afk_source_chat)]
+        #[qproperty(bool, afk_source_idle)]
         #[qproperty(bool, system_details_enabled)]
+        #[qproperty(QString, system_details_sections)]
+        #[qproperty(QString, system_details_style)]
+        #[qproperty(bool, system_details_lowercase)]
+        #[qproperty(i32, system_details_rotation_secs)]
         #[qproperty(bool, heartrate_enabled)]
         #[qproperty(QString, heartrate_token)]
+        #[qproperty(QString, heartrate_url)]
+        #[qproperty(i32, heartrate_alert_above)]
+        #[qproperty(i32, heartrate_alert_below)]
+        #[qproperty(bool, events_enabled)]
+        #[qproperty(i32, events_duration_secs)]
+        #[qproperty(i32, events_cooldown_secs)]
+        #[qproperty(bool, events_media)]
+        #[qproperty(QString, events_now_playing)]
+        #[qproperty(bool, events_afk)]
+        #[qproperty(bool, chat_page_indicator)]
+        #[qproperty(QString, layout_template)]
+        #[qproperty(QString, layout_error)]
+        #[qproperty(QString, config_error)]
+        #[qproperty(QString, profile_names)]
+        #[qproperty(QString, active_profile)]
+        #[qproperty(QString, rule_profile)]
         #[qproperty(QString, last_output)]
+        #[qproperty(QString, last_composed)]
+        #[qproperty(QString, last_sent_at)]
+        #[qproperty(i32, error_count)]
+        #[qproperty(QString, last_error)]
         #[qproperty(bool, running)]
         #[namespace = "osc_chatbox"]
         type ChatboxController = super::ChatboxControllerRust;
@@ -48,24 +102,59 @@ pub mod qobject {
         #[cxx_name = "setTyping"]
         fn set_typing(self: Pin<&mut Self>, typing: bool);
 
-        #[qinvokable]
-        fn tick(self: Pin<&mut Self>);
-
         #[qinvokable]
         #[cxx_name = "applySettings"]
         fn apply_settings(self: Pin<&mut Self>);
+
+        #[qinvokable]
+        #[cxx_name = "loadConfig"]
+        fn load_config(self: Pin<&mut Self>);
+
+        #[qinvokable]
+        #[cxx_name = "saveConfig"]
+        fn save_config(self: Pin<&mut Self>);
+
+        #[qinvokable]
+        #[cxx_name = "switchProfile"]
+        fn switch_profile(self: Pin<&mut Self>, name: &QString);
+
+        #[qinvokable]
+        #[cxx_name = "saveProfile"]
+        fn save_profile(self: Pin<&mut Self>, name: &QString);
+
+        #[qinvokable]
+        #[cxx_name = "deleteProfile"]
+        fn delete_profile(self: Pin<&mut Self>, name: &QString);
+
+        #[qinvokable]
+        #[cxx_name = "importProfile"]
+        fn import_profile(self: Pin<&mut Self>, file_url: &QString);
+
+        #[qinvokable]
+        #[cxx_name = "exportProfile"]
+        fn export_profile(self: Pin<&mut Self>, name: &QString, file_url: &QString);
     }
+
+    impl cxx_qt::Threading for ChatboxController {}
 }
 
 use core::pin::Pin;
-use cxx_qt::CxxQtType;
+use cxx_qt::{CxxQtType, Threading};
 use cxx_qt_lib::QString;
 
-use crate::modules::Module;
-use crate::orchestrator::Orchestrator;
+use std::path::PathBuf;
+
+use osc_chatbox::config::{Config, ConfigError, PlayerTemplate, Profile};
+use osc_chatbox::modules::{heartrate, media, network, sensors, stats, status, system_details};
+use osc_chatbox::osc::receiver;
+use osc_chatbox::profiles::ProfileStore;
+use osc_chatbox::template::Template;
+use osc_chatbox::worker::{Command, Update, Worker};
 
 pub struct ChatboxControllerRust {
     osc_address: QString,
+    osc_listen_address: QString,
+    send_interval_ms: i32,
     status_enabled: bool,
     status_line1: QString,
     status_line2: QString,
@@ -73,28 +162,97 @@ pub struct ChatboxControllerRust {
     status_line4: QString,
     status_line5: QString,
     status_line6: QString,
+    status_rotation_secs: i32,
     time_enabled: bool,
     time_format: QString,
     stats_enabled: bool,
-    stats_show_cpu: bool,
-    stats_show_ram: bool,
+    /// Comma-separated metric specs.
+    stats_metrics: QString,
+    stats_label_style: QString,
     network_enabled: bool,
+    network_unit: QString,
+    network_prefix: QString,
+    /// Percent; the config stores a fraction.
+    network_smoothing: i32,
+    /// Comma-separated interface prefixes.
+    network_interfaces: QString,
+    network_exclude: QString,
+    gpu_enabled: bool,
+    gpu_backend: QString,
+    gpu_card: QString,
+    sensors_enabled: bool,
+    /// Comma-separated sensor labels.
+    sensors_labels: QString,
+    sensors_fans: bool,
+    sensors_fahrenheit: bool,
+    /// Degrees Celsius.
+    sensors_warn_above: i32,
     media_enabled: bool,
+    /// Comma-separated player names.
+    media_players: QString,
+    media_blocklist: QString,
+    media_template: QString,
+    /// One `player: template` per line.
+    media_templates: QString,
+    media_bar_length: i32,
+    media_bar_filled: QString,
+    media_bar_empty: QString,
+    media_title_max: i32,
+    media_artist_max: i32,
+    lyrics_enabled: bool,
+    lyrics_dir: QString,
     afk_enabled: bool,
     afk_timeout_secs: i32,
     afk_text: QString,
+    afk_source_osc: bool,
+    afk_source_chat: bool,
+    afk_source_idle: bool,
     system_details_enabled: bool,
+    system_details_sections: QString,
+    system_details_style: QString,
+    system_details_lowercase: bool,
+    system_details_rotation_secs: i32,
     heartrate_enabled: bool,
     heartrate_token: QString,
+    heartrate_url: QString,
+    heartrate_alert_above: i32,
+    heartrate_alert_below: i32,
+    events_enabled: bool,
+    events_duration_secs: i32,
+    events_cooldown_secs: i32,
+    events_media: bool,
+    events_now_playing: QString,
+    events_afk: bool,
+    chat_page_indicator: bool,
+    layout_template: QString,
+    layout_error: QString,
+    config_error: QString,
+    /// Stored profile names, one per line.
+    profile_names: QString,
+    active_profile: QString,
+    /// Profile the automation rules switched to, empty when none applies.
+    rule_profile: QString,
+    /// Last message sent to the chatbox.
     last_output: QString,
+    /// Last message composed, which may have been held back as unchanged
+    /// or by the rate limit.
+    last_composed: QString,
+    last_sent_at: QString,
+    error_count: i32,
+    last_error: QString,
     running: bool,
-    orchestrator: Option<Orchestrator>,
+    /// Config as last loaded. Settings without controls of their own, like
+    /// automation rules, are carried over from it when saving.
+    loaded: Config,
+    worker: Option<Worker>,
 }
 
 impl Default for ChatboxControllerRust {
     fn default() -> Self {
         Self {
             osc_address: QString::from("127.0.0.1:9000"),
+            osc_listen_address: QString::from(receiver::DEFAULT_LISTEN_ADDR),
+            send_interval_ms: 1500,
             status_enabled: false,
             status_line1: QString::from(""),
             status_line2: QString::from(""),
@@ -102,118 +260,499 @@ impl Default for ChatboxControllerRust {
             status_line4: QString::from(""),
             status_line5: QString::from(""),
             status_line6: QString::from(""),
+            status_rotation_secs: status::DEFAULT_ROTATION.as_secs() as i32,
             time_enabled: false,
             time_format: QString::from("%H:%M"),
             stats_enabled: false,
-            stats_show_cpu: true,
-            stats_show_ram: true,
+            stats_metrics: QString::from(&stats::DEFAULT_METRICS.join(", ")),
+            stats_label_style: QString::from("upper"),
             network_enabled: false,
+            network_unit: QString::from("bytes"),
+            network_prefix: QString::from("si"),
+            network_smoothing: (network::DEFAULT_SMOOTHING * 100.0) as i32,
+            network_interfaces: QString::from(""),
+            network_exclude: QString::from(&network::DEFAULT_EXCLUDE.join(", ")),
+            gpu_enabled: false,
+            gpu_backend: QString::from("auto"),
+            gpu_card: QString::from(""),
+            sensors_enabled: false,
+            sensors_labels: QString::from(""),
+            sensors_fans: false,
+            sensors_fahrenheit: false,
+            sensors_warn_above: sensors::DEFAULT_WARN_ABOVE as i32,
             media_enabled: false,
+            media_players: QString::from("spotify, spotifyd"),
+            media_blocklist: QString::from(""),
+            media_template: QString::from(media::DEFAULT_TEMPLATE),
+            media_templates: QString::from(""),
+            media_bar_length: media::DEFAULT_BAR_LENGTH as i32,
+            media_bar_filled: QString::from(media::DEFAULT_BAR_FILLED),
+            media_bar_empty: QString::from(media::DEFAULT_BAR_EMPTY),
+            media_title_max: 0,
+            media_artist_max: 0,
+            lyrics_enabled: false,
+            lyrics_dir: QString::from(""),
             afk_enabled: false,
             afk_timeout_secs: 300,
             afk_text: QString::from("AFK"),
+            afk_source_osc: true,
+            afk_source_chat: true,
+            afk_source_idle: false,
             system_details_enabled: false,
+            system_details_sections: QString::from(&system_details::DEFAULT_SECTIONS.join(", ")),
+            system_details_style: QString::from("superscript"),
+            system_details_lowercase: true,
+            system_details_rotation_secs: system_details::DEFAULT_ROTATION.as_secs() as i32,
             heartrate_enabled: false,
             heartrate_token: QString::from(""),
+            heartrate_url: QString::from(heartrate::DEFAULT_URL),
+            heartrate_alert_above: 0,
+            heartrate_alert_below: 0,
+            events_enabled: false,
+            events_duration_secs: 5,
+            events_cooldown_secs: 60,
+            events_media: true,
+            events_now_playing: QString::from(media::DEFAULT_ANNOUNCE_TEMPLATE),
+            events_afk: true,
+            chat_page_indicator: true,
+            layout_template: QString::from(""),
+            layout_error: QString::from(""),
+            config_error: QString::from(""),
+            profile_names: QString::from(""),
+            active_profile: QString::from(""),
+            rule_profile: QString::from(""),
             last_output: QString::from(""),
+            last_composed: QString::from(""),
+            last_sent_at: QString::from(""),
+            error_count: 0,
+            last_error: QString::from(""),
             running: false,
-            orchestrator: None,
+            loaded: Config::default(),
+            worker: None,
         }
     }
 }
 
+impl ChatboxControllerRust {
+    fn to_config(&self) -> Config {
+        let mut config = self.loaded.clone();
+        config.osc.target = self.osc_address.to_string();
+        config.osc.listen = self.osc_listen_address.to_string();
+        config.osc.interval_ms = self.send_interval_ms.max(0) as u64;
+        config.active_profile = self.active_profile.to_string();
+        config.profile.layout = self.layout_template.to_string();
+        config.profile.chat.page_indicator = self.chat_page_indicator;
+        config.profile.status.enabled = self.status_enabled;
+        config.profile.status.lines = [
+            &self.status_line1,
+            &self.status_line2,
+            &self.status_line3,
+            &self.status_line4,
+            &self.status_line5,
+            &self.status_line6,
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+        config.profile.status.rotation_secs = self.status_rotation_secs.max(1) as u64;
+        config.profile.time.enabled = self.time_enabled;
+        config.profile.time.format = self.time_format.to_string();
+        config.profile.stats.enabled = self.stats_enabled;
+        config.profile.stats.metrics = split_list(&self.stats_metrics.to_string());
+        config.profile.stats.label_style = self.stats_label_style.to_string();
+        config.profile.network.enabled = self.network_enabled;
+        config.profile.network.unit = self.network_unit.to_string();
+        config.profile.network.prefix = self.network_prefix.to_string();
+        config.profile.network.smoothing = f64::from(self.network_smoothing.clamp(0, 99)) / 100.0;
+        config.profile.network.interfaces = split_list(&self.network_interfaces.to_string());
+        config.profile.network.exclude = split_list(&self.network_exclude.to_string());
+        config.profile.gpu.enabled = self.gpu_enabled;
+        config.profile.gpu.backend = self.gpu_backend.to_string();
+        config.profile.gpu.card = self.gpu_card.to_string();
+        config.profile.sensors.enabled = self.sensors_enabled;
+        config.profile.sensors.labels = split_list(&self.sensors_labels.to_string());
+        config.profile.sensors.fans = self.sensors_fans;
+        config.profile.sensors.fahrenheit = self.sensors_fahrenheit;
+        config.profile.sensors.warn_above = f64::from(self.sensors_warn_above.max(0));
+        config.profile.media.enabled = self.media_enabled;
+        config.profile.media.players = split_list(&self.media_players.to_string());
+        config.profile.media.blocklist = split_list(&self.media_blocklist.to_string());
+        config.profile.media.templates = self
+            .media_templates
+            .to_string()
+            .lines()
+            .filter_map(|line| {
+                let (player, template) = line.split_once(':')?;
+                Some(PlayerTemplate {
+                    player: player.trim().to_string(),
+                    template: template.trim().to_string(),
+                })
+            })
+            .collect();
+        config.profile.media.template = self.media_template.to_string();
+        config.profile.media.bar_length = self.media_bar_length.max(0) as usize;
+        config.profile.media.bar_filled = self.media_bar_filled.to_string();
+        config.profile.media.bar_empty = self.media_bar_empty.to_string();
+        config.profile.media.title_max_len = self.media_title_max.max(0) as usize;
+        config.profile.media.artist_max_len = self.media_artist_max.max(0) as usize;
+        config.profile.lyrics.enabled = self.lyrics_enabled;
+        config.profile.lyrics.dir = self.lyrics_dir.to_string();
+        config.profile.afk.enabled = self.afk_enabled;
+        config.profile.afk.timeout_secs = self.afk_timeout_secs.max(0) as u64;
+        config.profile.afk.text = self.afk_text.to_string();
+        config.profile.afk.source_osc = self.afk_source_osc;
+        config.profile.afk.source_chat = self.afk_source_chat;
+        config.profile.afk.source_idle = self.afk_source_idle;
+        config.profile.system_details.enabled = self.system_details_enabled;
+        config.profile.system_details.sections =
+            split_list(&self.system_details_sections.to_string());
+        config.profile.system_details.style = self.system_details_style.to_string();
+        config.profile.system_details.lowercase = self.system_details_lowercase;
+        config.profile.system_details.rotation_secs =
+            self.system_details_rotation_secs.max(1) as u64;
+        config.profile.heartrate.enabled = self.heartrate_enabled;
+        config.profile.heartrate.token = self.heartrate_token.to_string();
+        config.profile.heartrate.url = self.heartrate_url.to_string();
+        config.profile.heartrate.alert_above = self.heartrate_alert_above.max(0) as u32;
+        config.profile.heartrate.alert_below = self.heartrate_alert_below.max(0) as u32;
+        config.profile.events.enabled = self.events_enabled;
+        config.profile.events.duration_secs = self.events_duration_secs.max(0) as u64;
+        config.profile.events.cooldown_secs = self.events_cooldown_secs.max(0) as u64;
+        config.profile.events.media = self.events_media;
+        config.profile.events.now_playing = self.events_now_playing.to_string();
+        config.profile.events.afk = self.events_afk;
+        config
+    }
+}
+
 fn get_inner(pin: Pin<&mut ChatboxControllerRust>) -> &mut ChatboxControllerRust {
     unsafe { pin.get_unchecked_mut() }
 }
 
 impl qobject::ChatboxController {
+    /// Starts the worker thread that owns the orchestrator. Its updates are
+    /// queued back onto the GUI thread.
     pub fn start(mut self: Pin<&mut Self>) {
-        let addr = self.osc_address().to_string();
-        match Orchestrator::new(&addr) {
-            Ok(orch) => {
+        let config = self.rust().to_config();
+        // Status is reported per run of the worker
+        self.as_mut().set_error_count(0);
+        self.as_mut().set_last_error(QString::from(""));
+        let qt_thread = self.qt_thread();
+        let started = Worker::start(&config, move |update| {
+            let _ = qt_thread.queue(move |qobject| qobject.apply_update(update));
+        });
+
+        match started {
+            Ok(worker) => {
                 let inner = get_inner(self.as_mut().rust_mut());
-                inner.orchestrator = Some(orch);
+                inner.worker = Some(worker);
                 self.as_mut().set_running(true);
-                self.apply_settings();
+                // The worker already has `config`, only the layout check
+                // is left to show
+                self.show_layout_error(&config);
             }
             Err(e) => {
-                self.as_mut()
-                    .set_last_output(QString::from(&format!("Error: {e}")));
+                self.as_mut().set_error_count(1);
+                self.as_mut().set_last_error(QString::from(&e.to_string()));
             }
         }
     }
 
     pub fn stop(mut self: Pin<&mut Self>) {
         let inner = get_inner(self.as_mut().rust_mut());
-        inner.orchestrator = None;
+        inner.worker = None;
         self.as_mut().set_running(false);
+        self.set_rule_profile(QString::from(""));
     }
 
     pub fn send_message(self: Pin<&mut Self>, text: &QString) {
-        let text_str = text.to_string();
-        let inner = get_inner(self.rust_mut());
-        if let Some(ref mut orch) = inner.orchestrator {
-            orch.set_chat_message(text_str);
-        }
+        self.send(Command::ChatMessage(text.to_string()));
     }
 
     pub fn set_typing(self: Pin<&mut Self>, typing: bool) {
-        let inner = get_inner(self.rust_mut());
-        if let Some(ref orch) = inner.orchestrator {
-            let _ = orch.send_typing(typing);
+        self.send(Command::Typing(typing));
+    }
+
+    pub fn apply_settings(mut self: Pin<&mut Self>) {
+        let config = self.rust().to_config();
+        self.as_mut().show_layout_error(&config);
+        self.send(Command::Config(Box::new(config)));
+    }
+
+    fn show_layout_error(self: Pin<&mut Self>, config: &Config) {
+        let layout = &config.profile.layout;
+        let layout_error = match Template::parse(layout) {
+            Err(e) if !layout.trim().is_empty() => e.to_string(),
+            _ => String::new(),
+        };
+        self.set_layout_error(QString::from(&layout_error));
+    }
+
+    fn send(self: Pin<&mut Self>, command: Command) {
+        if let Some(worker) = &self.rust().worker {
+            worker.send(command);
         }
     }
 
-    pub fn tick(self: Pin<&mut Self>) {
-        let inner = get_inner(self.rust_mut());
-        if let Some(ref mut orch) = inner.orchestrator {
-            let _ = orch.tick();
+    fn apply_update(mut self: Pin<&mut Self>, update: Update) {
+        // Updates queued just before the worker stopped are stale
+        if !*self.running() {
+            return;
         }
+        match update {
+            Update::Status(status) => {
+                let text = |text: Option<String>| QString::from(&text.unwrap_or_default());
+                self.as_mut().set_last_output(text(status.sent));
+                self.as_mut().set_last_composed(text(status.composed));
+                self.as_mut().set_last_sent_at(text(
+                    status.sent_at.map(|at| at.format("%H:%M:%S").to_string()),
+                ));
+                self.as_mut()
+                    .set_error_count(status.error_count.min(i32::MAX as u64) as i32);
+                self.as_mut().set_last_error(text(status.last_error));
+            }
+            Update::RuleProfile(profile) => {
+                self.set_rule_profile(QString::from(profile.as_deref().unwrap_or("")))
+            }
+        }
+    }
+
+    pub fn load_config(mut self: Pin<&mut Self>) {
+        match Config::load_or_default(&Config::default_path()) {
+            Ok(config) => {
+                self.as_mut().set_from_config(&config);
+                self.as_mut().set_config_error(QString::from(""));
+            }
+            Err(e) => self
+                .as_mut()
+                .set_config_error(QString::from(&e.to_string())),
+        }
+        self.refresh_profiles();
+    }
+
+    /// Saves the config and keeps the active profile's file in step with it.
+    pub fn save_config(mut self: Pin<&mut Self>) {
+        let config = self.rust().to_config();
+        let result = config.save(&Config::default_path()).and_then(|()| {
+            if config.active_profile.is_empty() {
+                Ok(())
+            } else {
+                ProfileStore::default().save(&config.active_profile, &config.profile)
+            }
+        });
+        self.set_config_result(result);
     }
 
-    pub fn apply_settings(self: Pin<&mut Self>) {
-        let inner = get_inner(self.rust_mut());
-        let status_enabled = inner.status_enabled;
-        let status_lines: [String; 6] = [
-            inner.status_line1.to_string(),
-            inner.status_line2.to_string(),
-            inner.status_line3.to_string(),
-            inner.status_line4.to_string(),
-            inner.status_line5.to_string(),
-            inner.status_line6.to_string(),
-        ];
-        let time_enabled = inner.time_enabled;
-        let time_format = inner.time_format.to_string();
-        let stats_enabled = inner.stats_enabled;
-        let stats_show_cpu = inner.stats_show_cpu;
-        let stats_show_ram = inner.stats_show_ram;
-        let network_enabled = inner.network_enabled;
-        let media_enabled = inner.media_enabled;
-        let afk_enabled = inner.afk_enabled;
-        let afk_timeout = inner.afk_timeout_secs;
-        let afk_text = inner.afk_text.to_string();
-        let system_details_enabled = inner.system_details_enabled;
-        let heartrate_enabled = inner.heartrate_enabled;
-        let heartrate_token = inner.heartrate_token.to_string();
-
-        if let Some(ref mut orch) = inner.orchestrator {
-            orch.status.set_enabled(status_enabled);
-            for (i, line) in status_lines.iter().enumerate() {
-                orch.status.set_line(i, line.clone());
+    /// Loads a stored profile into every setting and makes it the active one.
+    pub fn switch_profile(mut self: Pin<&mut Self>, name: &QString) {
+        let name = name.to_string();
+        match ProfileStore::default().load(&name) {
+            Ok(profile) => {
+                self.as_mut().set_active_profile(QString::from(&name));
+                self.as_mut().set_from_profile(&profile);
+                self.save_config();
             }
-            orch.time.set_enabled(time_enabled);
-            orch.time.set_format(time_format);
-            orch.stats.set_enabled(stats_enabled);
-            orch.stats.set_show_cpu(stats_show_cpu);
-            orch.stats.set_show_ram(stats_show_ram);
-            orch.network.set_enabled(network_enabled);
-            orch.media.set_enabled(media_enabled);
-            orch.afk.set_enabled(afk_enabled);
-            orch.afk.set_timeout_secs(afk_timeout as u64);
-            orch.afk.set_afk_text(afk_text);
-            orch.system_details.set_enabled(system_details_enabled);
-            orch.heartrate.set_enabled(heartrate_enabled);
-            orch.heartrate.set_token(heartrate_token);
+            Err(e) => self.set_config_error(QString::from(&e.to_string())),
         }
     }
+
+    /// Stores the current settings under `name` and switches to it.
+    pub fn save_profile(mut self: Pin<&mut Self>, name: &QString) {
+        let name = name.to_string().trim().to_string();
+        let profile = self.rust().to_config().profile;
+        match ProfileStore::default().save(&name, &profile) {
+            Ok(()) => {
+                self.as_mut().set_active_profile(QString::from(&name));
+                self.as_mut().refresh_profiles();
+                self.save_config();
+            }
+            Err(e) => self.set_config_error(QString::from(&e.to_string())),
+        }
+    }
+
+    /// Deletes a stored profile. The current settings stay as they are.
+    pub fn delete_profile(mut self: Pin<&mut Self>, name: &QString) {
+        let name = name.to_string();
+        let result = ProfileStore::default().delete(&name);
+        if result.is_ok() && self.active_profile().to_string() == name {
+            self.as_mut().set_active_profile(QString::from(""));
+            self.as_mut().save_config();
+        }
+        self.as_mut().refresh_profiles();
+        self.set_config_result(result);
+    }
+
+    pub fn import_profile(mut self: Pin<&mut Self>, file_url: &QString) {
+        let result = local_path(file_url).and_then(|path| ProfileStore::default().import(&path));
+        self.as_mut().refresh_profiles();
+        self.set_config_result(result.map(|_| ()));
+    }
+
+    pub fn export_profile(self: Pin<&mut Self>, name: &QString, file_url: &QString) {
+        let result = local_path(file_url)
+            .and_then(|path| ProfileStore::default().export(&name.to_string(), &path));
+        self.set_config_result(result);
+    }
+
+    fn refresh_profiles(self: Pin<&mut Self>) {
+        let names = ProfileStore::default().list().join("\n");
+        self.set_profile_names(QString::from(&names));
+    }
+
+    fn set_config_result(self: Pin<&mut Self>, result: Result<(), ConfigError>) {
+        let error = match result {
+            Ok(()) => String::new(),
+            Err(e) => e.to_string(),
+        };
+        self.set_config_error(QString::from(&error));
+    }
+
+    fn set_from_config(mut self: Pin<&mut Self>, config: &Config) {
+        self.as_mut()
+            .set_osc_address(QString::from(&config.osc.target));
+        self.as_mut()
+            .set_osc_listen_address(QString::from(&config.osc.listen));
+        self.as_mut()
+            .set_send_interval_ms(config.osc.interval_ms.min(i32::MAX as u64) as i32);
+        self.as_mut()
+            .set_active_profile(QString::from(&config.active_profile));
+        get_inner(self.as_mut().rust_mut()).loaded = config.clone();
+        self.set_from_profile(&config.profile);
+    }
+
+    fn set_from_profile(mut self: Pin<&mut Self>, profile: &Profile) {
+        get_inner(self.as_mut().rust_mut()).loaded.profile = profile.clone();
+        let line = |i: usize| QString::from(profile.status.lines.get(i).map_or("", |l| l.as_str()));
+        self.as_mut()
+            .set_layout_template(QString::from(&profile.layout));
+        self.as_mut()
+            .set_chat_page_indicator(profile.chat.page_indicator);
+        self.as_mut().set_status_enabled(profile.status.enabled);
+        self.as_mut().set_status_line1(line(0));
+        self.as_mut().set_status_line2(line(1));
+        self.as_mut().set_status_line3(line(2));
+        self.as_mut().set_status_line4(line(3));
+        self.as_mut().set_status_line5(line(4));
+        self.as_mut().set_status_line6(line(5));
+        self.as_mut()
+            .set_status_rotation_secs(profile.status.rotation_secs.min(i32::MAX as u64) as i32);
+        self.as_mut().set_time_enabled(profile.time.enabled);
+        self.as_mut()
+            .set_time_format(QString::from(&profile.time.format));
+        self.as_mut().set_stats_enabled(profile.stats.enabled);
+        self.as_mut()
+            .set_stats_metrics(QString::from(&profile.stats.metrics.join(", ")));
+        self.as_mut()
+            .set_stats_label_style(QString::from(&profile.stats.label_style));
+        self.as_mut().set_network_enabled(profile.network.enabled);
+        self.as_mut()
+            .set_network_unit(QString::from(&profile.network.unit));
+        self.as_mut()
+            .set_network_prefix(QString::from(&profile.network.prefix));
+        self.as_mut()
+            .set_network_smoothing((profile.network.smoothing * 100.0).round() as i32);
+        self.as_mut()
+            .set_network_interfaces(QString::from(&profile.network.interfaces.join(", ")));
+        self.as_mut()
+            .set_network_exclude(QString::from(&profile.network.exclude.join(", ")));
+        self.as_mut().set_gpu_enabled(profile.gpu.enabled);
+        self.as_mut()
+            .set_gpu_backend(QString::from(&profile.gpu.backend));
+        self.as_mut().set_gpu_card(QString::from(&profile.gpu.card));
+        self.as_mut().set_sensors_enabled(profile.sensors.enabled);
+        self.as_mut()
+            .set_sensors_labels(QString::from(&profile.sensors.labels.join(", ")));
+        self.as_mut().set_sensors_fans(profile.sensors.fans);
+        self.as_mut()
+            .set_sensors_fahrenheit(profile.sensors.fahrenheit);
+        self.as_mut()
+            .set_sensors_warn_above(profile.sensors.warn_above.round() as i32);
+        self.as_mut().set_media_enabled(profile.media.enabled);
+        self.as_mut()
+            .set_media_players(QString::from(&profile.media.players.join(", ")));
+        self.as_mut()
+            .set_media_blocklist(QString::from(&profile.media.blocklist.join(", ")));
+        let templates: Vec<String> = profile
+            .media
+            .templates
+            .iter()
+            .map(|t| format!("{}: {}", t.player, t.template))
+            .collect();
+        self.as_mut()
+            .set_media_templates(QString::from(&templates.join("\n")));
+        self.as_mut()
+            .set_media_template(QString::from(&profile.media.template));
+        self.as_mut()
+            .set_media_bar_length(profile.media.bar_length.min(i32::MAX as usize) as i32);
+        self.as_mut()
+            .set_media_bar_filled(QString::from(&profile.media.bar_filled));
+        self.as_mut()
+            .set_media_bar_empty(QString::from(&profile.media.bar_empty));
+        self.as_mut()
+            .set_media_title_max(profile.media.title_max_len.min(i32::MAX as usize) as i32);
+        self.as_mut()
+            .set_media_artist_max(profile.media.artist_max_len.min(i32::MAX as usize) as i32);
+        self.as_mut().set_lyrics_enabled(profile.lyrics.enabled);
+        self.as_mut()
+            .set_lyrics_dir(QString::from(&profile.lyrics.dir));
+        self.as_mut().set_afk_enabled(profile.afk.enabled);
+        self.as_mut()
+            .set_afk_timeout_secs(profile.afk.timeout_secs.min(i32::MAX as u64) as i32);
+        self.as_mut().set_afk_text(QString::from(&profile.afk.text));
+        self.as_mut().set_afk_source_osc(profile.afk.source_osc);
+        self.as_mut().set_afk_source_chat(profile.afk.source_chat);
+        self.as_mut().set_afk_source_idle(profile.afk.source_idle);
+        self.as_mut()
+            .set_system_details_enabled(profile.system_details.enabled);
+        self.as_mut().set_system_details_sections(QString::from(
+            &profile.system_details.sections.join(", "),
+        ));
+        self.as_mut()
+            .set_system_details_style(QString::from(&profile.system_details.style));
+        self.as_mut()
+            .set_system_details_lowercase(profile.system_details.lowercase);
+        self.as_mut().set_system_details_rotation_secs(
+            profile.system_details.rotation_secs.min(i32::MAX as u64) as i32,
+        );
+        self.as_mut()
+            .set_heartrate_enabled(profile.heartrate.enabled);
+        self.as_mut()
+            .set_heartrate_token(QString::from(&profile.heartrate.token));
+        self.as_mut()
+            .set_heartrate_url(QString::from(&profile.heartrate.url));
+        self.as_mut()
+            .set_heartrate_alert_above(profile.heartrate.alert_above.min(i32::MAX as u32) as i32);
+        self.as_mut()
+            .set_heartrate_alert_below(profile.heartrate.alert_below.min(i32::MAX as u32) as i32);
+        self.as_mut().set_events_enabled(profile.events.enabled);
+        self.as_mut()
+            .set_events_duration_secs(profile.events.duration_secs.min(i32::MAX as u64) as i32);
+        self.as_mut()
+            .set_events_cooldown_secs(profile.events.cooldown_secs.min(i32::MAX as u64) as i32);
+        self.as_mut().set_events_media(profile.events.media);
+        self.as_mut()
+            .set_events_now_playing(QString::from(&profile.events.now_playing));
+        self.as_mut().set_events_afk(profile.events.afk);
+
+        self.apply_settings();
+    }
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Converts a `file://` URL from a QML file dialog into a local path.
+fn local_path(file_url: &QString) -> Result<PathBuf, ConfigError> {
+    let file_url = file_url.to_string();
+    url::Url::parse(&file_url)
+        .ok()
+        .and_then(|url| url.to_file_path().ok())
+        .ok_or(ConfigError::Invalid {
+            field: "profile",
+            message: format!("'{file_url}' is not a local file"),
+        })
 }
@@ -0,0 +1,67 @@
+//! Errors from talking to VRChat and running the orchestrator.
+
+use std::fmt;
+use std::io;
+
+use crate::template::TemplateError;
+
+#[derive(Debug)]
+pub enum Error {
+    /// A UDP socket couldn't be bound, e.g. because the port is taken.
+    Bind {
+        addr: String,
+        source: io::Error,
+    },
+    /// A packet couldn't be sent, e.g. because the target is unreachable.
+    Send {
+        target: String,
+        source: io::Error,
+    },
+    Encode(rosc::OscError),
+    Template(TemplateError),
+    /// A module panicked while ticking.
+    Module {
+        id: String,
+        message: String,
+    },
+    /// The worker thread couldn't be started.
+    Spawn(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Bind { addr, source } => write!(f, "can't bind {addr}: {source}"),
+            Error::Send { target, source } => write!(f, "can't send to {target}: {source}"),
+            Error::Encode(e) => write!(f, "can't encode OSC message: {e}"),
+            Error::Template(e) => write!(f, "layout: {e}"),
+            Error::Module { id, message } => write!(f, "{id} module failed: {message}"),
+            Error::Spawn(e) => write!(f, "can't start worker thread: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Bind { source, .. } | Error::Send { source, .. } | Error::Spawn(source) => {
+                Some(source)
+            }
+            Error::Encode(e) => Some(e),
+            Error::Template(e) => Some(e),
+            Error::Module { .. } => None,
+        }
+    }
+}
+
+impl From<TemplateError> for Error {
+    fn from(e: TemplateError) -> Self {
+        Error::Template(e)
+    }
+}
+
+impl From<rosc::OscError> for Error {
+    fn from(e: rosc::OscError) -> Self {
+        Error::Encode(e)
+    }
+}
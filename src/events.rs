@@ -0,0 +1,199 @@
+//! Short announcements that take over the chatbox for a while, e.g. a new
+//! track or going AFK.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// Identifies repeats of the same announcement, e.g. `media:Artist - Title`.
+    pub key: String,
+    pub text: String,
+    /// Higher priority events cut lower ones short.
+    pub priority: u8,
+}
+
+impl Event {
+    pub fn new(key: impl Into<String>, text: impl Into<String>, priority: u8) -> Self {
+        Self {
+            key: key.into(),
+            text: text.into(),
+            priority,
+        }
+    }
+}
+
+/// Decides which event is shown. One event is shown at a time for the
+/// display duration. Events that arrive meanwhile wait their turn unless
+/// they have a higher priority, and are dropped if they wait longer than an
+/// event would be shown. An event isn't shown again within the cooldown.
+pub struct EventQueue {
+    duration: Duration,
+    cooldown: Duration,
+    active: Option<(Event, Instant)>,
+    pending: Vec<(Event, Instant)>,
+    shown: HashMap<String, Instant>,
+}
+
+impl EventQueue {
+    pub fn new(duration: Duration, cooldown: Duration) -> Self {
+        Self {
+            duration,
+            cooldown,
+            active: None,
+            pending: Vec::new(),
+            shown: HashMap::new(),
+        }
+    }
+
+    pub fn set_timing(&mut self, duration: Duration, cooldown: Duration) {
+        self.duration = duration;
+        self.cooldown = cooldown;
+    }
+
+    /// Drops every queued and active event.
+    pub fn clear(&mut self) {
+        self.active = None;
+        self.pending.clear();
+    }
+
+    pub fn push(&mut self, event: Event) {
+        let cooldown = self.cooldown;
+        self.shown.retain(|_, at| at.elapsed() < cooldown);
+        if self.shown.contains_key(&event.key)
+            || self
+                .active
+                .as_ref()
+                .is_some_and(|(e, _)| e.key == event.key)
+            || self.pending.iter().any(|(e, _)| e.key == event.key)
+        {
+            return;
+        }
+
+        self.expire();
+        match &self.active {
+            Some((active, _)) if active.priority >= event.priority => {
+                self.pending.push((event, Instant::now()));
+            }
+            // Nothing shown or a less important event, which is cut short
+            _ => self.show(event),
+        }
+    }
+
+    /// Text of the event to show right now.
+    pub fn current(&mut self) -> Option<&str> {
+        self.expire();
+        self.active.as_ref().map(|(event, _)| event.text.as_str())
+    }
+
+    fn show(&mut self, event: Event) {
+        debug!(key = %event.key, "showing event");
+        let now = Instant::now();
+        self.shown.insert(event.key.clone(), now);
+        self.active = Some((event, now));
+    }
+
+    /// Ends the active event once its time is up and moves on to the most
+    /// important waiting one, oldest first among equals.
+    fn expire(&mut self) {
+        if self
+            .active
+            .as_ref()
+            .is_some_and(|(_, since)| since.elapsed() < self.duration)
+        {
+            return;
+        }
+        self.active = None;
+
+        let duration = self.duration;
+        self.pending
+            .retain(|(_, queued)| queued.elapsed() < duration);
+        let next = self
+            .pending
+            .iter()
+            .enumerate()
+            .max_by(|(a_index, (a, _)), (b_index, (b, _))| {
+                a.priority.cmp(&b.priority).then(b_index.cmp(a_index))
+            })
+            .map(|(index, _)| index);
+        if let Some(index) = next {
+            let (event, _) = self.pending.remove(index);
+            self.show(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DURATION: Duration = Duration::from_secs(5);
+    const COOLDOWN: Duration = Duration::from_secs(60);
+
+    /// Moves the active event and the cooldowns `by` into the past. Waiting
+    /// events stay fresh, as if they had just arrived.
+    fn wait(queue: &mut EventQueue, by: Duration) {
+        if let Some((_, since)) = queue.active.as_mut() {
+            *since -= by;
+        }
+        for at in queue.shown.values_mut() {
+            *at -= by;
+        }
+    }
+
+    #[test]
+    fn higher_priority_takes_over() {
+        let mut queue = EventQueue::new(DURATION, COOLDOWN);
+        queue.push(Event::new("media:a", "new track", 40));
+        queue.push(Event::new("afk", "AFK", 80));
+        assert_eq!(queue.current(), Some("AFK"));
+
+        // Lower priority waits for its turn
+        queue.push(Event::new("media:b", "another track", 40));
+        assert_eq!(queue.current(), Some("AFK"));
+        wait(&mut queue, DURATION);
+        assert_eq!(queue.current(), Some("another track"));
+    }
+
+    #[test]
+    fn merges_events_with_the_same_key() {
+        let mut queue = EventQueue::new(DURATION, COOLDOWN);
+        queue.push(Event::new("afk", "AFK", 80));
+        queue.push(Event::new("heartrate", "high", 60));
+        queue.push(Event::new("heartrate", "higher", 60));
+        assert_eq!(queue.pending.len(), 1);
+
+        wait(&mut queue, DURATION);
+        assert_eq!(queue.current(), Some("high"));
+        wait(&mut queue, DURATION);
+        assert_eq!(queue.current(), None);
+    }
+
+    #[test]
+    fn cooldown_blocks_repeats() {
+        let mut queue = EventQueue::new(DURATION, COOLDOWN);
+        queue.push(Event::new("afk", "AFK", 80));
+        wait(&mut queue, DURATION);
+        assert_eq!(queue.current(), None);
+
+        queue.push(Event::new("afk", "AFK", 80));
+        assert_eq!(queue.current(), None);
+
+        wait(&mut queue, COOLDOWN);
+        queue.push(Event::new("afk", "AFK", 80));
+        assert_eq!(queue.current(), Some("AFK"));
+    }
+
+    #[test]
+    fn ends_after_its_duration() {
+        let mut queue = EventQueue::new(DURATION, COOLDOWN);
+        queue.set_timing(Duration::from_secs(2), COOLDOWN);
+        queue.push(Event::new("media:a", "new track", 40));
+        wait(&mut queue, Duration::from_secs(1));
+        assert_eq!(queue.current(), Some("new track"));
+        wait(&mut queue, Duration::from_secs(1));
+        assert_eq!(queue.current(), None);
+    }
+}
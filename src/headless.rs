@@ -0,0 +1,138 @@
+//! Runs the chatbox without a window, driven entirely by a config file.
+
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use tracing::error;
+
+use crate::config::Config;
+use crate::logging;
+use crate::orchestrator::Orchestrator;
+use crate::profiles::ProfileStore;
+use crate::worker::{self, MIN_INTERVAL};
+
+const USAGE: &str = "\
+Usage: osc-chatbox --headless [OPTIONS]
+
+Options:
+  -c, --config <PATH>    Config file to load [default: $XDG_CONFIG_HOME/osc-chatbox/config.toml]
+  -p, --profile <NAME>   Use a saved profile instead of the config's module settings
+  -i, --interval <MS>    Milliseconds between ticks [default: osc.interval_ms]
+      --once             Tick once and exit
+      --dry-run          Print messages instead of sending them
+  -v, --verbose          Log debug output, including each message sent
+  -h, --help             Show this help";
+
+struct Options {
+    config: Option<PathBuf>,
+    profile: Option<String>,
+    interval: Option<Duration>,
+    once: bool,
+    dry_run: bool,
+    verbose: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        config: None,
+        profile: None,
+        interval: None,
+        once: false,
+        dry_run: false,
+        verbose: false,
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--headless" => {}
+            "-c" | "--config" => {
+                let path = iter.next().ok_or("--config needs a path")?;
+                options.config = Some(PathBuf::from(path));
+            }
+            "-p" | "--profile" => {
+                let name = iter.next().ok_or("--profile needs a name")?;
+                options.profile = Some(name.clone());
+            }
+            "-i" | "--interval" => {
+                let ms = iter.next().ok_or("--interval needs a value")?;
+                let ms: u64 = ms.parse().map_err(|_| format!("invalid interval '{ms}'"))?;
+                options.interval = Some(Duration::from_millis(ms).max(MIN_INTERVAL));
+            }
+            "--once" => options.once = true,
+            "--dry-run" => options.dry_run = true,
+            "-v" | "--verbose" => options.verbose = true,
+            "-h" | "--help" => return Ok(None),
+            other => return Err(format!("unknown argument '{other}'")),
+        }
+    }
+
+    Ok(Some(options))
+}
+
+/// Whether the command line asks for headless mode in a GUI build.
+#[cfg(feature = "gui")]
+pub fn requested(args: &[String]) -> bool {
+    args.iter().any(|a| a == "--headless")
+}
+
+/// Runs the headless loop and returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let options = match parse_args(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return 0;
+        }
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return 2;
+        }
+    };
+
+    let loaded = match &options.config {
+        Some(path) => Config::load(path),
+        None => Config::load_or_default(&Config::default_path()),
+    };
+    let loaded = loaded.and_then(|mut config| {
+        if let Some(name) = &options.profile {
+            config.profile = ProfileStore::default().load(name)?;
+            config.active_profile = name.clone();
+        }
+        Ok(config)
+    });
+    let config = match loaded {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return 1;
+        }
+    };
+
+    let _guard = logging::init(&config.logging, options.verbose);
+
+    let mut orch = match Orchestrator::from_config(&config) {
+        Ok(orch) => orch,
+        Err(e) => {
+            error!("{e}");
+            return 1;
+        }
+    };
+
+    let interval = options
+        .interval
+        .unwrap_or_else(|| worker::send_interval(&config));
+
+    orch.set_dry_run(options.dry_run);
+    loop {
+        if let Err(e) = orch.tick() {
+            error!("{e}");
+        }
+
+        if options.once {
+            return 0;
+        }
+        thread::sleep(interval);
+    }
+}
@@ -0,0 +1,84 @@
+//! Temperatures and fan speeds from the kernel's hwmon interface in sysfs.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_SYSFS_ROOT: &str = "/sys";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Degrees Celsius.
+    Temp,
+    /// Revolutions per minute.
+    Fan,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sensor {
+    /// Driver name from the chip's `name` file, e.g. `coretemp`.
+    pub chip: String,
+    /// From the sensor's `_label` file, or its file prefix such as `temp1`
+    /// when there is none.
+    pub label: String,
+    pub kind: Kind,
+    pub value: f64,
+}
+
+/// Every sensor under `root/class/hwmon`, by chip and then sensor number.
+pub fn read_all(root: &Path) -> Vec<Sensor> {
+    numbered(&root.join("class/hwmon"), "hwmon", "")
+        .iter()
+        .flat_map(|(_, dir)| read_chip(dir))
+        .collect()
+}
+
+/// Sensors of one chip directory such as `/sys/class/hwmon/hwmon2`.
+pub fn read_chip(dir: &Path) -> Vec<Sensor> {
+    let chip = fs::read_to_string(dir.join("name"))
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default();
+
+    let mut sensors = Vec::new();
+    for (prefix, kind, scale) in [("temp", Kind::Temp, 1000.0), ("fan", Kind::Fan, 1.0)] {
+        for (index, path) in numbered(dir, prefix, "_input") {
+            let Some(value) = fs::read_to_string(&path)
+                .ok()
+                .and_then(|raw| raw.trim().parse::<f64>().ok())
+            else {
+                continue;
+            };
+            let label = fs::read_to_string(dir.join(format!("{prefix}{index}_label")))
+                .map(|label| label.trim().to_string())
+                .unwrap_or_else(|_| format!("{prefix}{index}"));
+            sensors.push(Sensor {
+                chip: chip.clone(),
+                label,
+                kind,
+                value: value / scale,
+            });
+        }
+    }
+    sensors
+}
+
+/// Entries of `dir` named `{prefix}N{suffix}`, sorted by N.
+fn numbered(dir: &Path, prefix: &str, suffix: &str) -> Vec<(u32, PathBuf)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut found: Vec<(u32, PathBuf)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let index = name
+                .to_str()?
+                .strip_prefix(prefix)?
+                .strip_suffix(suffix)?
+                .parse()
+                .ok()?;
+            Some((index, entry.path()))
+        })
+        .collect();
+    found.sort();
+    found
+}
@@ -0,0 +1,20 @@
+pub mod budget;
+pub mod chat;
+pub mod config;
+pub mod error;
+pub mod events;
+pub mod headless;
+pub mod hwmon;
+pub mod logging;
+pub mod lrc;
+pub mod modules;
+pub mod mpris;
+pub mod orchestrator;
+pub mod osc;
+pub mod profiles;
+pub mod rules;
+pub mod smalltext;
+pub mod template;
+#[cfg(test)]
+mod testing;
+pub mod worker;
@@ -0,0 +1,82 @@
+//! Log output to stderr and to a daily rotated file under
+//! `$XDG_STATE_HOME/osc-chatbox/`.
+
+use std::path::PathBuf;
+
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+
+use crate::config::{LoggingConfig, APP_DIR};
+
+const LOG_PREFIX: &str = "osc-chatbox";
+const LOG_SUFFIX: &str = "log";
+/// Days of logs kept.
+const MAX_LOG_FILES: usize = 7;
+
+/// `$XDG_STATE_HOME/osc-chatbox`, falling back to `~/.local/state/osc-chatbox`.
+pub fn state_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join(APP_DIR)
+}
+
+/// Installs the global logger. `verbose` raises the level to debug, and
+/// `RUST_LOG` overrides both. The returned guard flushes the log file when
+/// dropped, so it has to be kept until the program exits.
+pub fn init(config: &LoggingConfig, verbose: bool) -> Option<WorkerGuard> {
+    let level = if verbose {
+        "debug"
+    } else {
+        config.level.trim()
+    };
+    // Dependencies only get to add warnings, or debug output would be
+    // mostly D-Bus traffic
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("warn,osc_chatbox={level}")));
+
+    let (file_layer, guard) = if config.file {
+        match open_log_file() {
+            Ok(appender) => {
+                let (writer, guard) = tracing_appender::non_blocking(appender);
+                let layer = fmt::layer().with_writer(writer).with_ansi(false);
+                (Some(layer), Some(guard))
+            }
+            Err(e) => {
+                eprintln!("Logging: can't open log file: {e}");
+                (None, None)
+            }
+        }
+    } else {
+        (None, None)
+    };
+
+    let initialized = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .with(file_layer)
+        .try_init();
+    if let Err(e) = initialized {
+        eprintln!("Logging: {e}");
+    }
+    guard
+}
+
+fn open_log_file() -> Result<RollingFileAppender, Box<dyn std::error::Error>> {
+    let dir = state_dir();
+    // The appender prunes old logs on startup and complains if there's no
+    // directory to look in yet
+    std::fs::create_dir_all(&dir)?;
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_PREFIX)
+        .filename_suffix(LOG_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(dir)?;
+    Ok(appender)
+}
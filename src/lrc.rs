@@ -0,0 +1,213 @@
+//! Parser for LRC synced lyrics.
+//!
+//! Lines look like `[01:23.45]Some words`. A line may carry several time
+//! tags when it repeats, e.g. a chorus. The `[offset:+/-ms]` tag shifts
+//! every line; positive values make lyrics appear earlier. Other ID tags
+//! such as `[ar:...]` are ignored, as are word timings from the enhanced
+//! format (`<01:23.45>`).
+
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub time: Duration,
+    pub text: String,
+}
+
+/// Synced lyrics, sorted by time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lyrics {
+    lines: Vec<Line>,
+}
+
+impl Lyrics {
+    /// Parses LRC text. Lines without a time tag are skipped, so plain
+    /// unsynced lyrics come out empty.
+    pub fn parse(text: &str) -> Self {
+        let mut offset_ms: i64 = 0;
+        let mut timed: Vec<(i64, String)> = Vec::new();
+
+        for raw in text.lines() {
+            let mut rest = raw.trim();
+            let mut times = Vec::new();
+
+            while let Some(tag) = rest.strip_prefix('[') {
+                let Some(end) = tag.find(']') else {
+                    break;
+                };
+                let (tag, after) = (&tag[..end], &tag[end + 1..]);
+                if let Some(ms) = parse_timestamp(tag) {
+                    times.push(ms);
+                } else if let Some(value) = tag.strip_prefix("offset:") {
+                    offset_ms = value.trim().parse().unwrap_or(offset_ms);
+                }
+                rest = after;
+            }
+
+            if times.is_empty() {
+                continue;
+            }
+            let text = strip_word_times(rest).trim().to_string();
+            timed.extend(times.into_iter().map(|ms| (ms, text.clone())));
+        }
+
+        // Stable, so lines sharing a time keep their file order
+        timed.sort_by_key(|(ms, _)| *ms);
+        let lines = timed
+            .into_iter()
+            .map(|(ms, text)| Line {
+                time: Duration::from_millis((ms - offset_ms).max(0) as u64),
+                text,
+            })
+            .collect();
+        Self { lines }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// Index of the line being sung at `position`, if it has started.
+    pub fn index_at(&self, position: Duration) -> Option<usize> {
+        self.lines
+            .partition_point(|line| line.time <= position)
+            .checked_sub(1)
+    }
+
+    /// The line being sung at `position`. Empty lines mark instrumental
+    /// breaks and are returned as they are.
+    pub fn line_at(&self, position: Duration) -> Option<&str> {
+        let index = self.index_at(position)?;
+        Some(&self.lines[index].text)
+    }
+}
+
+/// Parses `mm:ss`, `mm:ss.xx`, `mm:ss.xxx` or `mm:ss:xx` into milliseconds.
+fn parse_timestamp(tag: &str) -> Option<i64> {
+    let (mins, rest) = tag.split_once(':')?;
+    let (secs, fraction) = match rest.split_once(['.', ':']) {
+        Some((secs, fraction)) => (secs, Some(fraction)),
+        None => (rest, None),
+    };
+
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !digits(mins) || !digits(secs) || fraction.is_some_and(|f| !digits(f)) {
+        return None;
+    }
+
+    let mins: i64 = mins.parse().ok()?;
+    let secs: i64 = secs.parse().ok()?;
+    if secs >= 60 {
+        return None;
+    }
+    // Hundredths and milliseconds are both common; scale by digit count
+    let fraction_ms = match fraction {
+        Some(f) => {
+            let f = &f[..f.len().min(3)];
+            f.parse::<i64>().ok()? * 10_i64.pow(3 - f.len() as u32)
+        }
+        None => 0,
+    };
+    Some((mins * 60 + secs) * 1000 + fraction_ms)
+}
+
+/// Removes `<mm:ss.xx>` word timings from enhanced LRC.
+fn strip_word_times(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('>') {
+            Some(end) if parse_timestamp(&after[..end]).is_some() => {
+                rest = &after[end + 1..];
+            }
+            _ => {
+                out.push('<');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    fn texts(lyrics: &Lyrics) -> Vec<(u128, &str)> {
+        lyrics
+            .lines()
+            .iter()
+            .map(|line| (line.time.as_millis(), line.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_timestamp_formats() {
+        assert_eq!(parse_timestamp("01:02"), Some(62_000));
+        assert_eq!(parse_timestamp("01:02.5"), Some(62_500));
+        assert_eq!(parse_timestamp("01:02.50"), Some(62_500));
+        assert_eq!(parse_timestamp("01:02.505"), Some(62_505));
+        assert_eq!(parse_timestamp("01:02:50"), Some(62_500));
+        assert_eq!(parse_timestamp("123:00.00"), Some(7_380_000));
+        assert_eq!(parse_timestamp("ar:Someone"), None);
+        assert_eq!(parse_timestamp("00:75.00"), None);
+        assert_eq!(parse_timestamp(":12"), None);
+    }
+
+    #[test]
+    fn parses_lines_and_skips_tags() {
+        let lyrics = Lyrics::parse(
+            "[ar:Artist]\n[ti:Title]\n\n[00:01.00]First\n[00:03.50] Second \nnot a lyric\n",
+        );
+        assert_eq!(texts(&lyrics), vec![(1000, "First"), (3500, "Second")]);
+    }
+
+    #[test]
+    fn repeats_lines_with_several_timestamps() {
+        let lyrics = Lyrics::parse("[00:10.00][00:30.00]Chorus\n[00:20.00]Verse\n");
+        assert_eq!(
+            texts(&lyrics),
+            vec![(10_000, "Chorus"), (20_000, "Verse"), (30_000, "Chorus")]
+        );
+    }
+
+    #[test]
+    fn applies_offset() {
+        let earlier = Lyrics::parse("[offset:+500]\n[00:02.00]Line\n[00:00.20]Start\n");
+        assert_eq!(texts(&earlier), vec![(0, "Start"), (1500, "Line")]);
+
+        let later = Lyrics::parse("[offset:-250]\n[00:02.00]Line\n");
+        assert_eq!(texts(&later), vec![(2250, "Line")]);
+    }
+
+    #[test]
+    fn strips_word_timings() {
+        let lyrics = Lyrics::parse("[00:01.00]<00:01.00>Hello <00:01.50>world <3\n");
+        assert_eq!(texts(&lyrics), vec![(1000, "Hello world <3")]);
+    }
+
+    #[test]
+    fn finds_line_at_position() {
+        let lyrics = Lyrics::parse("[00:01.00]One\n[00:02.00]\n[00:03.00]Three\n");
+        assert_eq!(lyrics.line_at(secs(0.5)), None);
+        assert_eq!(lyrics.line_at(secs(1.0)), Some("One"));
+        assert_eq!(lyrics.line_at(secs(2.5)), Some(""));
+        assert_eq!(lyrics.line_at(secs(100.0)), Some("Three"));
+    }
+
+    #[test]
+    fn plain_text_has_no_lines() {
+        assert!(Lyrics::parse("just some words\nwithout times").is_empty());
+    }
+}
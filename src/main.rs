@@ -1,14 +1,34 @@
+#[cfg(feature = "gui")]
 mod cxxqt_object;
-mod modules;
-mod orchestrator;
-mod osc;
-pub mod smalltext;
 
+use osc_chatbox::headless;
+#[cfg(feature = "gui")]
+use osc_chatbox::{config::Config, logging};
+
+#[cfg(feature = "gui")]
 use cxx_qt::casting::Upcast;
+#[cfg(feature = "gui")]
 use cxx_qt_lib::{QGuiApplication, QQmlApplicationEngine, QQmlEngine, QString, QUrl};
+#[cfg(feature = "gui")]
 use std::pin::Pin;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    #[cfg(feature = "gui")]
+    if !headless::requested(&args) {
+        let verbose = args.iter().any(|a| a == "-v" || a == "--verbose");
+        let config = Config::load_or_default(&Config::default_path()).unwrap_or_default();
+        let _guard = logging::init(&config.logging, verbose);
+        run_gui();
+        return;
+    }
+
+    std::process::exit(headless::run(&args));
+}
+
+#[cfg(feature = "gui")]
+fn run_gui() {
     let mut app = QGuiApplication::new();
 
     if let Some(ref mut app_pin) = app.as_mut() {
@@ -30,7 +50,7 @@ fn main() {
         let engine: Pin<&mut QQmlEngine> = engine.upcast_pin();
         engine
             .on_quit(|_| {
-                println!("Shutting down.");
+                tracing::info!("shutting down");
             })
             .release();
     }
@@ -1,5 +1,139 @@
 use super::Module;
-use std::time::{Duration, Instant};
+use crate::events::Event;
+use crate::osc::receiver::{OscEvent, OscReceiver, Subscription};
+use rosc::OscType;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+const AFK_PARAMETER: &str = "AFK";
+/// Going away is announced ahead of anything but chat.
+const EVENT_PRIORITY: u8 = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivitySourceKind {
+    Osc,
+    Idle,
+}
+
+/// What an activity source observed since it was last polled.
+#[derive(Debug, Clone, Copy)]
+pub enum Activity {
+    /// The user did something; the AFK timer restarts.
+    Active,
+    /// The user has done nothing since the given instant. They count as
+    /// away once that is longer than the timeout.
+    Idle(Instant),
+    /// The source knows the user has been away since the given instant.
+    Away(Instant),
+}
+
+pub trait ActivitySource: Send {
+    fn kind(&self) -> ActivitySourceKind;
+    fn poll(&mut self) -> Option<Activity>;
+}
+
+/// Follows VRChat's built-in `AFK` avatar parameter. Once VRChat has sent
+/// it, it decides alone whether the user is away.
+pub struct OscActivitySource {
+    events: Receiver<OscEvent>,
+    /// `None` until VRChat sends the parameter, then `Some(None)` while
+    /// present and `Some(Some(since))` while away.
+    state: Option<Option<Instant>>,
+}
+
+impl OscActivitySource {
+    pub fn new(receiver: &OscReceiver) -> Self {
+        let state = receiver
+            .state()
+            .bool_parameter(AFK_PARAMETER)
+            .map(|afk| afk.then(Instant::now));
+
+        Self {
+            events: receiver.subscribe(Subscription::Parameter(AFK_PARAMETER.to_string())),
+            state,
+        }
+    }
+}
+
+impl ActivitySource for OscActivitySource {
+    fn kind(&self) -> ActivitySourceKind {
+        ActivitySourceKind::Osc
+    }
+
+    fn poll(&mut self) -> Option<Activity> {
+        for event in self.events.try_iter() {
+            if let OscEvent::AvatarParameter { value, .. } = event {
+                let afk = match value {
+                    OscType::Bool(b) => b,
+                    OscType::Int(i) => i != 0,
+                    _ => continue,
+                };
+                let since = self.state.flatten();
+                self.state = Some(afk.then(|| since.unwrap_or_else(Instant::now)));
+            }
+        }
+
+        self.state
+            .map(|away| away.map_or(Activity::Active, Activity::Away))
+    }
+}
+
+/// Reads the desktop idle state that logind exposes on the system bus.
+pub struct IdleActivitySource {
+    proxy: zbus::blocking::Proxy<'static>,
+}
+
+impl IdleActivitySource {
+    pub fn new() -> Result<Self, zbus::Error> {
+        let connection = zbus::blocking::Connection::system()?;
+        let proxy = zbus::blocking::Proxy::new(
+            &connection,
+            "org.freedesktop.login1",
+            "/org/freedesktop/login1/session/auto",
+            "org.freedesktop.login1.Session",
+        )?;
+        Ok(Self { proxy })
+    }
+}
+
+impl ActivitySource for IdleActivitySource {
+    fn kind(&self) -> ActivitySourceKind {
+        ActivitySourceKind::Idle
+    }
+
+    fn poll(&mut self) -> Option<Activity> {
+        let idle: bool = match self.proxy.get_property("IdleHint") {
+            Ok(idle) => idle,
+            Err(e) => {
+                debug!("can't read the logind idle hint: {e}");
+                return None;
+            }
+        };
+        if !idle {
+            return Some(Activity::Active);
+        }
+
+        // IdleSinceHint is wall-clock microseconds since the epoch
+        let since_us: u64 = match self.proxy.get_property("IdleSinceHint") {
+            Ok(since_us) => since_us,
+            Err(e) => {
+                debug!("can't read the logind idle time: {e}");
+                return None;
+            }
+        };
+        let now_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_micros() as u64;
+        let idle_for = Duration::from_micros(now_us.saturating_sub(since_us));
+        Some(Activity::Idle(
+            Instant::now()
+                .checked_sub(idle_for)
+                .unwrap_or_else(Instant::now),
+        ))
+    }
+}
 
 pub struct AfkModule {
     enabled: bool,
@@ -7,6 +141,14 @@ pub struct AfkModule {
     last_activity: Instant,
     afk_text: String,
     is_afk: bool,
+    chat_activity: bool,
+    sources: Vec<Box<dyn ActivitySource>>,
+    away_for: Option<String>,
+    announce: bool,
+    /// Whether the last tick found the user away, for raising events on
+    /// changes; `is_afk` is also cleared outside of ticks.
+    was_afk: bool,
+    events: Vec<Event>,
 }
 
 impl AfkModule {
@@ -17,6 +159,12 @@ impl AfkModule {
             last_activity: Instant::now(),
             afk_text: "AFK".to_string(),
             is_afk: false,
+            chat_activity: true,
+            sources: Vec::new(),
+            away_for: None,
+            announce: false,
+            was_afk: false,
+            events: Vec::new(),
         }
     }
 
@@ -24,10 +172,43 @@ impl AfkModule {
         self.timeout = Duration::from_secs(secs);
     }
 
+    /// Whether going away and coming back raise events.
+    pub fn set_announce(&mut self, announce: bool) {
+        self.announce = announce;
+    }
+
     pub fn set_afk_text(&mut self, text: String) {
         self.afk_text = text;
     }
 
+    /// Whether sending a chat message counts as activity.
+    pub fn set_chat_activity(&mut self, enabled: bool) {
+        self.chat_activity = enabled;
+    }
+
+    pub fn has_source(&self, kind: ActivitySourceKind) -> bool {
+        self.sources.iter().any(|s| s.kind() == kind)
+    }
+
+    pub fn add_source(&mut self, source: Box<dyn ActivitySource>) {
+        self.remove_source(source.kind());
+        debug!("following {:?} activity", source.kind());
+        self.sources.push(source);
+    }
+
+    pub fn remove_source(&mut self, kind: ActivitySourceKind) {
+        if self.has_source(kind) {
+            debug!("no longer following {kind:?} activity");
+            self.sources.retain(|s| s.kind() != kind);
+        }
+    }
+
+    pub fn record_chat(&mut self) {
+        if self.chat_activity {
+            self.poke();
+        }
+    }
+
     pub fn poke(&mut self) {
         self.last_activity = Instant::now();
         self.is_afk = false;
@@ -36,9 +217,55 @@ impl AfkModule {
     pub fn is_afk(&self) -> bool {
         self.is_afk
     }
+
+    /// Polls every source and returns when the user went away, if they are.
+    /// A source that knows the user is away decides at once; idle time only
+    /// counts once it is longer than the timeout.
+    fn away_since(&mut self) -> Option<Instant> {
+        let polled: Vec<Activity> = self.sources.iter_mut().filter_map(|s| s.poll()).collect();
+        let earliest = |a: Option<Instant>, b: Instant| Some(a.map_or(b, |a| a.min(b)));
+        let mut away: Option<Instant> = None;
+        let mut idle: Option<Instant> = None;
+
+        for activity in polled {
+            match activity {
+                Activity::Active => self.poke(),
+                Activity::Idle(since) => idle = earliest(idle, since),
+                Activity::Away(since) => away = earliest(away, since),
+            }
+        }
+
+        if away.is_some() {
+            return away;
+        }
+        // Activity seen after a source went idle, such as chatting, counts
+        let since = idle.map_or(self.last_activity, |i| i.max(self.last_activity));
+        (since.elapsed() >= self.timeout).then_some(since)
+    }
+}
+
+fn format_away(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{secs}s")
+    } else if secs < 3600 {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+impl Default for AfkModule {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Module for AfkModule {
+    fn id(&self) -> &str {
+        "afk"
+    }
+
     fn name(&self) -> &str {
         "AFK"
     }
@@ -56,12 +283,170 @@ impl Module for AfkModule {
             return None;
         }
 
-        if self.last_activity.elapsed() >= self.timeout {
-            self.is_afk = true;
-            Some(self.afk_text.clone())
-        } else {
-            self.is_afk = false;
-            None
+        let since = self.away_since();
+        let away_for = self.away_for.take();
+        self.is_afk = since.is_some();
+        self.away_for = since.map(|since| format_away(since.elapsed()));
+
+        if self.is_afk != self.was_afk {
+            debug!("user is {}", if self.is_afk { "away" } else { "back" });
+        }
+        if self.announce && self.is_afk != self.was_afk {
+            let event = if self.is_afk {
+                Event::new("afk", self.afk_text.clone(), EVENT_PRIORITY)
+            } else {
+                let text = match away_for {
+                    Some(away_for) => format!("Back after {away_for}"),
+                    None => "Back".to_string(),
+                };
+                Event::new("afk:back", text, EVENT_PRIORITY)
+            };
+            self.events.push(event);
+        }
+        self.was_afk = self.is_afk;
+
+        self.away_for
+            .as_ref()
+            .map(|away_for| format!("{} {away_for}", self.afk_text))
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "duration" => self.away_for.clone(),
+            _ => None,
         }
     }
+
+    fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{self, Sender};
+
+    /// Reports whatever the test last stored.
+    struct Fake(Option<Activity>);
+
+    impl ActivitySource for Fake {
+        fn kind(&self) -> ActivitySourceKind {
+            ActivitySourceKind::Idle
+        }
+
+        fn poll(&mut self) -> Option<Activity> {
+            self.0
+        }
+    }
+
+    fn module(timeout_secs: u64, activity: Option<Activity>) -> AfkModule {
+        let mut afk = AfkModule::new();
+        afk.set_enabled(true);
+        afk.set_timeout_secs(timeout_secs);
+        afk.add_source(Box::new(Fake(activity)));
+        afk
+    }
+
+    fn ago(secs: u64) -> Instant {
+        Instant::now() - Duration::from_secs(secs)
+    }
+
+    /// An AFK module following an OSC source the test sends parameters to.
+    fn osc_module(timeout_secs: u64) -> (AfkModule, Sender<OscEvent>) {
+        let (sender, events) = mpsc::channel();
+        let mut afk = AfkModule::new();
+        afk.set_enabled(true);
+        afk.set_timeout_secs(timeout_secs);
+        afk.add_source(Box::new(OscActivitySource {
+            events,
+            state: None,
+        }));
+        (afk, sender)
+    }
+
+    fn send_afk(sender: &Sender<OscEvent>, value: OscType) {
+        sender
+            .send(OscEvent::AvatarParameter {
+                name: AFK_PARAMETER.to_string(),
+                value,
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn waits_for_the_timeout_on_reported_idle() {
+        let mut afk = module(300, Some(Activity::Idle(Instant::now())));
+        assert_eq!(afk.tick(), None);
+        assert!(!afk.is_afk());
+
+        // Idle for longer than the timeout, but the module only just started
+        let mut afk = module(300, Some(Activity::Idle(ago(600))));
+        assert_eq!(afk.tick(), None);
+
+        let mut afk = module(300, Some(Activity::Idle(ago(600))));
+        afk.last_activity = ago(900);
+        assert_eq!(afk.tick().as_deref(), Some("AFK 10m"));
+        assert!(afk.is_afk());
+    }
+
+    #[test]
+    fn chatting_counts_as_activity() {
+        let mut afk = module(60, Some(Activity::Idle(ago(600))));
+        afk.last_activity = ago(900);
+        afk.record_chat();
+        assert_eq!(afk.tick(), None);
+
+        let mut afk = module(60, Some(Activity::Active));
+        afk.last_activity = ago(900);
+        assert_eq!(afk.tick(), None);
+
+        let mut afk = module(60, None);
+        afk.last_activity = ago(120);
+        assert_eq!(afk.tick().as_deref(), Some("AFK 2m"));
+    }
+
+    #[test]
+    fn vrchat_keeps_a_present_player_active() {
+        let (mut afk, sender) = osc_module(60);
+        send_afk(&sender, OscType::Bool(false));
+        afk.last_activity = ago(120);
+        assert_eq!(afk.tick(), None);
+
+        // No new values, the last one still holds
+        afk.last_activity = ago(120);
+        assert_eq!(afk.tick(), None);
+        assert!(!afk.is_afk());
+
+        // Desktop idle doesn't count while VRChat says the player is there
+        afk.add_source(Box::new(Fake(Some(Activity::Idle(ago(600))))));
+        afk.last_activity = ago(120);
+        assert_eq!(afk.tick(), None);
+    }
+
+    #[test]
+    fn vrchat_afk_shows_at_once() {
+        let (mut afk, sender) = osc_module(300);
+        send_afk(&sender, OscType::Int(1));
+        assert_eq!(afk.tick().as_deref(), Some("AFK 0s"));
+        assert!(afk.is_afk());
+
+        // Chatting doesn't override VRChat
+        afk.record_chat();
+        assert!(afk.tick().is_some());
+
+        send_afk(&sender, OscType::Bool(false));
+        assert_eq!(afk.tick(), None);
+        assert!(!afk.is_afk());
+    }
+
+    #[test]
+    fn falls_back_to_the_timeout_until_vrchat_sends() {
+        let (mut afk, sender) = osc_module(60);
+        afk.last_activity = ago(120);
+        assert_eq!(afk.tick().as_deref(), Some("AFK 2m"));
+
+        send_afk(&sender, OscType::Bool(false));
+        assert_eq!(afk.tick(), None);
+    }
 }
@@ -0,0 +1,348 @@
+use super::Module;
+use crate::hwmon::{self, Kind, Sensor};
+use crate::smalltext::to_superscript;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Utilisation is sampled by the driver, so refreshing faster than this
+/// mostly shows noise.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+/// hwmon sensor labels for the GPU die, in order of preference. amdgpu
+/// calls it `edge`, Intel's xe driver `pkg`.
+const TEMP_LABELS: &[&str] = &["edge", "pkg", "gpu"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuBackend {
+    /// sysfs, then `nvidia-smi` when the NVIDIA driver is loaded.
+    Auto,
+    Sysfs,
+    NvidiaSmi,
+}
+
+impl GpuBackend {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            "auto" => Some(Self::Auto),
+            "sysfs" => Some(Self::Sysfs),
+            "nvidia-smi" => Some(Self::NvidiaSmi),
+            _ => None,
+        }
+    }
+}
+
+/// One reading; drivers only expose some of these.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GpuStats {
+    /// Percent of time the GPU was busy.
+    pub busy: Option<u32>,
+    pub vram_used: Option<u64>,
+    pub vram_total: Option<u64>,
+    /// Degrees Celsius.
+    pub temp: Option<f64>,
+}
+
+impl GpuStats {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+pub struct GpuModule {
+    enabled: bool,
+    backend: GpuBackend,
+    root: PathBuf,
+    /// DRM card to read, e.g. `card1`; empty uses the first with stats.
+    card: String,
+    /// `nvidia-smi` isn't installed, so it isn't tried again.
+    no_nvidia_smi: bool,
+    stats: Option<GpuStats>,
+}
+
+impl GpuModule {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            backend: GpuBackend::Auto,
+            root: PathBuf::from(hwmon::DEFAULT_SYSFS_ROOT),
+            card: String::new(),
+            no_nvidia_smi: false,
+            stats: None,
+        }
+    }
+
+    pub fn set_backend(&mut self, backend: GpuBackend) {
+        self.backend = backend;
+    }
+
+    /// Where sysfs is mounted, normally `/sys`.
+    pub fn set_sysfs_root(&mut self, root: PathBuf) {
+        self.root = root;
+    }
+
+    pub fn set_card(&mut self, card: String) {
+        self.card = card;
+    }
+
+    fn read(&mut self) -> Option<GpuStats> {
+        let sysfs = || read_sysfs(&self.root, &self.card);
+        match self.backend {
+            GpuBackend::Sysfs => sysfs(),
+            GpuBackend::NvidiaSmi => self.query_nvidia_smi(),
+            GpuBackend::Auto => sysfs().or_else(|| {
+                let nvidia = self.root.join("module/nvidia").exists()
+                    || Path::new("/proc/driver/nvidia").exists();
+                nvidia.then(|| self.query_nvidia_smi()).flatten()
+            }),
+        }
+    }
+
+    fn query_nvidia_smi(&mut self) -> Option<GpuStats> {
+        if self.no_nvidia_smi {
+            return None;
+        }
+        let output = Command::new("nvidia-smi")
+            .args([
+                "--query-gpu=utilization.gpu,memory.used,memory.total,temperature.gpu",
+                "--format=csv,noheader,nounits",
+            ])
+            .output();
+        let output = match output {
+            Ok(output) if output.status.success() => output,
+            Ok(output) => {
+                debug!("nvidia-smi exited with {}", output.status);
+                return None;
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                warn!("nvidia-smi isn't installed, GPU stats are unavailable");
+                self.no_nvidia_smi = true;
+                return None;
+            }
+            Err(e) => {
+                warn!("can't run nvidia-smi: {e}");
+                return None;
+            }
+        };
+        parse_nvidia_smi(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// Parses the first GPU from `nvidia-smi --format=csv,noheader,nounits`.
+/// Memory is reported in MiB; unsupported fields read `[N/A]`.
+fn parse_nvidia_smi(output: &str) -> Option<GpuStats> {
+    let line = output.lines().next()?;
+    let mut values = line.split(',').map(str::trim);
+    let mut next = || values.next().and_then(|v| v.parse::<f64>().ok());
+    let stats = GpuStats {
+        busy: next().map(|v| v as u32),
+        vram_used: next().map(|v| v as u64 * 1024 * 1024),
+        vram_total: next().map(|v| v as u64 * 1024 * 1024),
+        temp: next(),
+    };
+    (!stats.is_empty()).then_some(stats)
+}
+
+/// Reads stats for `card` under `root/class/drm`, or for the first card
+/// that has any when `card` is empty.
+fn read_sysfs(root: &Path, card: &str) -> Option<GpuStats> {
+    let drm = root.join("class/drm");
+    if !card.is_empty() {
+        return read_card(&drm.join(card).join("device"));
+    }
+
+    let mut cards: Vec<(u32, PathBuf)> = fs::read_dir(&drm)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            // Connectors such as `card0-DP-1` share the prefix
+            let name = entry.file_name();
+            let number = name.to_str()?.strip_prefix("card")?.parse().ok()?;
+            Some((number, entry.path()))
+        })
+        .collect();
+    cards.sort();
+    cards
+        .iter()
+        .find_map(|(_, path)| read_card(&path.join("device")))
+}
+
+fn read_card(device: &Path) -> Option<GpuStats> {
+    let stats = GpuStats {
+        busy: read_number(&device.join("gpu_busy_percent")).map(|v| v as u32),
+        vram_used: read_number(&device.join("mem_info_vram_used")),
+        vram_total: read_number(&device.join("mem_info_vram_total")),
+        temp: read_temp(device),
+    };
+    (!stats.is_empty()).then_some(stats)
+}
+
+/// The GPU die temperature from the card's hwmon sensors, preferring the
+/// labels in [`TEMP_LABELS`] over the first sensor.
+fn read_temp(device: &Path) -> Option<f64> {
+    let dir = fs::read_dir(device.join("hwmon"))
+        .ok()?
+        .next()?
+        .ok()?
+        .path();
+    let temps: Vec<Sensor> = hwmon::read_chip(&dir)
+        .into_iter()
+        .filter(|sensor| sensor.kind == Kind::Temp)
+        .collect();
+    TEMP_LABELS
+        .iter()
+        .find_map(|wanted| {
+            temps
+                .iter()
+                .find(|sensor| sensor.label.eq_ignore_ascii_case(wanted))
+        })
+        .or(temps.first())
+        .map(|sensor| sensor.value)
+}
+
+fn read_number(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+impl Default for GpuModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for GpuModule {
+    fn id(&self) -> &str {
+        "gpu"
+    }
+
+    fn name(&self) -> &str {
+        "GPU"
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.stats = None;
+        }
+    }
+
+    fn refresh_interval(&self) -> Duration {
+        REFRESH_INTERVAL
+    }
+
+    fn tick(&mut self) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+
+        self.stats = self.read();
+        let parts: Vec<String> = ["usage", "vram", "temp"]
+            .iter()
+            .filter_map(|name| self.field(name))
+            .collect();
+        if parts.is_empty() {
+            return None;
+        }
+        Some(format!("GPU {}", to_superscript(&parts.join(" "))))
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        let stats = self.stats.as_ref()?;
+        match name {
+            "usage" => stats.busy.map(|busy| format!("{busy}%")),
+            "vram" => {
+                let used = stats.vram_used? / 1024 / 1024;
+                let total = stats.vram_total? / 1024 / 1024;
+                Some(format!("{used}/{total}mb"))
+            }
+            "temp" => stats.temp.map(|temp| format!("{temp:.0}°c")),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Fixture;
+
+    #[test]
+    fn reads_amdgpu_card() {
+        let root = Fixture::new(
+            "gpu-amd",
+            &[
+                ("class/drm/card0-DP-1/status", "connected\n"),
+                ("class/drm/card1/device/gpu_busy_percent", "42\n"),
+                ("class/drm/card1/device/mem_info_vram_used", "2147483648\n"),
+                ("class/drm/card1/device/mem_info_vram_total", "8589934592\n"),
+                ("class/drm/card1/device/hwmon/hwmon3/temp1_input", "55000\n"),
+                ("class/drm/card1/device/hwmon/hwmon3/temp1_label", "edge\n"),
+                ("class/drm/card1/device/hwmon/hwmon3/temp2_input", "71000\n"),
+                (
+                    "class/drm/card1/device/hwmon/hwmon3/temp2_label",
+                    "junction\n",
+                ),
+            ],
+        );
+        let stats = read_sysfs(&root, "").unwrap();
+        assert_eq!(stats.busy, Some(42));
+        assert_eq!(stats.vram_used, Some(2 << 30));
+        assert_eq!(stats.vram_total, Some(8 << 30));
+        assert_eq!(stats.temp, Some(55.0));
+    }
+
+    #[test]
+    fn skips_cards_without_stats() {
+        let root = Fixture::new(
+            "gpu-skip",
+            &[
+                ("class/drm/card0/device/vendor", "0x8086\n"),
+                ("class/drm/card1/device/hwmon/hwmon0/temp1_input", "48500\n"),
+            ],
+        );
+        let stats = read_sysfs(&root, "").unwrap();
+        assert_eq!(stats.busy, None);
+        assert_eq!(stats.temp, Some(48.5));
+        assert_eq!(read_sysfs(&root, "card0"), None);
+    }
+
+    #[test]
+    fn module_formats_fields() {
+        let root = Fixture::new(
+            "gpu-module",
+            &[
+                ("class/drm/card0/device/gpu_busy_percent", "7\n"),
+                ("class/drm/card0/device/mem_info_vram_used", "536870912\n"),
+                ("class/drm/card0/device/mem_info_vram_total", "4294967296\n"),
+            ],
+        );
+        let mut gpu = GpuModule::new();
+        gpu.set_backend(GpuBackend::Sysfs);
+        gpu.set_sysfs_root(root.to_path_buf());
+        gpu.set_enabled(true);
+        assert!(gpu.tick().is_some());
+        assert_eq!(gpu.field("usage").as_deref(), Some("7%"));
+        assert_eq!(gpu.field("vram").as_deref(), Some("512/4096mb"));
+        assert_eq!(gpu.field("temp"), None);
+    }
+
+    #[test]
+    fn parses_nvidia_smi_output() {
+        let stats = parse_nvidia_smi("31, 1024, 12288, 64\n").unwrap();
+        assert_eq!(stats.busy, Some(31));
+        assert_eq!(stats.vram_used, Some(1 << 30));
+        assert_eq!(stats.vram_total, Some(12 << 30));
+        assert_eq!(stats.temp, Some(64.0));
+
+        let stats = parse_nvidia_smi("[N/A], 512, 4096, [N/A]\n").unwrap();
+        assert_eq!(stats.busy, None);
+        assert_eq!(stats.temp, None);
+        assert_eq!(parse_nvidia_smi(""), None);
+    }
+}
@@ -1,10 +1,41 @@
 use super::Module;
+use crate::events::Event;
+use futures_util::StreamExt;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, error, info, warn};
+use url::Url;
+
+pub const DEFAULT_URL: &str = "wss://dev.pulsoid.net/api/v1/data/real_time";
+const STALE_AFTER: Duration = Duration::from_secs(10);
+const EVENT_PRIORITY: u8 = 70;
+/// How far back past a threshold the rate has to go before it can alert
+/// again, so hovering around it doesn't.
+const ALERT_HYSTERESIS: u32 = 5;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
 
 pub struct HeartrateModule {
     enabled: bool,
     bpm: Arc<Mutex<Option<u32>>>,
     token: String,
+    url: String,
+    client: Option<HeartrateClient>,
+    /// Thresholds that raise an event when crossed; 0 disables either.
+    alert_above: u32,
+    alert_below: u32,
+    alert: Option<Alert>,
+    events: Vec<Event>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Alert {
+    High,
+    Low,
 }
 
 impl HeartrateModule {
@@ -13,17 +44,49 @@ impl HeartrateModule {
             enabled: false,
             bpm: Arc::new(Mutex::new(None)),
             token: String::new(),
+            url: DEFAULT_URL.to_string(),
+            client: None,
+            alert_above: 0,
+            alert_below: 0,
+            alert: None,
+            events: Vec::new(),
         }
     }
 
     pub fn set_token(&mut self, token: String) {
-        self.token = token;
+        if self.token != token {
+            self.token = token;
+            self.restart_client();
+        }
     }
 
     pub fn token(&self) -> &str {
         &self.token
     }
 
+    pub fn set_url(&mut self, url: String) {
+        let url = if url.trim().is_empty() {
+            DEFAULT_URL.to_string()
+        } else {
+            url.trim().to_string()
+        };
+        if self.url != url {
+            self.url = url;
+            self.restart_client();
+        }
+    }
+
+    /// Raises an event when the rate goes above `above` or below `below`
+    /// BPM; 0 disables either.
+    pub fn set_alerts(&mut self, above: u32, below: u32) {
+        self.alert_above = above;
+        self.alert_below = below;
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
     pub fn bpm_handle(&self) -> Arc<Mutex<Option<u32>>> {
         self.bpm.clone()
     }
@@ -33,9 +96,205 @@ impl HeartrateModule {
             *bpm = value;
         }
     }
+
+    fn check_alerts(&mut self, bpm: u32) {
+        let (above, below) = (self.alert_above, self.alert_below);
+        let alert = match self.alert {
+            Some(Alert::High) if above > 0 && bpm + ALERT_HYSTERESIS > above => Some(Alert::High),
+            Some(Alert::Low) if below > 0 && bpm < below + ALERT_HYSTERESIS => Some(Alert::Low),
+            _ if above > 0 && bpm >= above => Some(Alert::High),
+            _ if below > 0 && bpm <= below => Some(Alert::Low),
+            _ => None,
+        };
+
+        if alert != self.alert {
+            match alert {
+                Some(Alert::High) => self.events.push(Event::new(
+                    "heartrate:high",
+                    format!("Heart rate high: {bpm} BPM"),
+                    EVENT_PRIORITY,
+                )),
+                Some(Alert::Low) => self.events.push(Event::new(
+                    "heartrate:low",
+                    format!("Heart rate low: {bpm} BPM"),
+                    EVENT_PRIORITY,
+                )),
+                None => {}
+            }
+            self.alert = alert;
+        }
+    }
+
+    /// Stops any running client and starts a new one if the module is enabled
+    /// and has a token to connect with.
+    fn restart_client(&mut self) {
+        self.client = None;
+        self.set_bpm(None);
+
+        if !self.enabled || self.token.is_empty() {
+            return;
+        }
+
+        match connect_url(&self.url, &self.token) {
+            Ok(url) => self.client = Some(HeartrateClient::spawn(url, self.bpm.clone())),
+            Err(e) => error!("invalid heart rate URL {:?}: {e}", self.url),
+        }
+    }
+}
+
+/// Builds the WebSocket URL, appending the token as `access_token` unless the
+/// configured URL already carries one.
+fn connect_url(base: &str, token: &str) -> Result<Url, url::ParseError> {
+    let mut url = Url::parse(base)?;
+    if !url.query_pairs().any(|(k, _)| k == "access_token") {
+        url.query_pairs_mut().append_pair("access_token", token);
+    }
+    Ok(url)
+}
+
+/// Extracts a BPM value from a Pulsoid-style JSON payload, e.g.
+/// `{"measured_at": 1700000000000, "data": {"heart_rate": 72}}`.
+fn parse_bpm(text: &str) -> Option<u32> {
+    let value: serde_json::Value = serde_json::from_str(text).ok()?;
+    let data = value.get("data").unwrap_or(&value);
+
+    ["heart_rate", "heartRate", "bpm"]
+        .iter()
+        .find_map(|key| data.get(key))
+        .and_then(|v| v.as_u64().or_else(|| v.as_f64().map(|f| f.round() as u64)))
+        .filter(|&bpm| bpm > 0)
+        .map(|bpm| bpm as u32)
+}
+
+/// Background WebSocket connection feeding the shared BPM value. The
+/// connection runs on its own thread and is shut down when dropped.
+struct HeartrateClient {
+    shutdown: watch::Sender<bool>,
+}
+
+impl HeartrateClient {
+    fn spawn(url: Url, bpm: Arc<Mutex<Option<u32>>>) -> Self {
+        let (shutdown, shutdown_rx) = watch::channel(false);
+
+        let spawned = thread::Builder::new()
+            .name("heartrate".to_string())
+            .spawn(move || {
+                let runtime = match tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                {
+                    Ok(rt) => rt,
+                    Err(e) => {
+                        error!("can't start heart rate runtime: {e}");
+                        return;
+                    }
+                };
+                runtime.block_on(run(url, bpm, shutdown_rx, STALE_AFTER));
+            });
+
+        if let Err(e) = spawned {
+            error!("can't start heart rate thread: {e}");
+        }
+
+        Self { shutdown }
+    }
+}
+
+impl Drop for HeartrateClient {
+    fn drop(&mut self) {
+        let _ = self.shutdown.send(true);
+    }
+}
+
+/// Keeps a connection to `url` open until `shutdown`, clearing the BPM when
+/// nothing arrives for `stale_after`.
+async fn run(
+    url: Url,
+    bpm: Arc<Mutex<Option<u32>>>,
+    mut shutdown: watch::Receiver<bool>,
+    stale_after: Duration,
+) {
+    let mut backoff = MIN_BACKOFF;
+
+    loop {
+        let mut received = false;
+        tokio::select! {
+            result = stream(&url, &bpm, &mut received, stale_after) => {
+                if let Err(e) = result {
+                    warn!("heart rate connection lost: {e}");
+                }
+            }
+            _ = shutdown.changed() => break,
+        }
+
+        store(&bpm, None);
+
+        // Only back off further when the connection never produced data
+        if received {
+            backoff = MIN_BACKOFF;
+        }
+
+        debug!("reconnecting to heart rate in {backoff:?}");
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = shutdown.changed() => break,
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+
+    store(&bpm, None);
+}
+
+async fn stream(
+    url: &Url,
+    bpm: &Arc<Mutex<Option<u32>>>,
+    received: &mut bool,
+    stale_after: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // The query carries the access token, so it is left out
+    let host = url.host_str().unwrap_or_default();
+    info!("connecting to heart rate at {host}");
+    let (mut ws, _) = connect_async(url.as_str()).await?;
+    info!("connected to heart rate at {host}");
+
+    loop {
+        let msg = match tokio::time::timeout(stale_after, ws.next()).await {
+            Ok(Some(msg)) => msg?,
+            Ok(None) => return Err("connection closed".into()),
+            Err(_) => return Err("no data received, reconnecting".into()),
+        };
+
+        let text = match msg {
+            Message::Text(text) => text.to_string(),
+            Message::Binary(data) => String::from_utf8_lossy(&data).into_owned(),
+            Message::Close(_) => return Err("connection closed by server".into()),
+            _ => continue,
+        };
+
+        if let Some(value) = parse_bpm(&text) {
+            *received = true;
+            store(bpm, Some(value));
+        }
+    }
+}
+
+fn store(bpm: &Arc<Mutex<Option<u32>>>, value: Option<u32>) {
+    if let Ok(mut bpm) = bpm.lock() {
+        *bpm = value;
+    }
+}
+
+impl Default for HeartrateModule {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Module for HeartrateModule {
+    fn id(&self) -> &str {
+        "heartrate"
+    }
+
     fn name(&self) -> &str {
         "Heart Rate"
     }
@@ -45,7 +304,10 @@ impl Module for HeartrateModule {
     }
 
     fn set_enabled(&mut self, enabled: bool) {
-        self.enabled = enabled;
+        if self.enabled != enabled {
+            self.enabled = enabled;
+            self.restart_client();
+        }
     }
 
     fn tick(&mut self) -> Option<String> {
@@ -54,6 +316,100 @@ impl Module for HeartrateModule {
         }
 
         let bpm = self.bpm.lock().ok()?.as_ref().copied();
+        if let Some(bpm) = bpm {
+            self.check_alerts(bpm);
+        }
         bpm.map(|v| format!("{v} BPM"))
     }
+
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "bpm" => self.bpm.lock().ok()?.map(|v| v.to_string()),
+            _ => None,
+        }
+    }
+
+    fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::SinkExt;
+    use std::time::Instant;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+    use tokio_tungstenite::accept_async;
+
+    async fn wait_for(bpm: &Arc<Mutex<Option<u32>>>, want: Option<u32>) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while *bpm.lock().unwrap() != want {
+            assert!(Instant::now() < deadline, "BPM never became {want:?}");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[test]
+    fn parses_payloads() {
+        assert_eq!(
+            parse_bpm(r#"{"measured_at": 1, "data": {"heart_rate": 72}}"#),
+            Some(72)
+        );
+        assert_eq!(parse_bpm(r#"{"heartRate": 64.6}"#), Some(65));
+        assert_eq!(parse_bpm(r#"{"data": {"heart_rate": 0}}"#), None);
+        assert_eq!(parse_bpm("72"), None);
+
+        let url = connect_url(DEFAULT_URL, "secret").unwrap();
+        assert_eq!(url.query(), Some("access_token=secret"));
+        let url = connect_url("ws://localhost/?access_token=mine", "secret").unwrap();
+        assert_eq!(url.query(), Some("access_token=mine"));
+    }
+
+    #[tokio::test]
+    async fn follows_a_local_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (step, mut next_step) = mpsc::unbounded_channel::<()>();
+
+        let server = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(tcp).await.unwrap();
+            ws.send(Message::text(r#"{"data": {"heart_rate": 72}}"#))
+                .await
+                .unwrap();
+            next_step.recv().await;
+            ws.close(None).await.unwrap();
+
+            // The client comes back, gets one reading and then nothing
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(tcp).await.unwrap();
+            ws.send(Message::text(r#"{"bpm": 90}"#)).await.unwrap();
+            next_step.recv().await;
+        });
+
+        let bpm = Arc::new(Mutex::new(None));
+        let url = connect_url(&format!("ws://{addr}/"), "token").unwrap();
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let client = tokio::spawn(run(
+            url,
+            bpm.clone(),
+            shutdown_rx,
+            Duration::from_millis(300),
+        ));
+
+        wait_for(&bpm, Some(72)).await;
+        // Closing clears the reading until the reconnect brings a new one
+        step.send(()).unwrap();
+        wait_for(&bpm, None).await;
+        wait_for(&bpm, Some(90)).await;
+        // Silence makes it stale
+        wait_for(&bpm, None).await;
+
+        shutdown.send(true).unwrap();
+        client.await.unwrap();
+        step.send(()).unwrap();
+        server.await.unwrap();
+    }
 }
@@ -0,0 +1,179 @@
+use super::media::MediaInfo;
+use super::Module;
+use crate::lrc::Lyrics;
+use crate::mpris::PlaybackStatus;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Directory under the config directory searched when none is set.
+pub const LYRICS_DIR: &str = "lyrics";
+const LRC_EXT: &str = "lrc";
+
+/// Shows the current line of synced lyrics for the track the media module
+/// picked. Lyrics come from `.lrc` files named `Artist - Title.lrc` or
+/// `Title.lrc`, or else from the player's `xesam:asText` metadata.
+pub struct LyricsModule {
+    enabled: bool,
+    dir: PathBuf,
+    track: Option<MediaInfo>,
+    /// Artist, title and embedded lyrics the current lyrics were resolved
+    /// for, so lookups only happen when the track changes.
+    resolved_for: Option<(String, String, String)>,
+    lyrics: Option<Lyrics>,
+    index: Option<usize>,
+}
+
+impl LyricsModule {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::new(),
+            track: None,
+            resolved_for: None,
+            lyrics: None,
+            index: None,
+        }
+    }
+
+    /// Directory of `.lrc` files to search.
+    pub fn set_dir(&mut self, dir: PathBuf) {
+        if self.dir != dir {
+            self.dir = dir;
+            self.resolved_for = None;
+        }
+    }
+
+    /// The track to show lyrics for, usually from the media module's last
+    /// tick.
+    pub fn set_track(&mut self, track: Option<MediaInfo>) {
+        self.track = track;
+    }
+
+    fn resolve(&mut self) {
+        let Some(track) = &self.track else {
+            self.resolved_for = None;
+            self.lyrics = None;
+            return;
+        };
+        let key = (
+            track.artist.clone(),
+            track.title.clone(),
+            track.lyrics.clone(),
+        );
+        if self.resolved_for.as_ref() == Some(&key) {
+            return;
+        }
+
+        self.lyrics = find_file(&self.dir, &track.artist, &track.title)
+            .and_then(|path| match fs::read_to_string(&path) {
+                Ok(text) => Some(Lyrics::parse(&text)),
+                Err(e) => {
+                    warn!("can't read lyrics from {}: {e}", path.display());
+                    None
+                }
+            })
+            .filter(|lyrics| !lyrics.is_empty())
+            .or_else(|| Some(Lyrics::parse(&track.lyrics)).filter(|l| !l.is_empty()));
+        debug!(
+            found = self.lyrics.is_some(),
+            "looked up lyrics for {} - {}", track.artist, track.title
+        );
+        self.resolved_for = Some(key);
+    }
+}
+
+/// Finds `Artist - Title.lrc`, falling back to `Title.lrc`. Names are
+/// compared on letters and digits only, so case and punctuation that can't
+/// be used in file names don't matter.
+fn find_file(dir: &Path, artist: &str, title: &str) -> Option<PathBuf> {
+    if title.trim().is_empty() {
+        return None;
+    }
+    let entries = fs::read_dir(dir).ok()?;
+    let files: Vec<(String, PathBuf)> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == LRC_EXT))
+        .filter_map(|path| Some((normalize(path.file_stem()?.to_str()?), path)))
+        .collect();
+
+    let mut candidates = Vec::new();
+    if !artist.trim().is_empty() {
+        candidates.push(normalize(&format!("{artist} - {title}")));
+    }
+    candidates.push(normalize(title));
+
+    candidates.iter().find_map(|wanted| {
+        files
+            .iter()
+            .find(|(stem, _)| stem == wanted)
+            .map(|(_, path)| path.clone())
+    })
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl Default for LyricsModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for LyricsModule {
+    fn id(&self) -> &str {
+        "lyrics"
+    }
+
+    fn name(&self) -> &str {
+        "Lyrics"
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.resolved_for = None;
+            self.lyrics = None;
+            self.index = None;
+        }
+    }
+
+    fn tick(&mut self) -> Option<String> {
+        self.index = None;
+        if !self.enabled {
+            return None;
+        }
+
+        self.resolve();
+        let track = self.track.as_ref()?;
+        if track.status != PlaybackStatus::Playing {
+            return None;
+        }
+        let lyrics = self.lyrics.as_ref()?;
+        self.index = lyrics.index_at(track.position_now());
+        // Empty lines are instrumental breaks
+        self.field("line")
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        let lines = self.lyrics.as_ref()?.lines();
+        let index = self.index?;
+        let non_empty = |text: &str| (!text.is_empty()).then(|| text.to_string());
+        match name {
+            "line" => non_empty(&lines[index].text),
+            "next" => lines[index + 1..]
+                .iter()
+                .find_map(|line| non_empty(&line.text)),
+            _ => None,
+        }
+    }
+}
@@ -1,73 +1,274 @@
 use super::Module;
-use std::process::Command;
+use crate::budget::abbreviate;
+use crate::events::Event;
+use crate::mpris::{LoopStatus, MprisWatcher, PlaybackStatus, Player};
+use crate::template::Template;
+use std::time::{Duration, Instant};
+use tracing::debug;
 
-const PREFERRED_PLAYERS: &[&str] = &["spotify", "spotifyd"];
-const BAR_LENGTH: usize = 10;
+/// The classic two-line layout: track on top, progress below.
+pub const DEFAULT_TEMPLATE: &str =
+    "{icon} {?artist}{artist} - {/}{title}\n{bar} {position}/{length}";
+/// Announced when a new track starts playing.
+pub const DEFAULT_ANNOUNCE_TEMPLATE: &str = "Now playing: {?artist}{artist} - {/}{title}";
+const EVENT_PRIORITY: u8 = 50;
+pub const DEFAULT_BAR_LENGTH: usize = 10;
+pub const DEFAULT_BAR_FILLED: &str = "\u{2501}";
+pub const DEFAULT_BAR_EMPTY: &str = "\u{2015}";
+
+/// How the `{bar}` field draws playback progress.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarStyle {
+    pub length: usize,
+    pub filled: String,
+    pub empty: String,
+}
+
+impl Default for BarStyle {
+    fn default() -> Self {
+        Self {
+            length: DEFAULT_BAR_LENGTH,
+            filled: DEFAULT_BAR_FILLED.to_string(),
+            empty: DEFAULT_BAR_EMPTY.to_string(),
+        }
+    }
+}
 
 pub struct MediaModule {
     enabled: bool,
+    bus_address: String,
+    watcher: Option<MprisWatcher>,
+    current: Option<MediaInfo>,
+    preferred: Vec<String>,
+    blocklist: Vec<String>,
+    template: Template,
+    templates: Vec<(String, Template)>,
+    bar: BarStyle,
+    /// Longest title and artist shown before abbreviating; 0 is unlimited.
+    title_max_len: usize,
+    artist_max_len: usize,
+    announce: Option<Template>,
+    /// Artist and title of the last track that started playing.
+    announced: Option<(String, String)>,
+    events: Vec<Event>,
 }
 
-struct MediaInfo {
-    artist: String,
-    title: String,
-    status: String,
-    position_us: u64,
-    length_us: u64,
+/// The track shown on the last tick.
+#[derive(Debug, Clone)]
+pub struct MediaInfo {
+    pub player: String,
+    pub artist: String,
+    pub title: String,
+    album: String,
+    track_number: Option<u32>,
+    pub status: PlaybackStatus,
+    /// Playback position when the tick happened.
+    pub position_us: u64,
+    pub length_us: u64,
+    volume: Option<f64>,
+    shuffle: bool,
+    loop_status: LoopStatus,
+    pub lyrics: String,
+    captured_at: Instant,
 }
 
 impl MediaModule {
     pub fn new() -> Self {
-        Self { enabled: false }
+        Self {
+            enabled: false,
+            bus_address: String::new(),
+            watcher: None,
+            current: None,
+            preferred: Vec::new(),
+            blocklist: Vec::new(),
+            template: default_template(),
+            templates: Vec::new(),
+            bar: BarStyle::default(),
+            title_max_len: 0,
+            artist_max_len: 0,
+            announce: None,
+            announced: None,
+            events: Vec::new(),
+        }
     }
 
-    fn find_player(&self) -> Option<String> {
-        let output = Command::new("playerctl").args(["-l"]).output().ok()?;
-        let list = String::from_utf8_lossy(&output.stdout);
+    /// Output template for players without one of their own.
+    pub fn set_template(&mut self, template: Template) {
+        self.template = template;
+    }
 
-        for preferred in PREFERRED_PLAYERS {
-            for line in list.lines() {
-                if line.starts_with(preferred) {
-                    return Some(line.trim().to_string());
-                }
-            }
+    /// Template for the event raised when a new track starts playing;
+    /// `None` raises none.
+    pub fn set_announce(&mut self, template: Option<Template>) {
+        self.announce = template;
+    }
+
+    pub fn set_bar_style(&mut self, bar: BarStyle) {
+        self.bar = bar;
+    }
+
+    /// Abbreviates titles and artists longer than these; 0 never does.
+    pub fn set_max_lengths(&mut self, title: usize, artist: usize) {
+        self.title_max_len = title;
+        self.artist_max_len = artist;
+    }
+
+    /// Player names in order of preference, matched by prefix.
+    pub fn set_preferred_players(&mut self, players: Vec<String>) {
+        self.preferred = normalize_names(players);
+    }
+
+    /// Players that are never shown, matched by prefix.
+    pub fn set_blocklist(&mut self, players: Vec<String>) {
+        self.blocklist = normalize_names(players);
+    }
+
+    /// Output templates for specific players, tried in order. Each can use
+    /// the module's fields, e.g. `{icon} {title}`.
+    pub fn set_player_templates(&mut self, templates: Vec<(String, Template)>) {
+        self.templates = templates
+            .into_iter()
+            .map(|(player, template)| (player.trim().to_lowercase(), template))
+            .filter(|(player, _)| !player.is_empty())
+            .collect();
+    }
+
+    /// D-Bus address to find players on; empty uses the session bus.
+    pub fn set_bus_address(&mut self, address: String) {
+        let address = address.trim().to_string();
+        if self.bus_address != address {
+            self.bus_address = address;
+            self.restart_watcher();
         }
+    }
 
-        list.lines()
-            .next()
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-    }
-
-    fn query(&self, player: &str) -> Option<MediaInfo> {
-        let output = Command::new("playerctl")
-            .args([
-                "-p",
-                player,
-                "metadata",
-                "--format",
-                "{{artist}}\n{{title}}\n{{status}}\n{{position}}\n{{mpris:length}}",
-            ])
-            .output()
-            .ok()?;
-
-        if !output.status.success() {
-            return None;
+    pub fn current(&self) -> Option<&MediaInfo> {
+        self.current.as_ref()
+    }
+
+    /// Whether the player shown on the last tick was playing.
+    pub fn is_playing(&self) -> bool {
+        self.current
+            .as_ref()
+            .is_some_and(|info| info.status == PlaybackStatus::Playing)
+    }
+
+    /// Raises an event when the current track differs from the last one
+    /// that played. Pausing and resuming the same track doesn't count.
+    fn announce_track(&mut self) {
+        let Some(info) = &self.current else {
+            return;
+        };
+        if info.status != PlaybackStatus::Playing || info.title.is_empty() {
+            return;
+        }
+        let track = (info.artist.clone(), info.title.clone());
+        if self.announced.as_ref() == Some(&track) {
+            return;
         }
 
-        let text = String::from_utf8_lossy(&output.stdout);
-        let lines: Vec<&str> = text.lines().collect();
-        if lines.len() < 5 {
-            return None;
+        if let Some(template) = &self.announce {
+            let text = template.render(|key| self.field(key));
+            if !text.trim().is_empty() {
+                let key = format!("media:{} - {}", track.0, track.1);
+                self.events.push(Event::new(key, text, EVENT_PRIORITY));
+            }
         }
+        self.announced = Some(track);
+    }
 
-        Some(MediaInfo {
-            artist: lines[0].to_string(),
-            title: lines[1].to_string(),
-            status: lines[2].to_string(),
-            position_us: lines[3].parse().unwrap_or(0),
-            length_us: lines[4].parse().unwrap_or(0),
+    fn restart_watcher(&mut self) {
+        self.watcher = None;
+        self.current = None;
+        if self.enabled {
+            self.watcher = Some(MprisWatcher::spawn(self.bus_address.clone()));
+        }
+    }
+}
+
+fn default_template() -> Template {
+    Template::parse(DEFAULT_TEMPLATE).expect("default media template is valid")
+}
+
+fn normalize_names(names: Vec<String>) -> Vec<String> {
+    names
+        .into_iter()
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Index of the first of `names` that `player` (e.g.
+/// `firefox.instance_1_42`) starts with.
+fn match_index(names: &[String], player: &str) -> Option<usize> {
+    let player = player.to_lowercase();
+    names
+        .iter()
+        .position(|name| player.starts_with(name.as_str()))
+}
+
+/// Picks the player to show: playing beats paused beats stopped, then the
+/// preference order, then the order players appeared in.
+fn find_player<'a>(
+    players: &'a [Player],
+    preferred: &[String],
+    blocklist: &[String],
+) -> Option<&'a Player> {
+    let status_rank = |status: PlaybackStatus| match status {
+        PlaybackStatus::Playing => 0,
+        PlaybackStatus::Paused => 1,
+        PlaybackStatus::Stopped => 2,
+    };
+
+    players
+        .iter()
+        .filter(|p| match_index(blocklist, p.name()).is_none())
+        .filter(|p| !p.artist.is_empty() || !p.title.is_empty())
+        .enumerate()
+        .min_by_key(|(index, p)| {
+            let preference = match_index(preferred, p.name()).unwrap_or(preferred.len());
+            (status_rank(p.status), preference, *index)
         })
+        .map(|(_, p)| p)
+}
+
+impl From<&Player> for MediaInfo {
+    fn from(player: &Player) -> Self {
+        Self {
+            player: player.name().to_string(),
+            artist: player.artist.clone(),
+            title: player.title.clone(),
+            album: player.album.clone(),
+            track_number: player.track_number,
+            status: player.status,
+            position_us: player.position_us(),
+            length_us: player.length_us,
+            volume: player.volume,
+            shuffle: player.shuffle,
+            loop_status: player.loop_status,
+            lyrics: player.lyrics.clone(),
+            captured_at: Instant::now(),
+        }
+    }
+}
+
+impl MediaInfo {
+    /// `position_us` advanced by the time since the tick while playing.
+    pub fn position_now(&self) -> Duration {
+        let position = Duration::from_micros(self.position_us);
+        if self.status == PlaybackStatus::Playing {
+            position + self.captured_at.elapsed()
+        } else {
+            position
+        }
+    }
+
+    fn icon(&self) -> &'static str {
+        match self.status {
+            PlaybackStatus::Playing => "\u{25B6}",
+            PlaybackStatus::Paused => "\u{23F8}",
+            PlaybackStatus::Stopped => "\u{25A0}",
+        }
     }
 }
 
@@ -78,20 +279,41 @@ fn format_time(us: u64) -> String {
     format!("{mins}:{secs:02}")
 }
 
-fn progress_bar(position: u64, length: u64) -> String {
-    if length == 0 {
-        return "\u{2015}".repeat(BAR_LENGTH);
-    }
+fn progress_bar(position: u64, length: u64, style: &BarStyle) -> String {
+    let filled = if length == 0 {
+        0
+    } else {
+        let fraction = position as f64 / length as f64;
+        ((fraction * style.length as f64).round() as usize).min(style.length)
+    };
+    let empty = style.length - filled;
 
-    let fraction = position as f64 / length as f64;
-    let filled = (fraction * BAR_LENGTH as f64).round() as usize;
-    let filled = filled.min(BAR_LENGTH);
-    let empty = BAR_LENGTH - filled;
+    format!(
+        "{}{}",
+        style.filled.repeat(filled),
+        style.empty.repeat(empty)
+    )
+}
 
-    format!("{}{}", "\u{2501}".repeat(filled), "\u{2015}".repeat(empty))
+/// Shortens `text` to `max` units unless `max` is 0.
+fn limit(text: &str, max: usize) -> String {
+    if max == 0 {
+        return text.to_string();
+    }
+    abbreviate(text, max).unwrap_or_default()
+}
+
+impl Default for MediaModule {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Module for MediaModule {
+    fn id(&self) -> &str {
+        "media"
+    }
+
     fn name(&self) -> &str {
         "Media"
     }
@@ -101,7 +323,10 @@ impl Module for MediaModule {
     }
 
     fn set_enabled(&mut self, enabled: bool) {
-        self.enabled = enabled;
+        if self.enabled != enabled {
+            self.enabled = enabled;
+            self.restart_watcher();
+        }
     }
 
     fn tick(&mut self) -> Option<String> {
@@ -109,26 +334,61 @@ impl Module for MediaModule {
             return None;
         }
 
-        let player = self.find_player()?;
-        let info = self.query(&player)?;
-
-        if info.artist.is_empty() && info.title.is_empty() {
-            return None;
+        let players = self.watcher.as_ref()?.players();
+        let current = find_player(&players, &self.preferred, &self.blocklist).map(MediaInfo::from);
+        let player = |info: &Option<MediaInfo>| info.as_ref().map(|info| info.player.clone());
+        if player(&current) != player(&self.current) {
+            match &current {
+                Some(info) => debug!("showing media from {}", info.player),
+                None => debug!("no media player to show"),
+            }
         }
+        self.current = current;
+        self.announce_track();
+        let info = self.current.as_ref()?;
 
-        let icon = match info.status.as_str() {
-            "Playing" => "\u{25B6}",
-            "Paused" => "\u{23F8}",
-            _ => "\u{25A0}",
-        };
+        let template = self
+            .templates
+            .iter()
+            .find(|(player, _)| info.player.to_lowercase().starts_with(player.as_str()))
+            .map_or(&self.template, |(_, template)| template);
+        let text = template.render(|key| self.field(key));
+        (!text.trim().is_empty()).then_some(text)
+    }
 
-        let bar = progress_bar(info.position_us, info.length_us);
-        let pos = format_time(info.position_us);
-        let dur = format_time(info.length_us);
+    fn field(&self, name: &str) -> Option<String> {
+        let info = self.current.as_ref()?;
+        let non_empty = |text: String| (!text.is_empty()).then_some(text);
+        match name {
+            "player" => Some(info.player.clone()),
+            "artist" => non_empty(limit(&info.artist, self.artist_max_len)),
+            "title" => non_empty(limit(&info.title, self.title_max_len)),
+            "album" => non_empty(info.album.clone()),
+            "track" => info.track_number.map(|n| n.to_string()),
+            "status" => Some(info.status.as_str().to_string()),
+            "icon" => Some(info.icon().to_string()),
+            "position" => Some(format_time(info.position_us)),
+            "length" => Some(format_time(info.length_us)),
+            "remaining" => (info.length_us > 0).then(|| {
+                format!(
+                    "-{}",
+                    format_time(info.length_us.saturating_sub(info.position_us))
+                )
+            }),
+            "bar" => Some(progress_bar(info.position_us, info.length_us, &self.bar)),
+            "volume" => info.volume.map(|v| format!("{:.0}%", v * 100.0)),
+            // Only present when on, so templates can use `{?shuffle}`
+            "shuffle" => info.shuffle.then(|| "shuffle".to_string()),
+            "loop" => match info.loop_status {
+                LoopStatus::None => None,
+                LoopStatus::Track => Some("track".to_string()),
+                LoopStatus::Playlist => Some("playlist".to_string()),
+            },
+            _ => None,
+        }
+    }
 
-        Some(format!(
-            "{icon} {} - {}\n{bar} {pos}/{dur}",
-            info.artist, info.title
-        ))
+    fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
     }
 }
@@ -1,15 +1,79 @@
 pub mod afk;
+pub mod gpu;
 pub mod heartrate;
+pub mod lyrics;
 pub mod media;
 pub mod network;
+pub mod sensors;
 pub mod stats;
 pub mod status;
 pub mod system_details;
 pub mod time;
 
+use std::time::{Duration, Instant};
+
+use crate::events::Event;
+
 pub trait Module: Send {
+    /// Stable identifier used to reference the module from layout templates.
+    fn id(&self) -> &str;
     fn name(&self) -> &str;
     fn enabled(&self) -> bool;
     fn set_enabled(&mut self, enabled: bool);
     fn tick(&mut self) -> Option<String>;
+
+    /// How long output from `tick` stays fresh. The orchestrator reuses it
+    /// until then; zero ticks on every send.
+    fn refresh_interval(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    /// How long each item stays up in modules that cycle through several,
+    /// e.g. status lines. `None` for modules that don't rotate.
+    fn rotation_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// A single value from the most recent tick, e.g. `artist` for media.
+    fn field(&self, _name: &str) -> Option<String> {
+        None
+    }
+
+    /// Announcements raised by ticks since the last call.
+    fn take_events(&mut self) -> Vec<Event> {
+        Vec::new()
+    }
+}
+
+/// Cycles through a list on a fixed real-time period, no matter how often
+/// the current position is asked for.
+pub struct Rotation {
+    period: Duration,
+    started: Instant,
+}
+
+impl Rotation {
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    pub fn set_period(&mut self, period: Duration) {
+        self.period = period;
+    }
+
+    /// Position in a list of `len` items, which may change between calls.
+    pub fn index(&self, len: usize) -> usize {
+        if len == 0 || self.period.is_zero() {
+            return 0;
+        }
+        let steps = self.started.elapsed().as_millis() / self.period.as_millis();
+        (steps % len as u128) as usize
+    }
 }
@@ -1,12 +1,71 @@
 use super::Module;
 use crate::smalltext::to_superscript;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use sysinfo::Networks;
+use tracing::debug;
+
+/// How often counters are sampled. Rates are divided by the real time
+/// between samples, so this only sets how fresh they are.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+/// Samples closer together than this are too noisy to use.
+const MIN_SAMPLE: Duration = Duration::from_millis(200);
+
+pub const DEFAULT_SMOOTHING: f64 = 0.5;
+/// Interfaces whose traffic is already counted on a physical one, or never
+/// leaves the machine, matched by prefix.
+pub const DEFAULT_EXCLUDE: &[&str] = &["lo", "docker", "br-", "veth", "virbr", "tun", "tap", "wg"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateUnit {
+    Bytes,
+    Bits,
+}
+
+impl RateUnit {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            "bytes" => Some(Self::Bytes),
+            "bits" => Some(Self::Bits),
+            _ => None,
+        }
+    }
+}
+
+/// Decimal (kB, 1000) or binary (KiB, 1024) multiples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitPrefix {
+    Si,
+    Iec,
+}
+
+impl UnitPrefix {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            "si" => Some(Self::Si),
+            "iec" => Some(Self::Iec),
+            _ => None,
+        }
+    }
+}
 
 pub struct NetworkModule {
     enabled: bool,
     networks: Networks,
-    prev_rx: u64,
-    prev_tx: u64,
+    sampled_at: Instant,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    unit: RateUnit,
+    prefix: UnitPrefix,
+    smoothing: f64,
+    /// Lifetime bytes received and sent per interface at the last sample.
+    totals: HashMap<String, (u64, u64)>,
+    /// Interfaces the last sample counted, to log when they change.
+    counting: Vec<String>,
+    /// Smoothed bytes per second received and sent.
+    rates: Option<(f64, f64)>,
+    down: Option<String>,
+    up: Option<String>,
 }
 
 impl NetworkModule {
@@ -14,23 +73,156 @@ impl NetworkModule {
         Self {
             enabled: false,
             networks: Networks::new_with_refreshed_list(),
-            prev_rx: 0,
-            prev_tx: 0,
+            sampled_at: Instant::now(),
+            include: Vec::new(),
+            exclude: DEFAULT_EXCLUDE.iter().map(|s| s.to_string()).collect(),
+            unit: RateUnit::Bytes,
+            prefix: UnitPrefix::Si,
+            smoothing: DEFAULT_SMOOTHING,
+            totals: HashMap::new(),
+            counting: Vec::new(),
+            rates: None,
+            down: None,
+            up: None,
+        }
+    }
+
+    /// Interfaces to count, matched by prefix. An empty `include` counts
+    /// every interface not in `exclude`.
+    pub fn set_interfaces(&mut self, include: Vec<String>, exclude: Vec<String>) {
+        if self.include != include || self.exclude != exclude {
+            self.include = include;
+            self.exclude = exclude;
+            self.rates = None;
+        }
+    }
+
+    pub fn set_units(&mut self, unit: RateUnit, prefix: UnitPrefix) {
+        self.unit = unit;
+        self.prefix = prefix;
+    }
+
+    /// Weight of the previous average against a new sample, from 0 (no
+    /// smoothing) up to but not including 1.
+    pub fn set_smoothing(&mut self, smoothing: f64) {
+        self.smoothing = smoothing.clamp(0.0, 0.99);
+    }
+
+    fn counted(&self, name: &str) -> bool {
+        let matches = |prefixes: &[String]| prefixes.iter().any(|p| name.starts_with(p.as_str()));
+        (self.include.is_empty() || matches(&self.include)) && !matches(&self.exclude)
+    }
+
+    /// Samples the counters if the last sample is old enough.
+    fn sample(&mut self) {
+        let elapsed = self.sampled_at.elapsed();
+        if elapsed >= MIN_SAMPLE {
+            self.refresh(elapsed);
         }
     }
+
+    fn refresh(&mut self, elapsed: Duration) {
+        self.networks.refresh(true);
+        self.sampled_at = Instant::now();
+        let totals = self
+            .networks
+            .iter()
+            .map(|(name, data)| {
+                let total = (data.total_received(), data.total_transmitted());
+                (name.clone(), total)
+            })
+            .collect();
+        self.record(totals, elapsed);
+    }
+
+    /// Folds the rates since the last sample into the average. `totals` are
+    /// the lifetime counters per interface, `elapsed` the time they cover.
+    fn record(&mut self, totals: HashMap<String, (u64, u64)>, elapsed: Duration) {
+        let (mut rx, mut tx) = (0u64, 0u64);
+        let mut counting = Vec::new();
+        for (name, (received, sent)) in &totals {
+            if !self.counted(name) {
+                continue;
+            }
+            // An interface seen for the first time only sets where its
+            // counters start
+            if let Some((last_received, last_sent)) = self.totals.get(name) {
+                rx += received.saturating_sub(*last_received);
+                tx += sent.saturating_sub(*last_sent);
+            }
+            counting.push(name.clone());
+        }
+        let first = self.totals.is_empty();
+        self.totals = totals;
+        counting.sort();
+        if counting != self.counting {
+            if counting.is_empty() {
+                debug!("no network interfaces match, rates stay at zero");
+            } else {
+                debug!("counting network interfaces {}", counting.join(", "));
+            }
+            self.counting = counting;
+        }
+
+        // Counters on their own are lifetime totals, not a rate
+        if first || elapsed.is_zero() {
+            return;
+        }
+        let secs = elapsed.as_secs_f64();
+        let sample = (rx as f64 / secs, tx as f64 / secs);
+        self.rates = Some(match self.rates {
+            Some((down, up)) => {
+                let s = self.smoothing;
+                (
+                    s * down + (1.0 - s) * sample.0,
+                    s * up + (1.0 - s) * sample.1,
+                )
+            }
+            None => sample,
+        });
+    }
 }
 
-fn format_rate(bytes: u64) -> String {
-    if bytes > 1_048_576 {
-        format!("{:.1}mb/s", bytes as f64 / 1_048_576.0)
-    } else if bytes > 1024 {
-        format!("{:.0}kb/s", bytes as f64 / 1024.0)
+/// A rate such as `1.2MB/s`, scaled to the largest multiple below it.
+pub fn format_rate(bytes_per_sec: f64, unit: RateUnit, prefix: UnitPrefix) -> String {
+    let (mut value, names) = match (unit, prefix) {
+        (RateUnit::Bytes, UnitPrefix::Si) => (bytes_per_sec, ["B", "kB", "MB", "GB"]),
+        (RateUnit::Bytes, UnitPrefix::Iec) => (bytes_per_sec, ["B", "KiB", "MiB", "GiB"]),
+        (RateUnit::Bits, UnitPrefix::Si) => (bytes_per_sec * 8.0, ["b", "kb", "Mb", "Gb"]),
+        (RateUnit::Bits, UnitPrefix::Iec) => (bytes_per_sec * 8.0, ["b", "Kib", "Mib", "Gib"]),
+    };
+    let base = match prefix {
+        UnitPrefix::Si => 1000.0,
+        UnitPrefix::Iec => 1024.0,
+    };
+
+    let mut scale = 0;
+    // Values shown without decimals round up to the next multiple
+    while value >= base - 0.5 && scale < names.len() - 1 {
+        value /= base;
+        scale += 1;
+    }
+    let name = names[scale];
+    if scale == 0 {
+        format!("{value:.0}{name}/s")
+    } else if value < 100.0 {
+        format!("{value:.1}{name}/s")
     } else {
-        format!("{bytes}b/s")
+        format!("{value:.0}{name}/s")
+    }
+}
+
+impl Default for NetworkModule {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Module for NetworkModule {
+    fn id(&self) -> &str {
+        "network"
+    }
+
     fn name(&self) -> &str {
         "Network"
     }
@@ -40,34 +232,141 @@ impl Module for NetworkModule {
     }
 
     fn set_enabled(&mut self, enabled: bool) {
+        if enabled && !self.enabled {
+            // Don't average in whatever happened while disabled
+            self.totals.clear();
+            self.rates = None;
+            self.refresh(Duration::ZERO);
+        }
         self.enabled = enabled;
     }
 
+    fn refresh_interval(&self) -> Duration {
+        REFRESH_INTERVAL
+    }
+
     fn tick(&mut self) -> Option<String> {
         if !self.enabled {
             return None;
         }
 
-        self.networks.refresh(true);
+        self.sample();
+        let (down, up) = self.rates?;
+        let down = format_rate(down, self.unit, self.prefix);
+        let up = format_rate(up, self.unit, self.prefix);
+        let line = format!(
+            "NET {} {}",
+            to_superscript(&format!("down:{down}")),
+            to_superscript(&format!("up:{up}"))
+        );
+        self.down = Some(down);
+        self.up = Some(up);
 
-        let mut total_rx: u64 = 0;
-        let mut total_tx: u64 = 0;
+        Some(line)
+    }
 
-        for (_name, data) in &self.networks {
-            total_rx += data.total_received();
-            total_tx += data.total_transmitted();
+    fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "down" => self.down.clone(),
+            "up" => self.up.clone(),
+            _ => None,
         }
+    }
+}
 
-        let rx_rate = total_rx.saturating_sub(self.prev_rx);
-        let tx_rate = total_tx.saturating_sub(self.prev_tx);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        self.prev_rx = total_rx;
-        self.prev_tx = total_tx;
+    fn totals(counters: &[(&str, u64, u64)]) -> HashMap<String, (u64, u64)> {
+        counters
+            .iter()
+            .map(|(name, rx, tx)| (name.to_string(), (*rx, *tx)))
+            .collect()
+    }
 
-        Some(format!(
-            "NET {} {}",
-            to_superscript(&format!("down:{}", format_rate(rx_rate))),
-            to_superscript(&format!("up:{}", format_rate(tx_rate)))
-        ))
+    #[test]
+    fn formats_rates() {
+        let si = UnitPrefix::Si;
+        let iec = UnitPrefix::Iec;
+        assert_eq!(format_rate(0.0, RateUnit::Bytes, si), "0B/s");
+        assert_eq!(format_rate(999.0, RateUnit::Bytes, si), "999B/s");
+        assert_eq!(format_rate(1_500.0, RateUnit::Bytes, si), "1.5kB/s");
+        assert_eq!(format_rate(1_536.0, RateUnit::Bytes, iec), "1.5KiB/s");
+        assert_eq!(format_rate(1_500.0, RateUnit::Bits, si), "12.0kb/s");
+        assert_eq!(format_rate(128.0 * 1024.0, RateUnit::Bits, iec), "1.0Mib/s");
+        assert_eq!(format_rate(250_000_000.0, RateUnit::Bytes, si), "250MB/s");
+        assert_eq!(format_rate(5e12, RateUnit::Bytes, si), "5000GB/s");
+        // Just under a multiple shows as the multiple
+        assert_eq!(format_rate(999.6, RateUnit::Bytes, si), "1.0kB/s");
+    }
+
+    #[test]
+    fn filters_interfaces() {
+        let mut network = NetworkModule::new();
+        assert!(network.counted("eth0"));
+        assert!(network.counted("wlp3s0"));
+        assert!(!network.counted("lo"));
+        assert!(!network.counted("docker0"));
+        assert!(!network.counted("veth1234"));
+
+        network.set_interfaces(vec!["eth".to_string()], vec!["eth1".to_string()]);
+        assert!(network.counted("eth0"));
+        assert!(!network.counted("eth1"));
+        assert!(!network.counted("wlp3s0"));
+    }
+
+    #[test]
+    fn skips_lifetime_totals_on_the_first_sample() {
+        let mut network = NetworkModule::new();
+        let second = Duration::from_secs(1);
+        network.record(totals(&[("eth0", 50_000_000_000, 1_000_000)]), second);
+        assert_eq!(network.rates, None);
+
+        network.record(totals(&[("eth0", 50_000_002_000, 1_000_500)]), second * 2);
+        assert_eq!(network.rates, Some((1000.0, 250.0)));
+    }
+
+    #[test]
+    fn counts_only_included_interfaces() {
+        let mut network = NetworkModule::new();
+        network.set_smoothing(0.0);
+        let second = Duration::from_secs(1);
+        network.record(totals(&[("eth0", 1000, 0), ("lo", 0, 0)]), second);
+        network.record(totals(&[("eth0", 2000, 0), ("lo", 90_000, 0)]), second);
+        assert_eq!(network.rates, Some((1000.0, 0.0)));
+
+        // A new interface starts from its own counters
+        network.record(
+            totals(&[
+                ("eth0", 3000, 0),
+                ("lo", 90_000, 0),
+                ("wlan0", 7_000_000, 0),
+            ]),
+            second,
+        );
+        assert_eq!(network.rates, Some((1000.0, 0.0)));
+        network.record(
+            totals(&[
+                ("eth0", 4000, 0),
+                ("lo", 90_000, 0),
+                ("wlan0", 7_001_000, 0),
+            ]),
+            second,
+        );
+        assert_eq!(network.rates, Some((2000.0, 0.0)));
+    }
+
+    #[test]
+    fn smooths_rates() {
+        let mut network = NetworkModule::new();
+        let second = Duration::from_secs(1);
+        network.record(totals(&[("eth0", 0, 0)]), second);
+        network.record(totals(&[("eth0", 1000, 0)]), second);
+        assert_eq!(network.rates, Some((1000.0, 0.0)));
+        network.record(totals(&[("eth0", 1000, 0)]), second);
+        assert_eq!(network.rates, Some((500.0, 0.0)));
+        network.record(totals(&[("eth0", 3000, 0)]), second);
+        assert_eq!(network.rates, Some((1250.0, 0.0)));
     }
 }
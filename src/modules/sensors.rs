@@ -0,0 +1,402 @@
+use super::Module;
+use crate::events::Event;
+use crate::hwmon::{self, Kind, Sensor};
+use crate::smalltext::to_superscript;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Temperatures change slowly and reading every sensor touches sysfs a lot.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+const EVENT_PRIORITY: u8 = 60;
+/// Degrees a warned sensor has to cool below the threshold before it can
+/// warn again, so it doesn't flicker around the limit.
+const WARN_HYSTERESIS: f64 = 3.0;
+pub const DEFAULT_WARN_ABOVE: f64 = 85.0;
+/// Label that stands for the CPU package sensor, whatever the driver.
+const CPU_LABEL: &str = "cpu";
+/// CPU package sensors by driver and label, best first. An empty label
+/// takes the chip's first temperature.
+const CPU_SENSORS: &[(&str, &str)] = &[
+    ("coretemp", "package id 0"),
+    ("k10temp", "tdie"),
+    ("k10temp", "tctl"),
+    ("zenpower", "tdie"),
+    ("zenpower", "tctl"),
+    ("cpu_thermal", ""),
+    ("soc_thermal", ""),
+];
+
+/// A sensor picked for display, with the name it is shown under.
+#[derive(Debug, Clone, PartialEq)]
+struct Reading {
+    name: String,
+    kind: Kind,
+    value: f64,
+}
+
+pub struct SensorsModule {
+    enabled: bool,
+    root: PathBuf,
+    /// Sensors to show as `label` or `chip/label`; empty shows the CPU.
+    labels: Vec<String>,
+    show_fans: bool,
+    fahrenheit: bool,
+    /// Degrees Celsius; 0 never warns.
+    warn_above: f64,
+    /// Raise an event when a sensor starts warning.
+    alerts: bool,
+    readings: Vec<Reading>,
+    /// Names of the readings last logged, so changes are logged once.
+    logged: Option<Vec<String>>,
+    /// Names of sensors currently over the threshold.
+    warning: HashSet<String>,
+    events: Vec<Event>,
+}
+
+impl SensorsModule {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            root: PathBuf::from(hwmon::DEFAULT_SYSFS_ROOT),
+            labels: Vec::new(),
+            show_fans: false,
+            fahrenheit: false,
+            warn_above: DEFAULT_WARN_ABOVE,
+            alerts: false,
+            readings: Vec::new(),
+            logged: None,
+            warning: HashSet::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Where sysfs is mounted, normally `/sys`.
+    pub fn set_sysfs_root(&mut self, root: PathBuf) {
+        self.root = root;
+    }
+
+    pub fn set_labels(&mut self, labels: Vec<String>) {
+        self.labels = labels;
+    }
+
+    pub fn set_show_fans(&mut self, show: bool) {
+        self.show_fans = show;
+    }
+
+    pub fn set_fahrenheit(&mut self, fahrenheit: bool) {
+        self.fahrenheit = fahrenheit;
+    }
+
+    pub fn set_warn_above(&mut self, celsius: f64) {
+        self.warn_above = celsius;
+    }
+
+    pub fn set_alerts(&mut self, alerts: bool) {
+        self.alerts = alerts;
+    }
+
+    /// Logs which sensors are shown, once per change rather than per tick.
+    fn log_readings(&mut self, sensors: &[Sensor]) {
+        let names: Vec<String> = self.readings.iter().map(|r| r.name.clone()).collect();
+        if self.logged.as_ref() == Some(&names) {
+            return;
+        }
+        if sensors.is_empty() {
+            warn!("no hwmon sensors under {}", self.root.display());
+        } else if names.is_empty() {
+            warn!("none of the sensors {:?} were found", self.labels);
+        } else {
+            debug!("showing sensors {}", names.join(", "));
+        }
+        self.logged = Some(names);
+    }
+
+    fn check_warnings(&mut self) {
+        let limit = self.warn_above;
+        let mut warning = HashSet::new();
+        for reading in &self.readings {
+            if reading.kind != Kind::Temp || limit <= 0.0 {
+                continue;
+            }
+            let was_warning = self.warning.contains(&reading.name);
+            let over = if was_warning {
+                reading.value > limit - WARN_HYSTERESIS
+            } else {
+                reading.value >= limit
+            };
+            if !over {
+                continue;
+            }
+            if !was_warning && self.alerts {
+                self.events.push(Event::new(
+                    format!("sensors:{}", reading.name),
+                    format!(
+                        "{} temperature high: {}",
+                        reading.name,
+                        self.format_temp(reading.value).to_uppercase()
+                    ),
+                    EVENT_PRIORITY,
+                ));
+            }
+            warning.insert(reading.name.clone());
+        }
+        self.warning = warning;
+    }
+
+    fn format_temp(&self, celsius: f64) -> String {
+        if self.fahrenheit {
+            format!("{:.0}°f", celsius * 9.0 / 5.0 + 32.0)
+        } else {
+            format!("{celsius:.0}°c")
+        }
+    }
+
+    fn format(&self, reading: &Reading) -> String {
+        match reading.kind {
+            Kind::Temp => self.format_temp(reading.value),
+            Kind::Fan => format!("{:.0}rpm", reading.value),
+        }
+    }
+}
+
+/// The CPU package temperature, going by the drivers in [`CPU_SENSORS`].
+fn cpu_sensor(sensors: &[Sensor]) -> Option<&Sensor> {
+    CPU_SENSORS.iter().find_map(|(chip, label)| {
+        sensors.iter().find(|sensor| {
+            sensor.kind == Kind::Temp
+                && sensor.chip == *chip
+                && (label.is_empty() || sensor.label.eq_ignore_ascii_case(label))
+        })
+    })
+}
+
+/// Picks the sensors to show: each of `labels` that matches one, or the CPU
+/// package when there are none, then every spinning fan if `fans` is set.
+fn select(sensors: &[Sensor], labels: &[String], fans: bool) -> Vec<Reading> {
+    let reading = |name: &str, sensor: &Sensor| Reading {
+        name: name.to_string(),
+        kind: sensor.kind,
+        value: sensor.value,
+    };
+
+    let mut readings: Vec<Reading> = Vec::new();
+    if labels.is_empty() {
+        readings.extend(cpu_sensor(sensors).map(|sensor| reading("CPU", sensor)));
+    }
+    for wanted in labels {
+        let found = if wanted.eq_ignore_ascii_case(CPU_LABEL) {
+            cpu_sensor(sensors)
+        } else {
+            let (chip, label) = match wanted.split_once('/') {
+                Some((chip, label)) => (Some(chip), label),
+                None => (None, wanted.as_str()),
+            };
+            sensors.iter().find(|sensor| {
+                chip.is_none_or(|chip| sensor.chip.eq_ignore_ascii_case(chip))
+                    && sensor.label.eq_ignore_ascii_case(label)
+            })
+        };
+        readings.extend(found.map(|sensor| reading(wanted, sensor)));
+    }
+    if fans {
+        for sensor in sensors {
+            let shown = readings
+                .iter()
+                .any(|r| r.kind == Kind::Fan && r.name.eq_ignore_ascii_case(&sensor.label));
+            if sensor.kind == Kind::Fan && sensor.value > 0.0 && !shown {
+                readings.push(reading(&sensor.label, sensor));
+            }
+        }
+    }
+    readings
+}
+
+impl Default for SensorsModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for SensorsModule {
+    fn id(&self) -> &str {
+        "sensors"
+    }
+
+    fn name(&self) -> &str {
+        "Sensors"
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.readings.clear();
+            self.warning.clear();
+        }
+    }
+
+    fn refresh_interval(&self) -> Duration {
+        REFRESH_INTERVAL
+    }
+
+    fn tick(&mut self) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+
+        let sensors = hwmon::read_all(&self.root);
+        self.readings = select(&sensors, &self.labels, self.show_fans);
+        self.log_readings(&sensors);
+        self.check_warnings();
+        if self.readings.is_empty() {
+            return None;
+        }
+
+        let parts: Vec<String> = self
+            .readings
+            .iter()
+            .map(|reading| {
+                let value = self.format(reading);
+                // Warnings are shown at full size so they stand out
+                if self.warning.contains(&reading.name) {
+                    format!("⚠{} {}", reading.name, value.to_uppercase())
+                } else {
+                    format!("{} {}", reading.name, to_superscript(&value))
+                }
+            })
+            .collect();
+        Some(parts.join(" "))
+    }
+
+    /// `cpu` is the CPU package temperature; any other name is a shown
+    /// sensor by the label it was picked with, e.g. `fan1`.
+    fn field(&self, name: &str) -> Option<String> {
+        self.readings
+            .iter()
+            .find(|reading| reading.name.eq_ignore_ascii_case(name))
+            .map(|reading| self.format(reading))
+    }
+
+    fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Fixture;
+    use std::fs;
+
+    fn labels(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn picks_intel_package_over_other_chips() {
+        let root = Fixture::new(
+            "sensors-intel",
+            &[
+                ("class/hwmon/hwmon0/name", "acpitz\n"),
+                ("class/hwmon/hwmon0/temp1_input", "27800\n"),
+                ("class/hwmon/hwmon3/name", "coretemp\n"),
+                ("class/hwmon/hwmon3/temp2_input", "51000\n"),
+                ("class/hwmon/hwmon3/temp2_label", "Core 0\n"),
+                ("class/hwmon/hwmon3/temp1_input", "54000\n"),
+                ("class/hwmon/hwmon3/temp1_label", "Package id 0\n"),
+            ],
+        );
+        let readings = select(&hwmon::read_all(&root), &[], false);
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].name, "CPU");
+        assert_eq!(readings[0].value, 54.0);
+    }
+
+    #[test]
+    fn prefers_tdie_over_tctl() {
+        let root = Fixture::new(
+            "sensors-amd",
+            &[
+                ("class/hwmon/hwmon1/name", "k10temp\n"),
+                ("class/hwmon/hwmon1/temp1_input", "71000\n"),
+                ("class/hwmon/hwmon1/temp1_label", "Tctl\n"),
+                ("class/hwmon/hwmon1/temp2_input", "61000\n"),
+                ("class/hwmon/hwmon1/temp2_label", "Tdie\n"),
+            ],
+        );
+        let readings = select(&hwmon::read_all(&root), &[], false);
+        assert_eq!(readings[0].value, 61.0);
+    }
+
+    #[test]
+    fn selects_labels_and_fans() {
+        let root = Fixture::new(
+            "sensors-labels",
+            &[
+                ("class/hwmon/hwmon1/name", "k10temp\n"),
+                ("class/hwmon/hwmon1/temp1_input", "66000\n"),
+                ("class/hwmon/hwmon1/temp1_label", "Tctl\n"),
+                ("class/hwmon/hwmon2/name", "nvme\n"),
+                ("class/hwmon/hwmon2/temp1_input", "42850\n"),
+                ("class/hwmon/hwmon2/temp1_label", "Composite\n"),
+                ("class/hwmon/hwmon4/name", "nct6798\n"),
+                ("class/hwmon/hwmon4/temp1_input", "35000\n"),
+                ("class/hwmon/hwmon4/temp1_label", "SYSTIN\n"),
+                ("class/hwmon/hwmon4/fan1_input", "0\n"),
+                ("class/hwmon/hwmon4/fan2_input", "1200\n"),
+                ("class/hwmon/hwmon4/fan2_label", "CPU Fan\n"),
+            ],
+        );
+        let sensors = hwmon::read_all(&root);
+
+        let readings = select(
+            &sensors,
+            &labels(&["cpu", "nvme/composite", "missing"]),
+            true,
+        );
+        let names: Vec<&str> = readings.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["cpu", "nvme/composite", "CPU Fan"]);
+        assert_eq!(readings[0].value, 66.0);
+        assert_eq!(readings[1].value, 42.85);
+        assert_eq!(readings[2].value, 1200.0);
+
+        // A stopped fan is only shown when asked for
+        let readings = select(&sensors, &labels(&["fan1"]), false);
+        assert_eq!(readings[0].kind, Kind::Fan);
+        assert_eq!(readings[0].value, 0.0);
+    }
+
+    #[test]
+    fn warns_above_threshold_with_hysteresis() {
+        let temp = "class/hwmon/hwmon0/temp1_input";
+        let root = Fixture::new(
+            "sensors-warn",
+            &[
+                ("class/hwmon/hwmon0/name", "coretemp\n"),
+                ("class/hwmon/hwmon0/temp1_label", "Package id 0\n"),
+                (temp, "86000\n"),
+            ],
+        );
+        let mut sensors = SensorsModule::new();
+        sensors.set_sysfs_root(root.to_path_buf());
+        sensors.set_alerts(true);
+        sensors.set_enabled(true);
+
+        assert_eq!(sensors.tick().as_deref(), Some("⚠CPU 86°C"));
+        assert_eq!(sensors.take_events().len(), 1);
+
+        fs::write(root.join(temp), "84000\n").unwrap();
+        assert_eq!(sensors.tick().as_deref(), Some("⚠CPU 84°C"));
+        assert!(sensors.take_events().is_empty());
+
+        fs::write(root.join(temp), "80000\n").unwrap();
+        assert!(!sensors.tick().unwrap().starts_with('⚠'));
+        sensors.set_fahrenheit(true);
+        assert_eq!(sensors.field("cpu").as_deref(), Some("176°f"));
+    }
+}
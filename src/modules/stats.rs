@@ -1,12 +1,171 @@
+use super::network::{format_rate, RateUnit, UnitPrefix};
 use super::Module;
-use crate::smalltext::to_superscript;
-use sysinfo::System;
+use crate::smalltext::{to_smallcaps, to_superscript};
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use sysinfo::{Disks, ProcessesToUpdate, System};
+use tracing::warn;
+
+/// CPU usage is averaged over the time between refreshes, so refreshing
+/// much faster only adds noise.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+pub const DEFAULT_METRICS: &[&str] = &["cpu", "ram"];
+
+/// Binary multiples for memory and disk sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeUnit {
+    Mib,
+    Gib,
+}
+
+/// How metric names are written before their values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelStyle {
+    Upper,
+    SmallCaps,
+    None,
+}
+
+impl LabelStyle {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            "upper" => Some(Self::Upper),
+            "smallcaps" => Some(Self::SmallCaps),
+            "none" => Some(Self::None),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetricKind {
+    Cpu,
+    /// Usage of each core.
+    Cores,
+    Ram,
+    Swap,
+    /// 1, 5 and 15 minute load average.
+    Load,
+    /// Space used on the filesystem mounted here.
+    Disk(String),
+    /// Read and write rates over all disks.
+    DiskIo,
+    /// The process using the most CPU.
+    Top,
+}
+
+/// One stat to show, parsed from a spec such as `ram gib`,
+/// `disk /home percent` or `cpu label=Proc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metric {
+    pub kind: MetricKind,
+    /// Size unit for memory and disks; the default differs per metric.
+    pub unit: Option<SizeUnit>,
+    /// Show sizes as a percentage used instead of used/total.
+    pub percent: bool,
+    /// Name shown before the value; empty shows none.
+    pub label: Option<String>,
+}
+
+impl Metric {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut words = spec.split_whitespace();
+        let name = words.next().ok_or("metric can't be empty")?;
+        let mut kind = match name {
+            "cpu" => MetricKind::Cpu,
+            "cores" => MetricKind::Cores,
+            "ram" => MetricKind::Ram,
+            "swap" => MetricKind::Swap,
+            "load" => MetricKind::Load,
+            "disk" => MetricKind::Disk(String::new()),
+            "disk_io" => MetricKind::DiskIo,
+            "top" => MetricKind::Top,
+            other => {
+                return Err(format!(
+                    "unknown metric '{other}', expected cpu, cores, ram, swap, load, \
+                     disk, disk_io or top"
+                ))
+            }
+        };
+
+        let (mut unit, mut percent, mut label) = (None, false, None);
+        for word in words {
+            match word {
+                "mib" => unit = Some(SizeUnit::Mib),
+                "gib" => unit = Some(SizeUnit::Gib),
+                "percent" => percent = true,
+                _ if word.starts_with("label=") => {
+                    label = Some(word["label=".len()..].to_string());
+                }
+                _ => match &mut kind {
+                    MetricKind::Disk(mount) if mount.is_empty() && word.starts_with('/') => {
+                        *mount = word.to_string();
+                    }
+                    _ => return Err(format!("'{spec}': unknown option '{word}'")),
+                },
+            }
+        }
+        if let MetricKind::Disk(mount) = &mut kind {
+            if mount.is_empty() {
+                *mount = "/".to_string();
+            }
+        }
+        Ok(Metric {
+            kind,
+            unit,
+            percent,
+            label,
+        })
+    }
+
+    /// Name of the metric's value for layout templates, e.g. `ram`,
+    /// `disk_root` for `/` or `disk_mnt_games` for `/mnt/games`.
+    pub fn id(&self) -> String {
+        match &self.kind {
+            MetricKind::Cpu => "cpu".to_string(),
+            MetricKind::Cores => "cores".to_string(),
+            MetricKind::Ram => "ram".to_string(),
+            MetricKind::Swap => "swap".to_string(),
+            MetricKind::Load => "load".to_string(),
+            MetricKind::Disk(mount) if mount == "/" => "disk_root".to_string(),
+            MetricKind::Disk(mount) => {
+                // Template keys are limited to letters, digits and `_`
+                let name: String = mount
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                    .collect();
+                format!("disk{}", name.trim_end_matches('_'))
+            }
+            MetricKind::DiskIo => "disk_io".to_string(),
+            MetricKind::Top => "top".to_string(),
+        }
+    }
+
+    fn default_label(&self) -> String {
+        match &self.kind {
+            MetricKind::Cpu => "CPU".to_string(),
+            MetricKind::Cores => "CORES".to_string(),
+            MetricKind::Ram => "RAM".to_string(),
+            MetricKind::Swap => "SWAP".to_string(),
+            MetricKind::Load => "LOAD".to_string(),
+            MetricKind::Disk(mount) if mount == "/" => "DISK".to_string(),
+            MetricKind::Disk(mount) => mount.clone(),
+            MetricKind::DiskIo => "IO".to_string(),
+            MetricKind::Top => "TOP".to_string(),
+        }
+    }
+}
 
 pub struct StatsModule {
     enabled: bool,
     system: System,
-    show_cpu: bool,
-    show_ram: bool,
+    disks: Disks,
+    disks_refreshed: Instant,
+    metrics: Vec<Metric>,
+    label_style: LabelStyle,
+    /// Values from the last tick by metric id.
+    values: Vec<(String, String)>,
 }
 
 impl StatsModule {
@@ -14,21 +173,189 @@ impl StatsModule {
         Self {
             enabled: false,
             system: System::new_all(),
-            show_cpu: true,
-            show_ram: true,
+            disks: Disks::new_with_refreshed_list(),
+            disks_refreshed: Instant::now(),
+            metrics: DEFAULT_METRICS
+                .iter()
+                .filter_map(|spec| Metric::parse(spec).ok())
+                .collect(),
+            label_style: LabelStyle::Upper,
+            values: Vec::new(),
+        }
+    }
+
+    pub fn set_metrics(&mut self, metrics: Vec<Metric>) {
+        if self.metrics == metrics {
+            return;
+        }
+        for metric in &metrics {
+            if let MetricKind::Disk(mount) = &metric.kind {
+                let mounted = self
+                    .disks
+                    .iter()
+                    .any(|disk| disk.mount_point() == Path::new(mount));
+                if !mounted {
+                    warn!("nothing is mounted at {mount}, its disk usage isn't shown");
+                }
+            }
+        }
+        self.metrics = metrics;
+    }
+
+    pub fn set_label_style(&mut self, style: LabelStyle) {
+        self.label_style = style;
+    }
+
+    /// Refreshes only what the chosen metrics read, since processes and
+    /// disks are slow to list. Returns the time the disk counters cover.
+    fn refresh(&mut self) -> Duration {
+        let wants = |f: fn(&MetricKind) -> bool| self.metrics.iter().any(|m| f(&m.kind));
+        let cpu = wants(|k| matches!(k, MetricKind::Cpu | MetricKind::Cores | MetricKind::Top));
+        let memory = wants(|k| matches!(k, MetricKind::Ram | MetricKind::Swap));
+        let disks = wants(|k| matches!(k, MetricKind::Disk(_) | MetricKind::DiskIo));
+        let processes = wants(|k| matches!(k, MetricKind::Top));
+
+        if cpu {
+            self.system.refresh_cpu_usage();
+        }
+        if memory {
+            self.system.refresh_memory();
+        }
+        if processes {
+            self.system.refresh_processes(ProcessesToUpdate::All, true);
+        }
+        let elapsed = self.disks_refreshed.elapsed();
+        if disks {
+            self.disks.refresh(true);
+            self.disks_refreshed = Instant::now();
         }
+        elapsed
     }
 
-    pub fn set_show_cpu(&mut self, show: bool) {
-        self.show_cpu = show;
+    fn value(&self, metric: &Metric, elapsed: Duration) -> Option<String> {
+        let size = |used: u64, total: u64, default_unit: SizeUnit| {
+            format_size(
+                used,
+                total,
+                metric.unit.unwrap_or(default_unit),
+                metric.percent,
+            )
+        };
+        match &metric.kind {
+            MetricKind::Cpu => Some(format!("{:.0}%", self.system.global_cpu_usage())),
+            MetricKind::Cores => {
+                let cores: Vec<String> = self
+                    .system
+                    .cpus()
+                    .iter()
+                    .map(|cpu| format!("{:.0}", cpu.cpu_usage()))
+                    .collect();
+                (!cores.is_empty()).then(|| cores.join(" "))
+            }
+            MetricKind::Ram => size(
+                self.system.used_memory(),
+                self.system.total_memory(),
+                SizeUnit::Mib,
+            ),
+            MetricKind::Swap => size(
+                self.system.used_swap(),
+                self.system.total_swap(),
+                SizeUnit::Mib,
+            ),
+            MetricKind::Load => {
+                let load = System::load_average();
+                Some(format!(
+                    "{:.2} {:.2} {:.2}",
+                    load.one, load.five, load.fifteen
+                ))
+            }
+            MetricKind::Disk(mount) => {
+                let disk = self
+                    .disks
+                    .iter()
+                    .find(|disk| disk.mount_point() == Path::new(mount))?;
+                let total = disk.total_space();
+                let used = total.saturating_sub(disk.available_space());
+                size(used, total, SizeUnit::Gib)
+            }
+            MetricKind::DiskIo => {
+                let secs = elapsed.as_secs_f64();
+                if secs <= 0.0 {
+                    return None;
+                }
+                // Subvolumes and bind mounts of one device share its counters
+                let mut devices = HashSet::new();
+                let (mut read, mut written) = (0, 0);
+                for disk in &self.disks {
+                    if devices.insert(disk.name()) {
+                        let usage = disk.usage();
+                        read += usage.read_bytes;
+                        written += usage.written_bytes;
+                    }
+                }
+                let rate =
+                    |bytes: u64| format_rate(bytes as f64 / secs, RateUnit::Bytes, UnitPrefix::Si);
+                Some(format!("r:{} w:{}", rate(read), rate(written)))
+            }
+            MetricKind::Top => {
+                let process = self
+                    .system
+                    .processes()
+                    .values()
+                    .max_by(|a, b| a.cpu_usage().total_cmp(&b.cpu_usage()))?;
+                Some(format!(
+                    "{} {:.0}%",
+                    process.name().to_string_lossy(),
+                    process.cpu_usage()
+                ))
+            }
+        }
     }
 
-    pub fn set_show_ram(&mut self, show: bool) {
-        self.show_ram = show;
+    fn label(&self, metric: &Metric) -> Option<String> {
+        let label = metric
+            .label
+            .clone()
+            .unwrap_or_else(|| metric.default_label());
+        if label.is_empty() {
+            return None;
+        }
+        match self.label_style {
+            LabelStyle::Upper => Some(label),
+            LabelStyle::SmallCaps => Some(to_smallcaps(&label)),
+            LabelStyle::None => None,
+        }
+    }
+}
+
+/// `used/total` in `unit`, or the percentage used.
+fn format_size(used: u64, total: u64, unit: SizeUnit, percent: bool) -> Option<String> {
+    if total == 0 {
+        return None;
+    }
+    if percent {
+        return Some(format!("{:.0}%", used as f64 / total as f64 * 100.0));
+    }
+    Some(match unit {
+        SizeUnit::Mib => format!("{}/{}mb", used / 1024 / 1024, total / 1024 / 1024),
+        SizeUnit::Gib => {
+            let gib = |bytes: u64| bytes as f64 / 1024.0 / 1024.0 / 1024.0;
+            format!("{:.1}/{:.1}gb", gib(used), gib(total))
+        }
+    })
+}
+
+impl Default for StatsModule {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Module for StatsModule {
+    fn id(&self) -> &str {
+        "stats"
+    }
+
     fn name(&self) -> &str {
         "Stats"
     }
@@ -41,32 +368,30 @@ impl Module for StatsModule {
         self.enabled = enabled;
     }
 
+    fn refresh_interval(&self) -> Duration {
+        REFRESH_INTERVAL
+    }
+
     fn tick(&mut self) -> Option<String> {
         if !self.enabled {
             return None;
         }
 
-        self.system.refresh_cpu_usage();
-        self.system.refresh_memory();
+        let elapsed = self.refresh();
 
+        let mut values = Vec::new();
         let mut parts = Vec::new();
-
-        if self.show_cpu {
-            let cpu_usage = self.system.global_cpu_usage();
-            parts.push(format!(
-                "CPU {}",
-                to_superscript(&format!("{cpu_usage:.0}%"))
-            ));
-        }
-
-        if self.show_ram {
-            let used = self.system.used_memory() / 1024 / 1024;
-            let total = self.system.total_memory() / 1024 / 1024;
-            parts.push(format!(
-                "RAM {}",
-                to_superscript(&format!("{used}/{total}mb"))
-            ));
+        for metric in &self.metrics {
+            let Some(value) = self.value(metric, elapsed) else {
+                continue;
+            };
+            parts.push(match self.label(metric) {
+                Some(label) => format!("{label} {}", to_superscript(&value)),
+                None => to_superscript(&value),
+            });
+            values.push((metric.id(), value));
         }
+        self.values = values;
 
         if parts.is_empty() {
             return None;
@@ -74,4 +399,39 @@ impl Module for StatsModule {
 
         Some(parts.join(" | "))
     }
+
+    fn field(&self, name: &str) -> Option<String> {
+        self.values
+            .iter()
+            .find(|(id, _)| id == name)
+            .map(|(_, value)| value.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::Template;
+
+    #[test]
+    fn disk_ids_are_template_keys() {
+        let id = |spec| Metric::parse(spec).unwrap().id();
+        assert_eq!(id("disk"), "disk_root");
+        assert_eq!(id("disk /home percent"), "disk_home");
+        assert_eq!(id("disk /mnt/games-2/"), "disk_mnt_games_2");
+    }
+
+    #[test]
+    fn renders_disk_fields_in_layouts() {
+        let mut stats = StatsModule::new();
+        let metrics = ["disk", "disk /home"].map(|spec| Metric::parse(spec).unwrap());
+        stats.values = vec![
+            (metrics[0].id(), "20.5/64.0gb".to_string()),
+            (metrics[1].id(), "41%".to_string()),
+        ];
+
+        let layout = Template::parse("/ {stats.disk_root} | home {stats.disk_home}").unwrap();
+        let text = layout.render(|key| stats.field(key.strip_prefix("stats.")?));
+        assert_eq!(text, "/ 20.5/64.0gb | home 41%");
+    }
 }
@@ -1,12 +1,14 @@
-use super::Module;
+use super::{Module, Rotation};
+use std::time::Duration;
+use tracing::debug;
 
-const TICKS_PER_LINE: usize = 4; // ~1.5s per tick × 4 = ~6s per line
+/// How long each line stays up before the next one.
+pub const DEFAULT_ROTATION: Duration = Duration::from_secs(6);
 
 pub struct StatusModule {
     enabled: bool,
     lines: [String; 6],
-    index: usize,
-    tick_count: usize,
+    rotation: Rotation,
 }
 
 impl StatusModule {
@@ -14,19 +16,33 @@ impl StatusModule {
         Self {
             enabled: false,
             lines: Default::default(),
-            index: 0,
-            tick_count: 0,
+            rotation: Rotation::new(DEFAULT_ROTATION),
         }
     }
 
     pub fn set_line(&mut self, index: usize, text: String) {
-        if index < 6 {
+        if index < 6 && self.lines[index] != text {
+            debug!("status line {} set to {text:?}", index + 1);
             self.lines[index] = text;
         }
     }
+
+    pub fn set_rotation_interval(&mut self, interval: Duration) {
+        self.rotation.set_period(interval);
+    }
+}
+
+impl Default for StatusModule {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Module for StatusModule {
+    fn id(&self) -> &str {
+        "status"
+    }
+
     fn name(&self) -> &str {
         "Status"
     }
@@ -39,6 +55,10 @@ impl Module for StatusModule {
         self.enabled = enabled;
     }
 
+    fn rotation_interval(&self) -> Option<Duration> {
+        Some(self.rotation.period())
+    }
+
     fn tick(&mut self) -> Option<String> {
         if !self.enabled {
             return None;
@@ -50,18 +70,6 @@ impl Module for StatusModule {
             return None;
         }
 
-        // Clamp index in case lines were removed
-        if self.index >= active.len() {
-            self.index = 0;
-            self.tick_count = 0;
-        }
-
-        let line = active[self.index].clone();
-        self.tick_count += 1;
-        if self.tick_count >= TICKS_PER_LINE {
-            self.tick_count = 0;
-            self.index = (self.index + 1) % active.len();
-        }
-        Some(line)
+        Some(active[self.rotation.index(active.len())].clone())
     }
 }
@@ -1,54 +1,338 @@
-use super::Module;
-use crate::smalltext::to_superscript;
+use super::{Module, Rotation};
+use crate::hwmon;
+use crate::smalltext::{to_smallcaps, to_superscript};
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
 use sysinfo::System;
+use tracing::{debug, warn};
 
-const TICKS_PER_SECTION: usize = 4; // ~1.5s per tick × 4 = ~6s per section
+/// How long each section stays up before the next one.
+pub const DEFAULT_ROTATION: Duration = Duration::from_secs(6);
+pub const DEFAULT_SECTIONS: &[&str] = &["os", "cpu", "gpu", "ram"];
+/// Where distributions install the PCI ID database, most common first.
+pub const PCI_IDS_PATHS: &[&str] = &[
+    "/usr/share/hwdata/pci.ids",
+    "/usr/share/misc/pci.ids",
+    "/usr/share/pci.ids",
+];
+const VENDOR_NVIDIA: u16 = 0x10de;
+const VENDOR_AMD: u16 = 0x1002;
+const VENDOR_INTEL: u16 = 0x8086;
+
+/// GPU makers, whose names are written differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GpuVendor {
+    Nvidia,
+    Amd,
+    Intel,
+    Other,
+}
+
+impl GpuVendor {
+    fn from_pci(vendor: u16) -> Self {
+        match vendor {
+            VENDOR_NVIDIA => Self::Nvidia,
+            VENDOR_AMD => Self::Amd,
+            VENDOR_INTEL => Self::Intel,
+            _ => Self::Other,
+        }
+    }
+
+    /// The vendor of a name that starts with it, as lspci prints them.
+    fn from_name(name: &str) -> Self {
+        [Self::Nvidia, Self::Amd, Self::Intel]
+            .into_iter()
+            .find(|vendor| vendor.strip_prefix(name).is_some())
+            .unwrap_or(Self::Other)
+    }
+
+    /// How the vendor's name starts a device name, longest first.
+    fn prefixes(self) -> &'static [&'static str] {
+        match self {
+            Self::Nvidia => &["NVIDIA Corporation", "NVIDIA"],
+            Self::Amd => &[
+                "Advanced Micro Devices, Inc. [AMD/ATI]",
+                "Advanced Micro Devices, Inc. [AMD]",
+                "Advanced Micro Devices, Inc.",
+                "AMD/ATI",
+                "AMD",
+                "ATI",
+            ],
+            Self::Intel => &["Intel Corporation", "Intel"],
+            Self::Other => &[],
+        }
+    }
+
+    /// `name` without the vendor's name in front, if it starts with it.
+    fn strip_prefix(self, name: &str) -> Option<&str> {
+        self.prefixes().iter().find_map(|prefix| {
+            let rest = name.strip_prefix(prefix)?;
+            (rest.is_empty() || rest.starts_with(' ')).then(|| rest.trim_start())
+        })
+    }
+
+    /// Brand in front of every consumer model, which says nothing next to
+    /// the model number.
+    fn brand(self) -> Option<&'static str> {
+        match self {
+            Self::Nvidia => Some("GeForce "),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+    Os,
+    Kernel,
+    Hostname,
+    Desktop,
+    Resolution,
+    Uptime,
+    Cpu,
+    Cores,
+    Gpu,
+    Ram,
+}
+
+/// One rotating section, parsed from a spec such as `kernel` or
+/// `uptime label=Up`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub kind: SectionKind,
+    /// Shown before the value as written; empty or unset shows none.
+    pub label: Option<String>,
+}
+
+impl Section {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut words = spec.split_whitespace();
+        let name = words.next().ok_or("section can't be empty")?;
+        let kind = match name {
+            "os" => SectionKind::Os,
+            "kernel" => SectionKind::Kernel,
+            "hostname" => SectionKind::Hostname,
+            "desktop" => SectionKind::Desktop,
+            "resolution" => SectionKind::Resolution,
+            "uptime" => SectionKind::Uptime,
+            "cpu" => SectionKind::Cpu,
+            "cores" => SectionKind::Cores,
+            "gpu" => SectionKind::Gpu,
+            "ram" => SectionKind::Ram,
+            other => {
+                return Err(format!(
+                    "unknown section '{other}', expected os, kernel, hostname, desktop, \
+                     resolution, uptime, cpu, cores, gpu or ram"
+                ))
+            }
+        };
+
+        let mut label = None;
+        for word in words {
+            match word.strip_prefix("label=") {
+                Some(text) => label = Some(text.to_string()),
+                None => return Err(format!("'{spec}': unknown option '{word}'")),
+            }
+        }
+        Ok(Section { kind, label })
+    }
+}
+
+/// How section values are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextStyle {
+    Superscript,
+    SmallCaps,
+    Plain,
+}
+
+impl TextStyle {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            "superscript" => Some(Self::Superscript),
+            "smallcaps" => Some(Self::SmallCaps),
+            "plain" => Some(Self::Plain),
+            _ => None,
+        }
+    }
+}
+
+/// Details that don't change while running, looked up on first use.
+#[derive(Debug)]
+struct Details {
+    os: String,
+    kernel: String,
+    hostname: String,
+    desktop: String,
+    resolution: String,
+    cpu: String,
+    cores: String,
+    gpu: String,
+    ram: String,
+}
+
+impl Details {
+    fn detect(sysfs: &Path, pci_ids: &[PathBuf]) -> Self {
+        let sys = System::new_all();
+
+        let os_name = System::name().unwrap_or_default();
+        let os_version = System::os_version().unwrap_or_default();
+        let os_version = os_version.replace("Rolling", "").trim().to_string();
+        let raw_gb = sys.total_memory() as f64 / 1024.0 / 1024.0 / 1024.0;
+        let total_ram = 2u64.pow((raw_gb.log2()).ceil() as u32);
+        let threads = sys.cpus().len();
+        let cores = match sys.physical_core_count() {
+            Some(cores) if cores != threads => format!("{cores}c/{threads}t"),
+            _ => format!("{threads} cores"),
+        };
+
+        let details = Self {
+            os: format!("{os_name} {os_version}").trim().to_string(),
+            kernel: System::kernel_version().unwrap_or_default(),
+            hostname: System::host_name().unwrap_or_default(),
+            desktop: detect_desktop(),
+            resolution: detect_resolutions(sysfs).join(" + "),
+            cpu: detect_cpu_name(&sys),
+            cores,
+            gpu: detect_gpu(sysfs, pci_ids),
+            ram: format!("{total_ram}gb"),
+        };
+        debug!("detected system details: {details:?}");
+        details
+    }
+}
 
 pub struct SystemDetailsModule {
     enabled: bool,
-    parts: Vec<String>,
-    index: usize,
-    tick_count: usize,
-}
-
-fn clean_gpu_name(name: &str) -> String {
-    let name = name
-        .replace("NVIDIA", "")
-        .replace("GeForce", "")
-        .replace("Advanced Micro Devices, Inc.", "")
-        .replace("Advanced Micro Devices", "")
-        .replace("AMD", "")
-        .replace("[", "")
-        .replace("]", "");
-    // Collapse multiple spaces
+    sysfs: PathBuf,
+    pci_ids: Vec<PathBuf>,
+    sections: Vec<Section>,
+    style: TextStyle,
+    lowercase: bool,
+    details: Option<Details>,
+    rotation: Rotation,
+}
+
+/// Shortens a GPU name to its model, e.g. `NVIDIA Corporation AD102
+/// [GeForce RTX 4090] (rev a1)` to `RTX 4090`.
+fn clean_gpu_name(vendor: GpuVendor, name: &str) -> String {
+    let mut name = name.trim();
+    // lspci ends with the chip revision
+    if let Some((rest, _)) = name.rsplit_once(" (rev ") {
+        name = rest;
+    }
+    let name = vendor.strip_prefix(name).unwrap_or(name);
+    let name = marketing_name(name);
+    let name = vendor
+        .brand()
+        .and_then(|brand| name.strip_prefix(brand))
+        .unwrap_or(name);
     name.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
-fn detect_gpu() -> String {
+/// Looks up a device in the `pci.ids` format: vendors at the start of a
+/// line, their devices indented by one tab and subsystems by two.
+fn pci_device_name(ids: &str, vendor: u16, device: u16) -> Option<String> {
+    let vendor = format!("{vendor:04x}");
+    let device = format!("{device:04x}");
+    let mut in_vendor = false;
+    for line in ids.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        if let Some(entry) = line.strip_prefix('\t') {
+            if in_vendor && !entry.starts_with('\t') {
+                if let Some(name) = entry.strip_prefix(device.as_str()) {
+                    return Some(name.trim().to_string());
+                }
+            }
+        } else {
+            if in_vendor {
+                // Devices of a vendor are listed together
+                return None;
+            }
+            in_vendor = line.starts_with(vendor.as_str());
+        }
+    }
+    None
+}
+
+/// The marketing name from a `pci.ids` device name: the bracketed part of
+/// names like `AD102 [GeForce RTX 4090]`, or the whole name.
+fn marketing_name(name: &str) -> &str {
+    name.rsplit_once('[')
+        .and_then(|(_, rest)| rest.strip_suffix(']'))
+        .unwrap_or(name)
+}
+
+fn read_hex(path: &Path) -> Option<u16> {
+    let text = fs::read_to_string(path).ok()?;
+    u16::from_str_radix(text.trim().trim_start_matches("0x"), 16).ok()
+}
+
+/// Names the display adapters under `sysfs/class/drm` from the PCI ID
+/// database, listing discrete GPUs before Intel integrated ones.
+fn detect_gpu_sysfs(sysfs: &Path, ids: &str) -> Option<String> {
+    let mut cards: Vec<(bool, u32, String)> = fs::read_dir(sysfs.join("class/drm"))
+        .ok()?
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            // Connectors such as `card0-DP-1` share the prefix
+            let name = entry.file_name();
+            let number = name.to_str()?.strip_prefix("card")?.parse().ok()?;
+            let device = entry.path().join("device");
+            let vendor = read_hex(&device.join("vendor"))?;
+            let id = read_hex(&device.join("device"))?;
+            let name = pci_device_name(ids, vendor, id)?;
+            let name = clean_gpu_name(GpuVendor::from_pci(vendor), &name);
+            Some((vendor == VENDOR_INTEL, number, name))
+        })
+        .collect();
+    cards.sort();
+    cards.into_iter().map(|(_, _, name)| name).next()
+}
+
+fn detect_gpu(sysfs: &Path, pci_ids: &[PathBuf]) -> String {
+    let ids = pci_ids
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok());
+    match ids.map(|ids| detect_gpu_sysfs(sysfs, &ids)) {
+        Some(Some(name)) => return name,
+        Some(None) => debug!(
+            "can't name a GPU from {}, trying the drivers",
+            sysfs.display()
+        ),
+        None => debug!("no pci.ids database found, trying the drivers"),
+    }
+
     if let Ok(entries) = fs::read_dir("/proc/driver/nvidia/gpus") {
         for entry in entries.flatten() {
             if let Ok(info) = fs::read_to_string(entry.path().join("information")) {
                 for line in info.lines() {
                     if line.starts_with("Model:") {
                         let raw = line.trim_start_matches("Model:").trim();
-                        return clean_gpu_name(raw);
+                        return clean_gpu_name(GpuVendor::Nvidia, raw);
                     }
                 }
             }
         }
     }
 
-    if let Ok(output) = Command::new("lspci").output() {
-        let text = String::from_utf8_lossy(&output.stdout);
-        for line in text.lines() {
-            if line.contains("VGA") || line.contains("3D controller") {
-                if let Some(pos) = line.find(": ") {
-                    return clean_gpu_name(&line[pos + 2..]);
+    match Command::new("lspci").output() {
+        Ok(output) => {
+            let text = String::from_utf8_lossy(&output.stdout);
+            for line in text.lines() {
+                if line.contains("VGA") || line.contains("3D controller") {
+                    if let Some(pos) = line.find(": ") {
+                        let name = &line[pos + 2..];
+                        return clean_gpu_name(GpuVendor::from_name(name), name);
+                    }
                 }
             }
         }
+        Err(e) => warn!("can't run lspci to name the GPU: {e}"),
     }
 
     "Unknown".to_string()
@@ -76,36 +360,144 @@ fn detect_cpu_name(sys: &System) -> String {
     "Unknown".to_string()
 }
 
+fn detect_desktop() -> String {
+    // `XDG_CURRENT_DESKTOP` can list several, e.g. `ubuntu:GNOME`
+    std::env::var("XDG_CURRENT_DESKTOP")
+        .ok()
+        .and_then(|desktops| desktops.split(':').next_back().map(str::to_string))
+        .or_else(|| std::env::var("DESKTOP_SESSION").ok())
+        .unwrap_or_default()
+}
+
+/// Preferred modes of connected monitors, from DRM connectors such as
+/// `card0-DP-1`.
+fn detect_resolutions(sysfs: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(sysfs.join("class/drm")) else {
+        return Vec::new();
+    };
+    let mut connectors: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            fs::read_to_string(path.join("status")).is_ok_and(|s| s.trim() == "connected")
+        })
+        .collect();
+    connectors.sort();
+    connectors
+        .iter()
+        .filter_map(|path| {
+            let modes = fs::read_to_string(path.join("modes")).ok()?;
+            modes.lines().next().map(str::to_string)
+        })
+        .collect()
+}
+
+fn format_uptime(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86_400, secs / 3600 % 24, secs / 60 % 60);
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else {
+        format!("{minutes}m")
+    }
+}
+
 impl SystemDetailsModule {
     pub fn new() -> Self {
-        let sys = System::new_all();
+        Self {
+            enabled: false,
+            sysfs: PathBuf::from(hwmon::DEFAULT_SYSFS_ROOT),
+            pci_ids: PCI_IDS_PATHS.iter().map(PathBuf::from).collect(),
+            sections: DEFAULT_SECTIONS
+                .iter()
+                .filter_map(|spec| Section::parse(spec).ok())
+                .collect(),
+            style: TextStyle::Superscript,
+            lowercase: true,
+            details: None,
+            rotation: Rotation::new(DEFAULT_ROTATION),
+        }
+    }
 
-        let os_name = System::name().unwrap_or_default();
-        let os_version = System::os_version().unwrap_or_default();
-        let cpu = detect_cpu_name(&sys);
-        let gpu = detect_gpu();
-        let raw_gb = sys.total_memory() as f64 / 1024.0 / 1024.0 / 1024.0;
-        let total_ram = 2u64.pow((raw_gb.log2()).ceil() as u32);
+    /// Where sysfs is mounted, normally `/sys`.
+    pub fn set_sysfs_root(&mut self, root: PathBuf) {
+        if self.sysfs != root {
+            self.sysfs = root;
+            self.details = None;
+        }
+    }
 
-        let os_version = os_version.replace("Rolling", "").trim().to_string();
+    /// PCI ID databases to name GPUs from, the first readable one is used.
+    pub fn set_pci_ids(&mut self, paths: Vec<PathBuf>) {
+        if self.pci_ids != paths {
+            self.pci_ids = paths;
+            self.details = None;
+        }
+    }
 
-        let parts = vec![
-            to_superscript(&format!("{os_name} {os_version}").to_lowercase()),
-            to_superscript(&cpu.to_lowercase()),
-            to_superscript(&gpu.to_lowercase()),
-            to_superscript(&format!("{total_ram}gb")),
-        ];
+    pub fn set_sections(&mut self, sections: Vec<Section>) {
+        self.sections = sections;
+    }
 
-        Self {
-            enabled: false,
-            parts,
-            index: 0,
-            tick_count: 0,
+    pub fn set_style(&mut self, style: TextStyle) {
+        self.style = style;
+    }
+
+    /// Lowercases values, which suits superscript since it has no capitals.
+    pub fn set_lowercase(&mut self, lowercase: bool) {
+        self.lowercase = lowercase;
+    }
+
+    pub fn set_rotation_interval(&mut self, interval: Duration) {
+        self.rotation.set_period(interval);
+    }
+
+    fn value(&self, kind: SectionKind) -> Option<String> {
+        let details = self.details.as_ref()?;
+        let value = match kind {
+            SectionKind::Os => details.os.clone(),
+            SectionKind::Kernel => details.kernel.clone(),
+            SectionKind::Hostname => details.hostname.clone(),
+            SectionKind::Desktop => details.desktop.clone(),
+            SectionKind::Resolution => details.resolution.clone(),
+            SectionKind::Uptime => format_uptime(System::uptime()),
+            SectionKind::Cpu => details.cpu.clone(),
+            SectionKind::Cores => details.cores.clone(),
+            SectionKind::Gpu => details.gpu.clone(),
+            SectionKind::Ram => details.ram.clone(),
+        };
+        (!value.is_empty()).then_some(value)
+    }
+
+    fn render(&self, section: &Section) -> Option<String> {
+        let mut value = self.value(section.kind)?;
+        if self.lowercase {
+            value = value.to_lowercase();
         }
+        let value = match self.style {
+            TextStyle::Superscript => to_superscript(&value),
+            TextStyle::SmallCaps => to_smallcaps(&value),
+            TextStyle::Plain => value,
+        };
+        Some(match &section.label {
+            Some(label) if !label.is_empty() => format!("{label} {value}"),
+            _ => value,
+        })
+    }
+}
+
+impl Default for SystemDetailsModule {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Module for SystemDetailsModule {
+    fn id(&self) -> &str {
+        "system_details"
+    }
+
     fn name(&self) -> &str {
         "System Details"
     }
@@ -118,16 +510,177 @@ impl Module for SystemDetailsModule {
         self.enabled = enabled;
     }
 
+    fn rotation_interval(&self) -> Option<Duration> {
+        Some(self.rotation.period())
+    }
+
     fn tick(&mut self) -> Option<String> {
-        if !self.enabled || self.parts.is_empty() {
+        if !self.enabled {
             return None;
         }
-        let part = self.parts[self.index].clone();
-        self.tick_count += 1;
-        if self.tick_count >= TICKS_PER_SECTION {
-            self.tick_count = 0;
-            self.index = (self.index + 1) % self.parts.len();
+        if self.details.is_none() {
+            self.details = Some(Details::detect(&self.sysfs, &self.pci_ids));
         }
-        Some(part)
+
+        let parts: Vec<String> = self
+            .sections
+            .iter()
+            .filter_map(|section| self.render(section))
+            .collect();
+        if parts.is_empty() {
+            return None;
+        }
+        Some(parts[self.rotation.index(parts.len())].clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Fixture;
+
+    const PCI_IDS: &str = "\
+# Comment
+10de  NVIDIA Corporation
+\t2684  AD102 [GeForce RTX 4090]
+\t\t10de 165b  GeForce RTX 4090 Founders Edition
+1002  Advanced Micro Devices, Inc. [AMD/ATI]
+\t744c  Navi 31 [Radeon RX 7900 XT/7900 XTX/7900 GRE/7900M]
+8086  Intel Corporation
+\t2684  Not this one
+\t46a6  Alder Lake-P GT2 [Iris Xe Graphics]
+\t56a0  DG2 [Arc A770]
+";
+
+    #[test]
+    fn looks_up_pci_names() {
+        let name = |vendor, device| pci_device_name(PCI_IDS, vendor, device);
+        assert_eq!(
+            name(0x10de, 0x2684).as_deref(),
+            Some("AD102 [GeForce RTX 4090]")
+        );
+        assert_eq!(name(0x8086, 0x56a0).as_deref(), Some("DG2 [Arc A770]"));
+        assert_eq!(name(0x10de, 0x46a6), None);
+        assert_eq!(name(0x1234, 0x2684), None);
+    }
+
+    #[test]
+    fn cleans_gpu_names() {
+        let clean = |vendor, device| {
+            let name = pci_device_name(PCI_IDS, vendor, device).unwrap();
+            clean_gpu_name(GpuVendor::from_pci(vendor), &name)
+        };
+        assert_eq!(clean(0x10de, 0x2684), "RTX 4090");
+        assert_eq!(clean(0x8086, 0x46a6), "Iris Xe Graphics");
+        assert_eq!(clean(0x8086, 0x56a0), "Arc A770");
+        assert_eq!(
+            clean(0x1002, 0x744c),
+            "Radeon RX 7900 XT/7900 XTX/7900 GRE/7900M"
+        );
+
+        // As lspci and the NVIDIA driver print them
+        let lspci = |name| clean_gpu_name(GpuVendor::from_name(name), name);
+        assert_eq!(
+            lspci("NVIDIA Corporation AD102 [GeForce RTX 4090] (rev a1)"),
+            "RTX 4090"
+        );
+        assert_eq!(
+            lspci("Advanced Micro Devices, Inc. [AMD/ATI] Navi 21 [Radeon RX 6800/6800 XT / 6900 XT] (rev c1)"),
+            "Radeon RX 6800/6800 XT / 6900 XT"
+        );
+        assert_eq!(
+            lspci("Intel Corporation Alder Lake-P GT2 [Iris Xe Graphics] (rev 0c)"),
+            "Iris Xe Graphics"
+        );
+        assert_eq!(
+            clean_gpu_name(GpuVendor::Nvidia, "NVIDIA GeForce GTX 1080 Ti"),
+            "GTX 1080 Ti"
+        );
+        // Brands are only dropped where the vendor uses them
+        assert_eq!(
+            clean_gpu_name(GpuVendor::Nvidia, "NVIDIA RTX A4000"),
+            "RTX A4000"
+        );
+        assert_eq!(
+            lspci(
+                "Matrox Electronics Systems Ltd. MGA G200e [Pilot] ServerEngines (SEP1) (rev 05)"
+            ),
+            "Matrox Electronics Systems Ltd. MGA G200e [Pilot] ServerEngines (SEP1)"
+        );
+        assert_eq!(GpuVendor::from_name("ATI Technologies Inc"), GpuVendor::Amd);
+        assert_eq!(GpuVendor::from_name("ATIX"), GpuVendor::Other);
+    }
+
+    #[test]
+    fn prefers_discrete_gpu() {
+        let root = Fixture::new(
+            "details-gpu",
+            &[
+                ("class/drm/card0/device/vendor", "0x8086\n"),
+                ("class/drm/card0/device/device", "0x46a6\n"),
+                ("class/drm/card0-eDP-1/status", "connected\n"),
+                ("class/drm/card0-eDP-1/modes", "2560x1600\n1920x1200\n"),
+                ("class/drm/card1/device/vendor", "0x10de\n"),
+                ("class/drm/card1/device/device", "0x2684\n"),
+                ("class/drm/card1-HDMI-A-1/status", "disconnected\n"),
+            ],
+        );
+        assert_eq!(
+            detect_gpu_sysfs(&root, PCI_IDS).as_deref(),
+            Some("RTX 4090")
+        );
+        assert_eq!(detect_resolutions(&root), ["2560x1600"]);
+
+        fs::remove_dir_all(root.join("class/drm/card1")).unwrap();
+        assert_eq!(
+            detect_gpu_sysfs(&root, PCI_IDS).as_deref(),
+            Some("Iris Xe Graphics")
+        );
+    }
+
+    #[test]
+    fn shows_details_from_the_sysfs_root() {
+        let root = Fixture::new(
+            "details-module",
+            &[
+                ("pci.ids", PCI_IDS),
+                ("class/drm/card0/device/vendor", "0x1002\n"),
+                ("class/drm/card0/device/device", "0x744c\n"),
+                ("class/drm/card0-DP-1/status", "connected\n"),
+                ("class/drm/card0-DP-1/modes", "3840x2160\n"),
+                ("class/drm/card0-DP-2/status", "connected\n"),
+                ("class/drm/card0-DP-2/modes", "1920x1080\n"),
+            ],
+        );
+        let mut details = SystemDetailsModule::new();
+        details.set_sysfs_root(root.to_path_buf());
+        details.set_pci_ids(vec![root.join("missing.ids"), root.join("pci.ids")]);
+        details.set_style(TextStyle::Plain);
+        details.set_lowercase(false);
+        details.set_enabled(true);
+
+        let show = |details: &mut SystemDetailsModule, spec| {
+            details.set_sections(vec![Section::parse(spec).unwrap()]);
+            details.tick()
+        };
+        assert_eq!(
+            show(&mut details, "gpu").as_deref(),
+            Some("Radeon RX 7900 XT/7900 XTX/7900 GRE/7900M")
+        );
+        assert_eq!(
+            show(&mut details, "resolution label=Res").as_deref(),
+            Some("Res 3840x2160 + 1920x1080")
+        );
+    }
+
+    #[test]
+    fn parses_sections() {
+        let section = Section::parse("uptime label=Up").unwrap();
+        assert_eq!(section.kind, SectionKind::Uptime);
+        assert_eq!(section.label.as_deref(), Some("Up"));
+        assert!(Section::parse("gpu bold").is_err());
+        assert!(Section::parse("battery").is_err());
+        assert_eq!(format_uptime(3 * 86_400 + 5 * 3600), "3d 5h");
+        assert_eq!(format_uptime(2 * 3600 + 7 * 60), "2h 7m");
     }
 }
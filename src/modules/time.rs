@@ -1,5 +1,8 @@
 use super::Module;
+use chrono::format::{Item, StrftimeItems};
 use chrono::Local;
+use std::fmt::Write;
+use tracing::debug;
 
 pub struct TimeModule {
     enabled: bool,
@@ -15,11 +18,26 @@ impl TimeModule {
     }
 
     pub fn set_format(&mut self, format: String) {
-        self.format = format;
+        if self.format != format {
+            if StrftimeItems::new(&format).any(|item| matches!(item, Item::Error)) {
+                debug!("time format {format:?} is invalid, nothing is shown");
+            }
+            self.format = format;
+        }
+    }
+}
+
+impl Default for TimeModule {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Module for TimeModule {
+    fn id(&self) -> &str {
+        "time"
+    }
+
     fn name(&self) -> &str {
         "Time"
     }
@@ -36,7 +54,10 @@ impl Module for TimeModule {
         if !self.enabled {
             return None;
         }
-        let now = Local::now();
-        Some(now.format(&self.format).to_string())
+        // An invalid format (e.g. a trailing '%' mid-edit) shows nothing
+        // instead of panicking inside `to_string`
+        let mut out = String::new();
+        write!(out, "{}", Local::now().format(&self.format)).ok()?;
+        Some(out)
     }
 }
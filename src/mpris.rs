@@ -0,0 +1,687 @@
+//! Native MPRIS client that keeps track of every media player on the bus.
+//!
+//! Players are discovered through the bus daemon and kept up to date from
+//! their `PropertiesChanged` and `Seeked` signals, so reading the current
+//! state never blocks on D-Bus.
+
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tracing::{debug, error, info, warn};
+use zbus::fdo::{DBusProxy, PropertiesProxy};
+use zbus::message::Type as MessageType;
+use zbus::names::InterfaceName;
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::{Connection, MatchRule, MessageStream};
+
+pub const BUS_NAME_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+type Properties = HashMap<String, OwnedValue>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    #[default]
+    Stopped,
+}
+
+impl PlaybackStatus {
+    fn parse(status: &str) -> Self {
+        match status {
+            "Playing" => Self::Playing,
+            "Paused" => Self::Paused,
+            _ => Self::Stopped,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Playing => "Playing",
+            Self::Paused => "Paused",
+            Self::Stopped => "Stopped",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopStatus {
+    #[default]
+    None,
+    Track,
+    Playlist,
+}
+
+impl LoopStatus {
+    fn parse(status: &str) -> Self {
+        match status {
+            "Track" => Self::Track,
+            "Playlist" => Self::Playlist,
+            _ => Self::None,
+        }
+    }
+}
+
+/// Last known state of one player.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Player {
+    /// Well-known bus name, e.g. `org.mpris.MediaPlayer2.spotify`.
+    pub bus_name: String,
+    pub artist: String,
+    pub title: String,
+    pub album: String,
+    pub track_number: Option<u32>,
+    pub status: PlaybackStatus,
+    pub length_us: u64,
+    /// 0.0 to 1.0, when the player reports it.
+    pub volume: Option<f64>,
+    pub shuffle: bool,
+    pub loop_status: LoopStatus,
+    /// Lyrics from `xesam:asText`, empty when the player doesn't send them.
+    pub lyrics: String,
+    position_us: u64,
+    position_at: Instant,
+    rate: f64,
+}
+
+impl Player {
+    fn new(bus_name: String) -> Self {
+        Self {
+            bus_name,
+            artist: String::new(),
+            title: String::new(),
+            album: String::new(),
+            track_number: None,
+            status: PlaybackStatus::Stopped,
+            length_us: 0,
+            volume: None,
+            shuffle: false,
+            loop_status: LoopStatus::None,
+            lyrics: String::new(),
+            position_us: 0,
+            position_at: Instant::now(),
+            rate: 1.0,
+        }
+    }
+
+    /// The bus name without the MPRIS prefix, e.g. `spotify` or
+    /// `firefox.instance_1_42`.
+    pub fn name(&self) -> &str {
+        self.bus_name
+            .strip_prefix(BUS_NAME_PREFIX)
+            .unwrap_or(&self.bus_name)
+    }
+
+    /// Playback position, advanced by the time since the player last
+    /// reported it. Players only signal position changes on seeks.
+    pub fn position_us(&self) -> u64 {
+        let mut position = self.position_us;
+        if self.status == PlaybackStatus::Playing {
+            let elapsed = self.position_at.elapsed().as_micros() as f64 * self.rate;
+            position = position.saturating_add(elapsed.max(0.0) as u64);
+        }
+        if self.length_us > 0 {
+            position = position.min(self.length_us);
+        }
+        position
+    }
+
+    fn set_position(&mut self, position_us: i64) {
+        self.position_us = position_us.max(0) as u64;
+        self.position_at = Instant::now();
+    }
+
+    fn update(&mut self, properties: &Properties) {
+        // Keep the extrapolated position when the rate or status changes
+        if properties.contains_key("PlaybackStatus") || properties.contains_key("Rate") {
+            let position = self.position_us();
+            self.set_position(position as i64);
+        }
+
+        if let Some(status) = properties.get("PlaybackStatus").and_then(|v| as_string(v)) {
+            self.status = PlaybackStatus::parse(&status);
+        }
+        if let Some(rate) = properties.get("Rate").and_then(|v| as_f64(v)) {
+            self.rate = rate;
+        }
+        if let Some(volume) = properties.get("Volume").and_then(|v| as_f64(v)) {
+            self.volume = Some(volume.clamp(0.0, 1.0));
+        }
+        if let Some(Value::Bool(shuffle)) = properties.get("Shuffle").map(|v| unwrap_variant(v)) {
+            self.shuffle = *shuffle;
+        }
+        if let Some(status) = properties.get("LoopStatus").and_then(|v| as_string(v)) {
+            self.loop_status = LoopStatus::parse(&status);
+        }
+        // Metadata first, as a new track resets the position
+        if let Some(metadata) = properties.get("Metadata") {
+            self.update_metadata(metadata);
+        }
+        if let Some(position) = properties.get("Position").and_then(|v| as_i64(v)) {
+            self.set_position(position);
+        }
+    }
+
+    fn update_metadata(&mut self, metadata: &Value) {
+        let Ok(metadata) = unwrap_variant(metadata).try_clone() else {
+            return;
+        };
+        let Ok(metadata) = HashMap::<String, OwnedValue>::try_from(metadata) else {
+            return;
+        };
+        let get = |key: &str| metadata.get(key).map(|v| unwrap_variant(v));
+
+        let title = get("xesam:title").and_then(as_string).unwrap_or_default();
+        let track_changed = title != self.title;
+
+        self.artist = get("xesam:artist").and_then(as_string).unwrap_or_default();
+        self.title = title;
+        self.album = get("xesam:album").and_then(as_string).unwrap_or_default();
+        self.track_number = get("xesam:trackNumber")
+            .and_then(as_i64)
+            .and_then(|n| u32::try_from(n).ok())
+            .filter(|&n| n > 0);
+        self.lyrics = get("xesam:asText").and_then(as_string).unwrap_or_default();
+        self.length_us = get("mpris:length")
+            .and_then(as_i64)
+            .map_or(0, |length| length.max(0) as u64);
+        if track_changed {
+            self.set_position(0);
+        }
+    }
+}
+
+fn unwrap_variant<'a>(value: &'a Value<'a>) -> &'a Value<'a> {
+    match value {
+        Value::Value(inner) => unwrap_variant(inner),
+        value => value,
+    }
+}
+
+/// A string, or a list of strings such as `xesam:artist` joined by commas.
+fn as_string(value: &Value) -> Option<String> {
+    match unwrap_variant(value) {
+        Value::Str(s) => Some(s.to_string()),
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().filter_map(as_string).collect();
+            Some(items.join(", "))
+        }
+        _ => None,
+    }
+}
+
+fn as_i64(value: &Value) -> Option<i64> {
+    match unwrap_variant(value) {
+        Value::I64(v) => Some(*v),
+        Value::U64(v) => i64::try_from(*v).ok(),
+        Value::I32(v) => Some(i64::from(*v)),
+        Value::U32(v) => Some(i64::from(*v)),
+        Value::F64(v) => Some(*v as i64),
+        _ => None,
+    }
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match unwrap_variant(value) {
+        Value::F64(v) => Some(*v),
+        value => as_i64(value).map(|v| v as f64),
+    }
+}
+
+/// Players in the order they appeared on the bus.
+type SharedPlayers = Arc<Mutex<Vec<Player>>>;
+/// Property reads in flight, by the player's bus name.
+type Fetches = JoinSet<(String, zbus::Result<Properties>)>;
+
+/// Background D-Bus connection tracking MPRIS players. It runs on its own
+/// thread and is shut down when dropped.
+pub struct MprisWatcher {
+    players: SharedPlayers,
+    shutdown: watch::Sender<bool>,
+}
+
+impl MprisWatcher {
+    /// Watches the session bus, or the bus at `address` when it isn't empty,
+    /// e.g. `unix:path=/run/user/1000/bus`.
+    pub fn spawn(address: String) -> Self {
+        let players: SharedPlayers = Arc::default();
+        let (shutdown, shutdown_rx) = watch::channel(false);
+
+        let shared = Arc::clone(&players);
+        let spawned = thread::Builder::new()
+            .name("mpris".to_string())
+            .spawn(move || {
+                let runtime = match tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                {
+                    Ok(rt) => rt,
+                    Err(e) => {
+                        error!("can't start MPRIS runtime: {e}");
+                        return;
+                    }
+                };
+                runtime.block_on(run(address, shared, shutdown_rx));
+            });
+
+        if let Err(e) = spawned {
+            error!("can't start MPRIS thread: {e}");
+        }
+
+        Self { players, shutdown }
+    }
+
+    pub fn players(&self) -> Vec<Player> {
+        self.players
+            .lock()
+            .map(|players| players.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for MprisWatcher {
+    fn drop(&mut self) {
+        let _ = self.shutdown.send(true);
+    }
+}
+
+async fn run(address: String, players: SharedPlayers, mut shutdown: watch::Receiver<bool>) {
+    let mut backoff = MIN_BACKOFF;
+
+    loop {
+        let mut connected = false;
+        tokio::select! {
+            result = watch_bus(&address, &players, &mut connected) => {
+                if let Err(e) = result {
+                    warn!("MPRIS connection lost: {e}");
+                }
+            }
+            _ = shutdown.changed() => break,
+        }
+
+        set_players(&players, Vec::new());
+        if connected {
+            backoff = MIN_BACKOFF;
+        }
+        debug!("reconnecting to MPRIS in {backoff:?}");
+
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = shutdown.changed() => break,
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+
+    set_players(&players, Vec::new());
+}
+
+async fn connect(address: &str) -> zbus::Result<Connection> {
+    if address.trim().is_empty() {
+        Connection::session().await
+    } else {
+        zbus::connection::Builder::address(address.trim())?
+            .build()
+            .await
+    }
+}
+
+async fn watch_bus(
+    address: &str,
+    shared: &SharedPlayers,
+    connected: &mut bool,
+) -> zbus::Result<()> {
+    let bus = if address.is_empty() {
+        "the session bus"
+    } else {
+        address
+    };
+    info!("watching media players on {bus}");
+    let conn = connect(address).await?;
+    *connected = true;
+    let dbus = DBusProxy::new(&conn).await?;
+
+    // Subscribe before listing so players appearing in between aren't missed
+    let mut owner_changes = dbus.receive_name_owner_changed().await?;
+    let properties_rule = MatchRule::builder()
+        .msg_type(MessageType::Signal)
+        .interface(PROPERTIES_INTERFACE)?
+        .member("PropertiesChanged")?
+        .path(OBJECT_PATH)?
+        .arg(0, PLAYER_INTERFACE)?
+        .build();
+    let mut property_changes = MessageStream::for_match_rule(properties_rule, &conn, None).await?;
+    let seeked_rule = MatchRule::builder()
+        .msg_type(MessageType::Signal)
+        .interface(PLAYER_INTERFACE)?
+        .member("Seeked")?
+        .path(OBJECT_PATH)?
+        .build();
+    let mut seeks = MessageStream::for_match_rule(seeked_rule, &conn, None).await?;
+
+    // Unique connection name of each player's well-known name
+    let mut owners: HashMap<String, String> = HashMap::new();
+    let mut players: Vec<Player> = Vec::new();
+    // Properties are read off the loop, so a player that doesn't answer
+    // can't hold up signals from the others until the call times out
+    let mut fetches = JoinSet::new();
+
+    for name in dbus.list_names().await? {
+        let name = name.to_string();
+        if !name.starts_with(BUS_NAME_PREFIX) {
+            continue;
+        }
+        let Ok(owner) = dbus.get_name_owner(name.as_str().try_into()?).await else {
+            continue;
+        };
+        owners.insert(name.clone(), owner.to_string());
+        fetch_properties(&mut fetches, &conn, name.clone());
+        players.push(Player::new(name));
+    }
+    set_players(shared, players.clone());
+
+    loop {
+        tokio::select! {
+            change = owner_changes.next() => {
+                let change = change.ok_or(zbus::Error::InvalidReply)?;
+                let args = change.args()?;
+                let name = args.name().to_string();
+                if !name.starts_with(BUS_NAME_PREFIX) {
+                    continue;
+                }
+
+                players.retain(|p| p.bus_name != name);
+                owners.remove(&name);
+                if let Some(owner) = args.new_owner().as_ref() {
+                    debug!("media player {name} appeared");
+                    owners.insert(name.clone(), owner.to_string());
+                    fetch_properties(&mut fetches, &conn, name.clone());
+                    players.push(Player::new(name));
+                } else {
+                    debug!("media player {name} went away");
+                }
+            }
+            Some(fetched) = fetches.join_next() => {
+                let Ok((bus_name, properties)) = fetched else {
+                    continue;
+                };
+                match properties {
+                    Ok(properties) => {
+                        // The player may have gone away in the meantime
+                        if let Some(player) = players.iter_mut().find(|p| p.bus_name == bus_name) {
+                            player.update(&properties);
+                        }
+                    }
+                    Err(e) => warn!("can't read player {bus_name}: {e}"),
+                }
+            }
+            msg = property_changes.next() => {
+                let msg = msg.ok_or(zbus::Error::InvalidReply)??;
+                let Some(player) = sender_player(&msg, &owners, &mut players) else {
+                    continue;
+                };
+                let Ok((_, changed, invalidated)) =
+                    msg.body().deserialize::<(String, Properties, Vec<String>)>()
+                else {
+                    continue;
+                };
+                player.update(&changed);
+
+                // Position is never signalled, so re-read it when playback
+                // changes and players that only invalidate get re-queried
+                let refetch = changed.contains_key("PlaybackStatus")
+                    || changed.contains_key("Metadata")
+                    || !invalidated.is_empty();
+                if refetch {
+                    let bus_name = player.bus_name.clone();
+                    fetch_properties(&mut fetches, &conn, bus_name);
+                }
+            }
+            msg = seeks.next() => {
+                let msg = msg.ok_or(zbus::Error::InvalidReply)??;
+                let Ok(position) = msg.body().deserialize::<i64>() else {
+                    continue;
+                };
+                if let Some(player) = sender_player(&msg, &owners, &mut players) {
+                    player.set_position(position);
+                }
+            }
+        }
+
+        set_players(shared, players.clone());
+    }
+}
+
+/// The player that sent `msg`; signals carry the sender's unique name.
+fn sender_player<'a>(
+    msg: &zbus::Message,
+    owners: &HashMap<String, String>,
+    players: &'a mut [Player],
+) -> Option<&'a mut Player> {
+    let header = msg.header();
+    let sender = header.sender()?.as_str();
+    let (name, _) = owners.iter().find(|(_, owner)| owner.as_str() == sender)?;
+    players.iter_mut().find(|p| &p.bus_name == name)
+}
+
+async fn get_properties(conn: &Connection, bus_name: &str) -> zbus::Result<Properties> {
+    let proxy = PropertiesProxy::builder(conn)
+        .destination(bus_name.to_string())?
+        .path(OBJECT_PATH)?
+        .build()
+        .await?;
+    let interface = InterfaceName::from_static_str_unchecked(PLAYER_INTERFACE);
+    Ok(proxy.get_all(interface).await?)
+}
+
+/// Reads a player's current state in the background. Players that don't
+/// answer are still listed so they show up once they signal a change.
+fn fetch_properties(fetches: &mut Fetches, conn: &Connection, bus_name: String) {
+    let conn = conn.clone();
+    fetches.spawn(async move {
+        let properties = get_properties(&conn, &bus_name).await;
+        (bus_name, properties)
+    });
+}
+
+fn set_players(shared: &SharedPlayers, players: Vec<Player>) {
+    if let Ok(mut shared) = shared.lock() {
+        *shared = players;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::path::PathBuf;
+    use std::process::{Child, Command, Stdio};
+    use zbus::object_server::SignalEmitter;
+
+    /// A `dbus-daemon` of our own, stopped when dropped.
+    struct PrivateBus {
+        daemon: Child,
+        dir: PathBuf,
+        address: String,
+    }
+
+    impl PrivateBus {
+        /// Starts a bus, or returns `None` where `dbus-daemon` isn't
+        /// installed.
+        fn start() -> Option<Self> {
+            let dir =
+                std::env::temp_dir().join(format!("osc-chatbox-mpris-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            let spawned = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address=1"])
+                .arg(format!("--address=unix:path={}", dir.join("bus").display()))
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn();
+            let Ok(mut daemon) = spawned else {
+                eprintln!("dbus-daemon isn't installed, skipping");
+                return None;
+            };
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+            Some(Self {
+                daemon,
+                dir,
+                address: address.trim().to_string(),
+            })
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    struct MockPlayer {
+        status: String,
+        title: String,
+    }
+
+    #[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl MockPlayer {
+        #[zbus(property)]
+        fn playback_status(&self) -> String {
+            self.status.clone()
+        }
+
+        #[zbus(property)]
+        fn metadata(&self) -> HashMap<String, OwnedValue> {
+            let value = |v: Value| OwnedValue::try_from(v).unwrap();
+            HashMap::from([
+                (
+                    "xesam:title".to_string(),
+                    value(Value::from(self.title.as_str())),
+                ),
+                (
+                    "xesam:artist".to_string(),
+                    value(Value::from(vec!["Mock Artist"])),
+                ),
+                (
+                    "mpris:length".to_string(),
+                    value(Value::from(180_000_000i64)),
+                ),
+            ])
+        }
+    }
+
+    /// A player that never answers, as a hung application would.
+    struct StuckPlayer;
+
+    #[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl StuckPlayer {
+        #[zbus(property)]
+        async fn playback_status(&self) -> String {
+            std::future::pending().await
+        }
+    }
+
+    async fn serve<I: zbus::object_server::Interface>(
+        address: &str,
+        name: &str,
+        player: I,
+    ) -> Connection {
+        zbus::connection::Builder::address(address)
+            .unwrap()
+            .name(format!("{BUS_NAME_PREFIX}{name}"))
+            .unwrap()
+            .serve_at(OBJECT_PATH, player)
+            .unwrap()
+            .build()
+            .await
+            .unwrap()
+    }
+
+    async fn wait_until(watcher: &MprisWatcher, done: impl Fn(&[Player]) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(&watcher.players()) {
+            assert!(
+                Instant::now() < deadline,
+                "gave up waiting, players: {:?}",
+                watcher.players()
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn follows_players_on_a_private_bus() {
+        let Some(bus) = PrivateBus::start() else {
+            return;
+        };
+        let _stuck = serve(&bus.address, "stuck", StuckPlayer).await;
+        let mock = serve(
+            &bus.address,
+            "mock",
+            MockPlayer {
+                status: "Paused".to_string(),
+                title: "First".to_string(),
+            },
+        )
+        .await;
+
+        let watcher = MprisWatcher::spawn(bus.address.clone());
+        // Listed without waiting for the player that doesn't answer
+        wait_until(&watcher, |players| {
+            players.len() == 2
+                && players
+                    .iter()
+                    .any(|p| p.name() == "mock" && p.title == "First")
+        })
+        .await;
+        let player = watcher
+            .players()
+            .into_iter()
+            .find(|p| p.name() == "mock")
+            .unwrap();
+        assert_eq!(player.artist, "Mock Artist");
+        assert_eq!(player.status, PlaybackStatus::Paused);
+        assert_eq!(player.length_us, 180_000_000);
+
+        let iface = mock
+            .object_server()
+            .interface::<_, MockPlayer>(OBJECT_PATH)
+            .await
+            .unwrap();
+        {
+            let mut player = iface.get_mut().await;
+            player.status = "Playing".to_string();
+            player.title = "Second".to_string();
+            let emitter: &SignalEmitter = iface.signal_emitter();
+            player.playback_status_changed(emitter).await.unwrap();
+            player.metadata_changed(emitter).await.unwrap();
+        }
+        wait_until(&watcher, |players| {
+            players.iter().any(|p| {
+                p.name() == "mock" && p.title == "Second" && p.status == PlaybackStatus::Playing
+            })
+        })
+        .await;
+
+        // Players leaving the bus are forgotten
+        drop(iface);
+        drop(mock);
+        wait_until(&watcher, |players| {
+            players.len() == 1 && players[0].name() == "stuck"
+        })
+        .await;
+    }
+}
@@ -1,61 +1,593 @@
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
-use crate::modules::afk::AfkModule;
+use chrono::{DateTime, Local};
+use tracing::{debug, error, info, trace, warn};
+
+use crate::budget::{self, Entry, Limits, ModuleBudget};
+use crate::chat::ChatQueue;
+use crate::config::{config_dir, AutomationConfig, Config, Profile};
+use crate::error::Error;
+use crate::events::EventQueue;
+
+use crate::hwmon;
+use crate::modules::afk::{ActivitySourceKind, AfkModule, IdleActivitySource, OscActivitySource};
+use crate::modules::gpu::{GpuBackend, GpuModule};
 use crate::modules::heartrate::HeartrateModule;
-use crate::modules::media::MediaModule;
-use crate::modules::network::NetworkModule;
-use crate::modules::stats::StatsModule;
+use crate::modules::lyrics::{self, LyricsModule};
+use crate::modules::media::{self, BarStyle, MediaModule};
+use crate::modules::network::{NetworkModule, RateUnit, UnitPrefix};
+use crate::modules::sensors::SensorsModule;
+use crate::modules::stats::{LabelStyle, Metric, StatsModule};
 use crate::modules::status::StatusModule;
-use crate::modules::system_details::SystemDetailsModule;
+use crate::modules::system_details::{self, Section, SystemDetailsModule, TextStyle};
 use crate::modules::time::TimeModule;
 use crate::modules::Module;
-use crate::osc::OscClient;
+use crate::osc::receiver::OscReceiver;
+use crate::osc::throttle::SendThrottle;
+use crate::osc::{OscClient, CHATBOX_MAX_LINES, MESSAGE_MAX_CHARS};
+use crate::profiles::ProfileStore;
+use crate::rules::{Context, Rule, RuleEngine};
+use crate::template::{Template, TemplateError};
+
+/// Default share of the chatbox for each module, by module id. Status and
+/// AFK lines are what people read first; system details go first when full.
+const DEFAULT_BUDGETS: &[(&str, ModuleBudget)] = &[
+    ("afk", ModuleBudget::new(90, 3, MESSAGE_MAX_CHARS)),
+    ("status", ModuleBudget::new(80, 12, MESSAGE_MAX_CHARS)),
+    ("time", ModuleBudget::new(70, 5, 32)),
+    ("media", ModuleBudget::new(60, 16, 80)),
+    ("lyrics", ModuleBudget::new(55, 8, 80)),
+    ("heartrate", ModuleBudget::new(50, 6, 16)),
+    ("stats", ModuleBudget::new(40, 8, 48)),
+    ("network", ModuleBudget::new(30, 8, 48)),
+    ("gpu", ModuleBudget::new(30, 8, 48)),
+    ("sensors", ModuleBudget::new(35, 8, 48)),
+    ("system_details", ModuleBudget::new(20, 8, 48)),
+];
+const FALLBACK_BUDGET: ModuleBudget = ModuleBudget::new(50, 8, MESSAGE_MAX_CHARS);
+
+/// The budget a module gets unless the profile overrides it, or `None` for
+/// an unknown module id.
+pub fn default_budget(id: &str) -> Option<ModuleBudget> {
+    DEFAULT_BUDGETS
+        .iter()
+        .find(|(module, _)| *module == id)
+        .map(|(_, budget)| *budget)
+}
+/// A module that panicked is left out for this long before it is ticked
+/// again, so a persistent failure doesn't flood the log.
+const MODULE_RETRY: Duration = Duration::from_secs(10);
 
-const CHAT_MESSAGE_DURATION: Duration = Duration::from_secs(20);
+/// Output of a module's last refresh, reused until it goes stale.
+struct CachedOutput {
+    text: Option<String>,
+    refreshed_at: Instant,
+    fresh_for: Duration,
+}
+
+/// What happened to recent messages, for showing to the user.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SendStatus {
+    /// Last message composed, whether or not it was sent.
+    pub composed: Option<String>,
+    pub sent: Option<String>,
+    pub sent_at: Option<DateTime<Local>>,
+    pub error_count: u64,
+    pub last_error: Option<String>,
+}
+
+/// A chatbox message ready to be sent.
+pub struct Composed {
+    pub text: String,
+    pub play_sound: bool,
+    /// Whether the first line is a chat message.
+    pub chat: bool,
+}
 
 pub struct Orchestrator {
     osc: OscClient,
+    osc_receiver: Option<OscReceiver>,
     pub status: StatusModule,
     pub time: TimeModule,
     pub stats: StatsModule,
     pub network: NetworkModule,
+    pub gpu: GpuModule,
+    pub sensors: SensorsModule,
     pub media: MediaModule,
+    pub lyrics: LyricsModule,
     pub afk: AfkModule,
     pub heartrate: HeartrateModule,
     pub system_details: SystemDetailsModule,
-    pending_chat_message: Option<(String, Instant)>,
+    chat: ChatQueue,
+    /// A chat message started showing and hasn't been sent with a sound yet.
+    chat_sound: bool,
+    throttle: SendThrottle,
+    /// Print messages instead of sending them.
+    dry_run: bool,
+    /// Log chat messages as `[chat]`.
+    redact_chat: bool,
+    send_status: SendStatus,
+    layout: Option<Template>,
+    events: EventQueue,
+    budgets: HashMap<String, ModuleBudget>,
+    cache: HashMap<String, CachedOutput>,
+    rules: RuleEngine,
+    /// Settings in effect while no rule matches.
+    base_profile: Profile,
+    rule_profiles: HashMap<String, Profile>,
 }
 
 impl Orchestrator {
-    pub fn new(osc_target: &str) -> Result<Self, Box<dyn std::error::Error>> {
+    /// Creates the orchestrator. An empty `osc_listen` disables the OSC
+    /// receiver, e.g. when another tool already owns VRChat's output port;
+    /// so does failing to bind it, since sending still works without it.
+    pub fn new(osc_target: &str, osc_listen: &str) -> Result<Self, Error> {
+        let listen = osc_listen.trim();
+        let osc_receiver = if listen.is_empty() {
+            None
+        } else {
+            match OscReceiver::bind(listen) {
+                Ok(receiver) => Some(receiver),
+                Err(e) => {
+                    warn!(
+                        "can't listen for OSC on {listen}, avatar parameters are unavailable: {e}"
+                    );
+                    None
+                }
+            }
+        };
+
         Ok(Self {
             osc: OscClient::new(osc_target)?,
+            osc_receiver,
             status: StatusModule::new(),
             time: TimeModule::new(),
             stats: StatsModule::new(),
             network: NetworkModule::new(),
+            gpu: GpuModule::new(),
+            sensors: SensorsModule::new(),
             media: MediaModule::new(),
+            lyrics: LyricsModule::new(),
             afk: AfkModule::new(),
             heartrate: HeartrateModule::new(),
             system_details: SystemDetailsModule::new(),
-            pending_chat_message: None,
+            chat: ChatQueue::new(MESSAGE_MAX_CHARS),
+            chat_sound: false,
+            throttle: SendThrottle::new(),
+            dry_run: false,
+            redact_chat: true,
+            send_status: SendStatus::default(),
+            layout: None,
+            events: EventQueue::new(Duration::from_secs(5), Duration::from_secs(60)),
+            budgets: DEFAULT_BUDGETS
+                .iter()
+                .map(|(id, budget)| (id.to_string(), *budget))
+                .collect(),
+            cache: HashMap::new(),
+            rules: RuleEngine::new(),
+            base_profile: Profile::default(),
+            rule_profiles: HashMap::new(),
         })
     }
 
+    pub fn from_config(config: &Config) -> Result<Self, Error> {
+        let mut orch = Self::new(&config.osc.target, &config.osc.listen)?;
+        orch.apply_config(config)?;
+        Ok(orch)
+    }
+
+    /// Applies every module setting from `config`. The OSC addresses only
+    /// take effect when the orchestrator is created. A layout that fails to
+    /// parse is also recorded in the send status.
+    pub fn apply_config(&mut self, config: &Config) -> Result<(), TemplateError> {
+        debug!(profile = %config.active_profile, "applying settings");
+        self.redact_chat = config.logging.redact_chat;
+        self.base_profile = config.profile.clone();
+        self.set_automation(&config.automation);
+
+        let profile = self
+            .rules
+            .current()
+            .and_then(|name| self.rule_profiles.get(name))
+            .unwrap_or(&self.base_profile)
+            .clone();
+        self.apply_profile(&profile)
+            .inspect_err(|e| self.send_status.record_error(&Error::from(e.clone())))
+    }
+
+    /// Loads the rules and the profiles they switch to. Rules whose profile
+    /// can't be loaded are skipped.
+    pub fn set_automation(&mut self, automation: &AutomationConfig) {
+        self.rule_profiles.clear();
+        let mut rules = Vec::new();
+
+        if automation.enabled {
+            let store = ProfileStore::default();
+            for config in &automation.rules {
+                let rule = match Rule::from_config(config) {
+                    Ok(rule) => rule,
+                    Err(e) => {
+                        warn!("skipping rule: {e}");
+                        continue;
+                    }
+                };
+                if !self.rule_profiles.contains_key(&rule.profile) {
+                    match store.load(&rule.profile) {
+                        Ok(profile) => {
+                            self.rule_profiles.insert(rule.profile.clone(), profile);
+                        }
+                        Err(e) => {
+                            warn!("skipping rule for profile '{}': {e}", rule.profile);
+                            continue;
+                        }
+                    }
+                }
+                rules.push(rule);
+            }
+        }
+
+        self.rules
+            .set_rules(rules, Duration::from_secs(automation.hold_secs));
+    }
+
+    /// Profile the rules switched to, or `None` while the base settings apply.
+    pub fn rule_profile(&self) -> Option<&str> {
+        self.rules.current()
+    }
+
+    /// Switches every module over to `profile`. A layout that fails to parse
+    /// leaves the previous layout in place.
+    pub fn apply_profile(&mut self, profile: &Profile) -> Result<(), TemplateError> {
+        self.status.set_enabled(profile.status.enabled);
+        for i in 0..6 {
+            let line = profile.status.lines.get(i).cloned().unwrap_or_default();
+            self.status.set_line(i, line);
+        }
+        self.status
+            .set_rotation_interval(Duration::from_secs(profile.status.rotation_secs));
+        self.time.set_enabled(profile.time.enabled);
+        self.time.set_format(profile.time.format.clone());
+        self.stats.set_enabled(profile.stats.enabled);
+        self.stats.set_metrics(
+            profile
+                .stats
+                .metrics
+                .iter()
+                .filter_map(|spec| Metric::parse(spec).ok())
+                .collect(),
+        );
+        self.stats.set_label_style(
+            LabelStyle::parse(&profile.stats.label_style).unwrap_or(LabelStyle::Upper),
+        );
+        self.network.set_enabled(profile.network.enabled);
+        self.network.set_units(
+            RateUnit::parse(&profile.network.unit).unwrap_or(RateUnit::Bytes),
+            UnitPrefix::parse(&profile.network.prefix).unwrap_or(UnitPrefix::Si),
+        );
+        self.network.set_smoothing(profile.network.smoothing);
+        self.network.set_interfaces(
+            profile.network.interfaces.clone(),
+            profile.network.exclude.clone(),
+        );
+        self.gpu.set_enabled(profile.gpu.enabled);
+        self.gpu
+            .set_backend(GpuBackend::parse(&profile.gpu.backend).unwrap_or(GpuBackend::Auto));
+        let sysfs_root = profile.gpu.sysfs_root.trim();
+        self.gpu
+            .set_sysfs_root(PathBuf::from(if sysfs_root.is_empty() {
+                hwmon::DEFAULT_SYSFS_ROOT
+            } else {
+                sysfs_root
+            }));
+        self.gpu.set_card(profile.gpu.card.trim().to_string());
+        self.sensors.set_enabled(profile.sensors.enabled);
+        let sysfs_root = profile.sensors.sysfs_root.trim();
+        self.sensors
+            .set_sysfs_root(PathBuf::from(if sysfs_root.is_empty() {
+                hwmon::DEFAULT_SYSFS_ROOT
+            } else {
+                sysfs_root
+            }));
+        self.sensors.set_labels(profile.sensors.labels.clone());
+        self.sensors.set_show_fans(profile.sensors.fans);
+        self.sensors.set_fahrenheit(profile.sensors.fahrenheit);
+        self.sensors.set_warn_above(profile.sensors.warn_above);
+        self.media
+            .set_bus_address(profile.media.bus_address.clone());
+        self.media
+            .set_preferred_players(profile.media.players.clone());
+        self.media.set_blocklist(profile.media.blocklist.clone());
+        self.media.set_player_templates(
+            profile
+                .media
+                .templates
+                .iter()
+                .filter_map(|t| Some((t.player.clone(), Template::parse(&t.template).ok()?)))
+                .collect(),
+        );
+        let media = &profile.media;
+        let template = if media.template.trim().is_empty() {
+            media::DEFAULT_TEMPLATE
+        } else {
+            &media.template
+        };
+        if let Ok(template) = Template::parse(template) {
+            self.media.set_template(template);
+        }
+        self.media.set_bar_style(BarStyle {
+            length: media.bar_length,
+            filled: media.bar_filled.clone(),
+            empty: media.bar_empty.clone(),
+        });
+        self.media
+            .set_max_lengths(media.title_max_len, media.artist_max_len);
+        self.media.set_enabled(profile.media.enabled);
+        let lyrics_dir = profile.lyrics.dir.trim();
+        self.lyrics.set_dir(if lyrics_dir.is_empty() {
+            config_dir().join(lyrics::LYRICS_DIR)
+        } else {
+            PathBuf::from(lyrics_dir)
+        });
+        self.lyrics.set_enabled(profile.lyrics.enabled);
+        self.afk.set_enabled(profile.afk.enabled);
+        self.afk.set_timeout_secs(profile.afk.timeout_secs);
+        self.afk.set_afk_text(profile.afk.text.clone());
+        self.set_afk_sources(
+            profile.afk.source_osc,
+            profile.afk.source_chat,
+            profile.afk.source_idle,
+        );
+        self.system_details.set_sections(
+            profile
+                .system_details
+                .sections
+                .iter()
+                .filter_map(|spec| Section::parse(spec).ok())
+                .collect(),
+        );
+        self.system_details.set_style(
+            TextStyle::parse(&profile.system_details.style).unwrap_or(TextStyle::Superscript),
+        );
+        self.system_details
+            .set_lowercase(profile.system_details.lowercase);
+        self.system_details
+            .set_rotation_interval(Duration::from_secs(profile.system_details.rotation_secs));
+        let sysfs_root = profile.system_details.sysfs_root.trim();
+        self.system_details
+            .set_sysfs_root(PathBuf::from(if sysfs_root.is_empty() {
+                hwmon::DEFAULT_SYSFS_ROOT
+            } else {
+                sysfs_root
+            }));
+        let pci_ids = profile.system_details.pci_ids.trim();
+        self.system_details.set_pci_ids(if pci_ids.is_empty() {
+            system_details::PCI_IDS_PATHS
+                .iter()
+                .map(PathBuf::from)
+                .collect()
+        } else {
+            vec![PathBuf::from(pci_ids)]
+        });
+        self.system_details
+            .set_enabled(profile.system_details.enabled);
+        self.heartrate.set_url(profile.heartrate.url.clone());
+        self.heartrate.set_token(profile.heartrate.token.clone());
+        self.heartrate.set_enabled(profile.heartrate.enabled);
+        self.set_events(profile);
+        self.set_chat_page_indicator(profile.chat.page_indicator);
+        for (id, budget) in DEFAULT_BUDGETS {
+            let budget = profile
+                .budgets
+                .get(*id)
+                .map_or(*budget, |overrides| overrides.apply(*budget));
+            self.set_module_budget(id, budget);
+        }
+
+        let layout = if profile.layout.trim().is_empty() {
+            None
+        } else {
+            Some(Template::parse(&profile.layout)?)
+        };
+        self.set_layout(layout);
+        // Settings may change what modules output, so refresh them all now
+        self.cache.clear();
+        Ok(())
+    }
+
+    /// Sets which modules raise events and how long they are shown.
+    fn set_events(&mut self, profile: &Profile) {
+        let events = &profile.events;
+        self.events.set_timing(
+            Duration::from_secs(events.duration_secs),
+            Duration::from_secs(events.cooldown_secs),
+        );
+        if !events.enabled {
+            self.events.clear();
+        }
+
+        let now_playing = if events.now_playing.trim().is_empty() {
+            media::DEFAULT_ANNOUNCE_TEMPLATE
+        } else {
+            &events.now_playing
+        };
+        self.media.set_announce(
+            (events.enabled && events.media)
+                .then(|| Template::parse(now_playing).ok())
+                .flatten(),
+        );
+        self.afk.set_announce(events.enabled && events.afk);
+        let (above, below) = if events.enabled {
+            (profile.heartrate.alert_above, profile.heartrate.alert_below)
+        } else {
+            (0, 0)
+        };
+        self.heartrate.set_alerts(above, below);
+        self.sensors.set_alerts(events.enabled);
+    }
+
+    pub fn osc_receiver(&self) -> Option<&OscReceiver> {
+        self.osc_receiver.as_ref()
+    }
+
+    /// Selects which activity sources reset the AFK timer. Sources are only
+    /// (re)created when their selection changes.
+    pub fn set_afk_sources(&mut self, osc: bool, chat: bool, idle: bool) {
+        self.afk.set_chat_activity(chat);
+
+        if !osc {
+            self.afk.remove_source(ActivitySourceKind::Osc);
+        } else if !self.afk.has_source(ActivitySourceKind::Osc) {
+            if let Some(receiver) = &self.osc_receiver {
+                self.afk
+                    .add_source(Box::new(OscActivitySource::new(receiver)));
+            }
+        }
+
+        if !idle {
+            self.afk.remove_source(ActivitySourceKind::Idle);
+        } else if !self.afk.has_source(ActivitySourceKind::Idle) {
+            match IdleActivitySource::new() {
+                Ok(source) => self.afk.add_source(Box::new(source)),
+                Err(e) => warn!("logind idle source unavailable: {e}"),
+            }
+        }
+    }
+
+    /// Sets the layout template. `None` stacks every module's output on its
+    /// own line in the default order.
+    pub fn set_layout(&mut self, layout: Option<Template>) {
+        self.layout = layout;
+    }
+
+    pub fn set_module_budget(&mut self, id: &str, budget: ModuleBudget) {
+        self.budgets.insert(id.to_string(), budget);
+    }
+
     pub fn set_chat_message(&mut self, text: String) {
-        self.pending_chat_message = Some((text, Instant::now()));
+        self.afk.record_chat();
+        self.chat.push(text);
+    }
+
+    pub fn set_chat_page_indicator(&mut self, show: bool) {
+        self.chat.set_show_indicator(show);
+    }
+
+    /// Prints messages to stdout instead of sending them over OSC.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    pub fn send_typing(&mut self, typing: bool) -> Result<(), Error> {
+        self.osc
+            .send_typing_indicator(typing)
+            .inspect_err(|e| self.send_status.record_error(e))
     }
 
-    pub fn send_typing(&self, typing: bool) -> Result<(), Box<dyn std::error::Error>> {
-        self.osc.send_typing_indicator(typing)
+    pub fn send_status(&self) -> &SendStatus {
+        &self.send_status
     }
 
-    pub fn tick(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        // Check if the pending chat message has expired
-        if let Some((_, sent_at)) = &self.pending_chat_message {
-            if sent_at.elapsed() >= CHAT_MESSAGE_DURATION {
-                self.pending_chat_message = None;
+    /// Composes the next message and sends it if it should go out. Returns
+    /// the message that was sent.
+    pub fn tick(&mut self) -> Result<Option<String>, Error> {
+        match self.compose() {
+            Some(msg) if self.send(&msg)? => Ok(Some(msg.text)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Sends `msg` unless it is the same as the last message and no
+    /// keep-alive is due, or VRChat's rate limit would drop it. A message
+    /// with a sound always goes out as soon as the rate limit allows.
+    /// Returns whether it was sent.
+    pub fn send(&mut self, msg: &Composed) -> Result<bool, Error> {
+        if !self.throttle.admit(&msg.text, msg.play_sound) {
+            trace!("message unchanged or rate limited");
+            return Ok(false);
+        }
+        debug!(
+            sound = msg.play_sound,
+            "sending {:?}",
+            if msg.chat && self.redact_chat {
+                redact_chat(&msg.text)
+            } else {
+                msg.text.clone()
             }
+        );
+
+        if self.dry_run {
+            println!("{}\n", msg.text);
+        } else if let Err(e) = self
+            .osc
+            .send_chatbox_message(&msg.text, true, msg.play_sound)
+        {
+            self.throttle.forget();
+            self.send_status.record_error(&e);
+            return Err(e);
+        }
+        if msg.play_sound {
+            self.chat_sound = false;
+        }
+        self.send_status.sent = Some(msg.text.clone());
+        self.send_status.sent_at = Some(Local::now());
+        Ok(true)
+    }
+
+    /// Switches profiles when the rules settle on a different one. Uses what
+    /// the modules saw on the previous tick.
+    fn update_rules(&mut self) {
+        if self.rules.is_empty() {
+            return;
+        }
+
+        let context = Context {
+            time: chrono::Local::now().time(),
+            media_playing: self.media.is_playing(),
+            afk: self.afk.is_afk(),
+            avatar_id: self
+                .osc_receiver
+                .as_ref()
+                .and_then(|receiver| receiver.state().avatar_id),
+            processes: if self.rules.needs_processes() {
+                self.rules.processes()
+            } else {
+                Vec::new()
+            },
+        };
+        let Some(target) = self.rules.evaluate(&context) else {
+            return;
+        };
+
+        match &target {
+            Some(name) => info!("rules switched to profile '{name}'"),
+            None => info!("rules switched back to the base settings"),
+        }
+        let profile = target
+            .and_then(|name| self.rule_profiles.get(&name))
+            .unwrap_or(&self.base_profile)
+            .clone();
+        if let Err(e) = self.apply_profile(&profile) {
+            let e = Error::from(e);
+            error!("{e}");
+            self.send_status.record_error(&e);
+        }
+    }
+
+    /// Evaluates the rules, ticks every module and builds the next message
+    /// without sending it.
+    pub fn compose(&mut self) -> Option<Composed> {
+        self.update_rules();
+        // Lyrics follow the track from the previous tick; its position is
+        // advanced to now
+        self.lyrics.set_track(self.media.current().cloned());
+        let chat_page = self.chat.current();
+        if self.chat.take_started() {
+            self.chat_sound = true;
+        } else if chat_page.is_none() {
+            self.chat_sound = false;
         }
 
         let modules: Vec<&mut dyn Module> = vec![
@@ -63,26 +595,345 @@ impl Orchestrator {
             &mut self.time,
             &mut self.stats,
             &mut self.network,
+            &mut self.gpu,
+            &mut self.sensors,
             &mut self.media,
+            &mut self.lyrics,
             &mut self.heartrate,
             &mut self.system_details,
             &mut self.afk,
         ];
 
-        let parts: Vec<String> = modules.into_iter().filter_map(|m| m.tick()).collect();
-        let module_line = parts.join("\n");
+        let mut entries: Vec<Entry> = Vec::with_capacity(modules.len());
+        let mut ticked: Vec<&dyn Module> = Vec::with_capacity(modules.len());
+        for module in modules {
+            let budget = self
+                .budgets
+                .get(module.id())
+                .copied()
+                .unwrap_or(FALLBACK_BUDGET);
+            let text = refresh(&mut self.cache, module).unwrap_or_else(|e| {
+                error!("{e}");
+                self.send_status.record_error(&e);
+                None
+            });
+            entries.push(Entry::new(budget, text));
+            for event in module.take_events() {
+                self.events.push(event);
+            }
+            ticked.push(module);
+        }
+
+        // The chat message is never shortened; modules share what is left
+        let limits = Limits {
+            chars: MESSAGE_MAX_CHARS,
+            lines: CHATBOX_MAX_LINES,
+        };
+        let module_limits = match &chat_page {
+            Some(page) => limits.after(page),
+            None => limits,
+        };
+        // An event takes the place of every module until it is over
+        let module_line = match self.events.current() {
+            Some(event) => fit_event(event, module_limits),
+            None => {
+                let layout = self.layout.as_ref();
+                budget::fit(&mut entries, module_limits, |entries| {
+                    compose(layout, &ticked, entries)
+                })
+            }
+        };
 
         // Build the final message: chat message on top, module output below
-        let play_sound = self.pending_chat_message.is_some();
-        let combined = match &self.pending_chat_message {
-            Some((msg, _)) if !module_line.is_empty() => {
-                format!("{msg}\n{module_line}")
+        // Only the first send of a chat message plays the sound
+        let chat = chat_page.is_some();
+        let play_sound = chat && self.chat_sound;
+        let combined = match chat_page {
+            Some(page) if !module_line.is_empty() => {
+                format!("{page}\n{module_line}")
             }
-            Some((msg, _)) => msg.clone(),
+            Some(page) => page,
             None if !module_line.is_empty() => module_line,
-            None => return Ok(()),
+            None => {
+                self.send_status.composed = None;
+                return None;
+            }
         };
+        self.send_status.composed = Some(combined.clone());
+
+        Some(Composed {
+            text: combined,
+            play_sound,
+            chat,
+        })
+    }
+}
+
+impl SendStatus {
+    fn record_error(&mut self, e: &Error) {
+        self.error_count += 1;
+        self.last_error = Some(e.to_string());
+    }
+}
+
+/// Ticks `module` if its cached output is stale and returns the output. A
+/// module that panics is left out until `MODULE_RETRY` has passed, rather
+/// than taking the whole tick down.
+fn refresh(
+    cache: &mut HashMap<String, CachedOutput>,
+    module: &mut dyn Module,
+) -> Result<Option<String>, Error> {
+    if !module.enabled() {
+        cache.remove(module.id());
+        return Ok(None);
+    }
+
+    if let Some(cached) = cache.get(module.id()) {
+        if cached.refreshed_at.elapsed() < cached.fresh_for {
+            return Ok(cached.text.clone());
+        }
+    }
+
+    let ticked = panic::catch_unwind(AssertUnwindSafe(|| module.tick()));
+    // Rotating output has to move on when the next item is due
+    let interval = match module.rotation_interval() {
+        Some(rotation) => module.refresh_interval().min(rotation),
+        None => module.refresh_interval(),
+    };
+    let (text, fresh_for) = match &ticked {
+        Ok(text) => (text.clone(), interval),
+        Err(_) => (None, MODULE_RETRY.max(module.refresh_interval())),
+    };
+    cache.insert(
+        module.id().to_string(),
+        CachedOutput {
+            text: text.clone(),
+            refreshed_at: Instant::now(),
+            fresh_for,
+        },
+    );
+
+    ticked.map_err(|payload| Error::Module {
+        id: module.id().to_string(),
+        message: panic_message(payload.as_ref()),
+    })
+}
+
+/// Replaces the chat line at the top of a message.
+fn redact_chat(text: &str) -> String {
+    match text.split_once('\n') {
+        Some((_, rest)) => format!("[chat]\n{rest}"),
+        None => "[chat]".to_string(),
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "panicked".to_string()
+    }
+}
+
+fn fit_event(text: &str, limits: Limits) -> String {
+    let text = text
+        .lines()
+        .take(limits.lines)
+        .collect::<Vec<_>>()
+        .join("\n");
+    budget::abbreviate(&text, limits.chars).unwrap_or_default()
+}
+
+/// Lays out module output, either through the user's template or stacked one
+/// module per line.
+fn compose(layout: Option<&Template>, modules: &[&dyn Module], entries: &[Entry]) -> String {
+    match layout {
+        Some(layout) => layout.render(|key| {
+            let (id, field) = match key.split_once('.') {
+                Some((id, field)) => (id, Some(field)),
+                None => (key, None),
+            };
+            let index = modules.iter().position(|m| m.id() == id)?;
+            let output = entries[index].text.as_ref()?;
+            match field {
+                Some(field) => {
+                    let value = modules[index].field(field)?;
+                    budget::shrink(&value, entries[index].shrunk_by)
+                }
+                None => Some(output.clone()),
+            }
+        }),
+        None => entries
+            .iter()
+            .filter_map(|entry| entry.text.as_deref())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Media output with the title also available as a field.
+    struct Track;
+
+    impl Module for Track {
+        fn id(&self) -> &str {
+            "media"
+        }
+
+        fn name(&self) -> &str {
+            "Track"
+        }
+
+        fn enabled(&self) -> bool {
+            true
+        }
+
+        fn set_enabled(&mut self, _enabled: bool) {}
+
+        fn tick(&mut self) -> Option<String> {
+            Some("Some Artist - A rather long song title".to_string())
+        }
+
+        fn field(&self, name: &str) -> Option<String> {
+            (name == "title").then(|| "A rather long song title".to_string())
+        }
+    }
+
+    /// Output that changes every rotation but is otherwise slow to refresh.
+    struct Lines;
+
+    impl Module for Lines {
+        fn id(&self) -> &str {
+            "status"
+        }
+
+        fn name(&self) -> &str {
+            "Lines"
+        }
+
+        fn enabled(&self) -> bool {
+            true
+        }
+
+        fn set_enabled(&mut self, _enabled: bool) {}
+
+        fn tick(&mut self) -> Option<String> {
+            Some("line".to_string())
+        }
+
+        fn refresh_interval(&self) -> Duration {
+            Duration::from_secs(60)
+        }
+
+        fn rotation_interval(&self) -> Option<Duration> {
+            Some(Duration::from_secs(2))
+        }
+    }
+
+    #[test]
+    fn caches_rotating_output_until_the_next_item() {
+        let mut cache = HashMap::new();
+        assert_eq!(
+            refresh(&mut cache, &mut Lines).unwrap().as_deref(),
+            Some("line")
+        );
+        assert_eq!(cache["status"].fresh_for, Duration::from_secs(2));
+
+        refresh(&mut cache, &mut Track).unwrap();
+        assert_eq!(cache["media"].fresh_for, Duration::ZERO);
+    }
+
+    #[test]
+    fn applies_rotation_intervals() {
+        let mut orch = Orchestrator::new("127.0.0.1:9000", "").unwrap();
+        let mut config = Config::default();
+        config.profile.status.rotation_secs = 15;
+        config.profile.system_details.rotation_secs = 3;
+        orch.apply_config(&config).unwrap();
+        assert_eq!(
+            orch.status.rotation_interval(),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            orch.system_details.rotation_interval(),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(orch.time.rotation_interval(), None);
+    }
+
+    #[test]
+    fn fit_shortens_layout_fields() {
+        let layout = Template::parse("\u{266A} {media.title}").unwrap();
+        let mut track = Track;
+        let mut entries = vec![Entry::new(ModuleBudget::new(50, 4, 80), track.tick())];
+        let limits = Limits {
+            chars: 20,
+            lines: 9,
+        };
+        let modules: [&dyn Module; 1] = [&track];
+        let text = budget::fit(&mut entries, limits, |entries| {
+            compose(Some(&layout), &modules, entries)
+        });
+        assert_eq!(text, "\u{266A} A rather\u{2026}");
+    }
+
+    #[test]
+    fn max_len_shortens_layout_fields() {
+        let layout = Template::parse("\u{266A} {media.title}").unwrap();
+        let mut track = Track;
+        // The field is shortened as much as the whole output
+        let mut entries = vec![Entry::new(ModuleBudget::new(50, 4, 18), track.tick())];
+        let limits = Limits {
+            chars: 144,
+            lines: 9,
+        };
+        let modules: [&dyn Module; 1] = [&track];
+        let text = budget::fit(&mut entries, limits, |entries| {
+            compose(Some(&layout), &modules, entries)
+        });
+        assert_eq!(entries[0].shrunk_by, 22);
+        assert_eq!(text, "\u{266A} A\u{2026}");
+    }
+
+    #[test]
+    fn plays_the_sound_on_the_first_send_of_a_chat_message() {
+        let mut orch = Orchestrator::new("127.0.0.1:9000", "").unwrap();
+        orch.set_dry_run(true);
+        // Use up the rate limit so the first send is refused
+        while orch.throttle.admit("status", true) {}
+        orch.set_chat_message("hello".to_string());
+
+        let msg = orch.compose().unwrap();
+        assert!(msg.chat && msg.play_sound);
+        assert!(!orch.send(&msg).unwrap());
+
+        // The sound is kept until the message goes out
+        orch.throttle = SendThrottle::new();
+        let msg = orch.compose().unwrap();
+        assert!(msg.play_sound);
+        assert!(orch.send(&msg).unwrap());
+
+        let msg = orch.compose().unwrap();
+        assert!(msg.chat && !msg.play_sound);
+    }
 
-        self.osc.send_chatbox_message(&combined, true, play_sound)
+    #[test]
+    fn records_layout_errors() {
+        let mut orch = Orchestrator::new("127.0.0.1:9000", "").unwrap();
+        let mut config = Config::default();
+        config.profile.layout = "{#media}".to_string();
+        assert!(orch.apply_config(&config).is_err());
+        assert_eq!(orch.send_status().error_count, 1);
+        assert!(orch
+            .send_status()
+            .last_error
+            .as_ref()
+            .unwrap()
+            .starts_with("layout: "));
     }
 }
@@ -1,6 +1,18 @@
+pub mod receiver;
+pub mod throttle;
+
+use crate::budget;
+use crate::error::Error;
 use rosc::encoder;
 use rosc::{OscMessage, OscPacket, OscType};
 use std::net::UdpSocket;
+use tracing::info;
+
+pub const CHATBOX_MAX_CHARS: usize = 144;
+pub const CHATBOX_MAX_LINES: usize = 9;
+const MESSAGE_SUFFIX: &str = "\u{0003}\u{001F}";
+/// Room left for text once the suffix appended to every message is counted.
+pub const MESSAGE_MAX_CHARS: usize = CHATBOX_MAX_CHARS - MESSAGE_SUFFIX.len();
 
 pub struct OscClient {
     socket: UdpSocket,
@@ -8,8 +20,13 @@ pub struct OscClient {
 }
 
 impl OscClient {
-    pub fn new(target_addr: &str) -> Result<Self, std::io::Error> {
-        let socket = UdpSocket::bind("0.0.0.0:0")?;
+    pub fn new(target_addr: &str) -> Result<Self, Error> {
+        let addr = "0.0.0.0:0";
+        let socket = UdpSocket::bind(addr).map_err(|source| Error::Bind {
+            addr: addr.to_string(),
+            source,
+        })?;
+        info!("sending OSC to {target_addr}");
         Ok(Self {
             socket,
             target: target_addr.to_string(),
@@ -21,9 +38,11 @@ impl OscClient {
         text: &str,
         send_immediately: bool,
         play_sound: bool,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut truncated: String = text.chars().take(142).collect();
-        truncated.push_str("\u{0003}\u{001F}");
+    ) -> Result<(), Error> {
+        // The orchestrator already budgets its output; this only guards the limit
+        let lines: Vec<&str> = text.lines().take(CHATBOX_MAX_LINES).collect();
+        let mut truncated = budget::truncate(&lines.join("\n"), MESSAGE_MAX_CHARS).to_string();
+        truncated.push_str(MESSAGE_SUFFIX);
 
         let msg = OscPacket::Message(OscMessage {
             addr: "/chatbox/input".to_string(),
@@ -33,20 +52,25 @@ impl OscClient {
                 OscType::Bool(play_sound),
             ],
         });
-
-        let buf = encoder::encode(&msg)?;
-        self.socket.send_to(&buf, &self.target)?;
-        Ok(())
+        self.send(&msg)
     }
 
-    pub fn send_typing_indicator(&self, is_typing: bool) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn send_typing_indicator(&self, is_typing: bool) -> Result<(), Error> {
         let msg = OscPacket::Message(OscMessage {
             addr: "/chatbox/typing".to_string(),
             args: vec![OscType::Bool(is_typing)],
         });
+        self.send(&msg)
+    }
 
-        let buf = encoder::encode(&msg)?;
-        self.socket.send_to(&buf, &self.target)?;
+    fn send(&self, packet: &OscPacket) -> Result<(), Error> {
+        let buf = encoder::encode(packet)?;
+        self.socket
+            .send_to(&buf, &self.target)
+            .map_err(|source| Error::Send {
+                target: self.target.clone(),
+                source,
+            })?;
         Ok(())
     }
 }
@@ -0,0 +1,302 @@
+use rosc::{decoder, OscMessage, OscPacket, OscType};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tracing::{info, warn};
+
+pub const DEFAULT_LISTEN_ADDR: &str = "127.0.0.1:9001";
+const PARAMETER_PREFIX: &str = "/avatar/parameters/";
+const AVATAR_CHANGE: &str = "/avatar/change";
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone)]
+pub enum OscEvent {
+    AvatarParameter { name: String, value: OscType },
+    AvatarChange(String),
+    Message(OscMessage),
+}
+
+/// Selects which incoming events a subscriber receives.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Subscription {
+    /// A single avatar parameter by name, e.g. `AFK` or `MuteSelf`.
+    Parameter(String),
+    AllParameters,
+    AvatarChange,
+    /// Any message whose address starts with the given prefix.
+    Address(String),
+    All,
+}
+
+impl Subscription {
+    fn matches(&self, addr: &str, event: &OscEvent) -> bool {
+        match (self, event) {
+            (Subscription::All, _) => true,
+            (Subscription::Address(prefix), _) => addr.starts_with(prefix.as_str()),
+            (Subscription::AllParameters, OscEvent::AvatarParameter { .. }) => true,
+            (Subscription::Parameter(want), OscEvent::AvatarParameter { name, .. }) => want == name,
+            (Subscription::AvatarChange, OscEvent::AvatarChange(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Last known in-game state, built up from the events seen so far.
+#[derive(Debug, Clone, Default)]
+pub struct AvatarState {
+    pub avatar_id: Option<String>,
+    pub parameters: HashMap<String, OscType>,
+}
+
+impl AvatarState {
+    pub fn bool_parameter(&self, name: &str) -> Option<bool> {
+        match self.parameters.get(name)? {
+            OscType::Bool(b) => Some(*b),
+            OscType::Int(i) => Some(*i != 0),
+            OscType::Float(f) => Some(*f >= 0.5),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Router {
+    subscribers: Vec<(Subscription, Sender<OscEvent>)>,
+    state: AvatarState,
+}
+
+impl Router {
+    fn dispatch_packet(&mut self, packet: OscPacket) {
+        match packet {
+            OscPacket::Message(msg) => self.dispatch(msg),
+            OscPacket::Bundle(bundle) => {
+                for packet in bundle.content {
+                    self.dispatch_packet(packet);
+                }
+            }
+        }
+    }
+
+    fn dispatch(&mut self, msg: OscMessage) {
+        let addr = msg.addr.clone();
+        let event = classify(msg);
+
+        match &event {
+            OscEvent::AvatarParameter { name, value } => {
+                self.state.parameters.insert(name.clone(), value.clone());
+            }
+            OscEvent::AvatarChange(id) => {
+                self.state.avatar_id = Some(id.clone());
+            }
+            OscEvent::Message(_) => {}
+        }
+
+        // Drop subscribers whose receiving end has gone away
+        self.subscribers
+            .retain(|(sub, tx)| !sub.matches(&addr, &event) || tx.send(event.clone()).is_ok());
+    }
+}
+
+fn classify(msg: OscMessage) -> OscEvent {
+    if let Some(name) = msg.addr.strip_prefix(PARAMETER_PREFIX) {
+        if let Some(value) = msg.args.first() {
+            return OscEvent::AvatarParameter {
+                name: name.to_string(),
+                value: value.clone(),
+            };
+        }
+    }
+
+    if msg.addr == AVATAR_CHANGE {
+        if let Some(OscType::String(id)) = msg.args.first() {
+            return OscEvent::AvatarChange(id.clone());
+        }
+    }
+
+    OscEvent::Message(msg)
+}
+
+/// Listens for OSC packets sent by VRChat (port 9001 by default) and routes
+/// them to subscribers on a background thread.
+pub struct OscReceiver {
+    local_addr: SocketAddr,
+    router: Arc<Mutex<Router>>,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl OscReceiver {
+    pub fn bind(listen_addr: &str) -> Result<Self, std::io::Error> {
+        let socket = UdpSocket::bind(listen_addr)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        let local_addr = socket.local_addr()?;
+        info!("listening for OSC on {local_addr}");
+
+        let router = Arc::new(Mutex::new(Router::default()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let router = router.clone();
+            let shutdown = shutdown.clone();
+            thread::Builder::new()
+                .name("osc-receiver".to_string())
+                .spawn(move || listen(socket, router, shutdown))?
+        };
+
+        Ok(Self {
+            local_addr,
+            router,
+            shutdown,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn subscribe(&self, subscription: Subscription) -> Receiver<OscEvent> {
+        let (tx, rx) = mpsc::channel();
+        if let Ok(mut router) = self.router.lock() {
+            router.subscribers.push((subscription, tx));
+        }
+        rx
+    }
+
+    pub fn state(&self) -> AvatarState {
+        self.router
+            .lock()
+            .map(|router| router.state.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for OscReceiver {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn listen(socket: UdpSocket, router: Arc<Mutex<Router>>, shutdown: Arc<AtomicBool>) {
+    let mut buf = [0u8; decoder::MTU];
+
+    while !shutdown.load(Ordering::Relaxed) {
+        let len = match socket.recv_from(&mut buf) {
+            Ok((len, _)) => len,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted
+                ) =>
+            {
+                continue
+            }
+            Err(e) => {
+                warn!("OSC receive failed: {e}");
+                continue;
+            }
+        };
+
+        match decoder::decode_udp(&buf[..len]) {
+            Ok((_, packet)) => {
+                if let Ok(mut router) = router.lock() {
+                    router.dispatch_packet(packet);
+                }
+            }
+            Err(e) => warn!("can't decode OSC packet: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rosc::{encoder, OscBundle, OscTime};
+    use std::sync::mpsc::RecvTimeoutError;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn message(addr: &str, args: Vec<OscType>) -> OscPacket {
+        OscPacket::Message(OscMessage {
+            addr: addr.to_string(),
+            args,
+        })
+    }
+
+    fn send(receiver: &OscReceiver, packet: &OscPacket) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let bytes = encoder::encode(packet).unwrap();
+        socket.send_to(&bytes, receiver.local_addr()).unwrap();
+    }
+
+    #[test]
+    fn routes_packets_to_subscribers() {
+        let receiver = OscReceiver::bind("127.0.0.1:0").unwrap();
+        let afk = receiver.subscribe(Subscription::Parameter("AFK".to_string()));
+        let parameters = receiver.subscribe(Subscription::AllParameters);
+        let avatars = receiver.subscribe(Subscription::AvatarChange);
+        let chatbox = receiver.subscribe(Subscription::Address("/chatbox/".to_string()));
+
+        send(
+            &receiver,
+            &message("/avatar/parameters/MuteSelf", vec![OscType::Bool(true)]),
+        );
+        send(
+            &receiver,
+            &OscPacket::Bundle(OscBundle {
+                timetag: OscTime::from((0, 1)),
+                content: vec![
+                    message("/avatar/parameters/AFK", vec![OscType::Int(1)]),
+                    message(
+                        "/avatar/change",
+                        vec![OscType::String("avtr_1".to_string())],
+                    ),
+                    message("/chatbox/typing", vec![OscType::Bool(false)]),
+                ],
+            }),
+        );
+
+        match afk.recv_timeout(TIMEOUT).unwrap() {
+            OscEvent::AvatarParameter { name, value } => {
+                assert_eq!(name, "AFK");
+                assert_eq!(value, OscType::Int(1));
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+        // Only the subscribed parameter, and nothing from the first packet
+        assert_eq!(
+            afk.recv_timeout(Duration::from_millis(100)).unwrap_err(),
+            RecvTimeoutError::Timeout
+        );
+
+        let names: Vec<String> = (0..2)
+            .map(|_| match parameters.recv_timeout(TIMEOUT).unwrap() {
+                OscEvent::AvatarParameter { name, .. } => name,
+                other => panic!("unexpected event {other:?}"),
+            })
+            .collect();
+        assert_eq!(names, ["MuteSelf", "AFK"]);
+
+        match avatars.recv_timeout(TIMEOUT).unwrap() {
+            OscEvent::AvatarChange(id) => assert_eq!(id, "avtr_1"),
+            other => panic!("unexpected event {other:?}"),
+        }
+        match chatbox.recv_timeout(TIMEOUT).unwrap() {
+            OscEvent::Message(msg) => assert_eq!(msg.addr, "/chatbox/typing"),
+            other => panic!("unexpected event {other:?}"),
+        }
+
+        let state = receiver.state();
+        assert_eq!(state.avatar_id.as_deref(), Some("avtr_1"));
+        assert_eq!(state.bool_parameter("AFK"), Some(true));
+        assert_eq!(state.bool_parameter("MuteSelf"), Some(true));
+    }
+}
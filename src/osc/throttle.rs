@@ -0,0 +1,165 @@
+//! Keeps chatbox sends within what VRChat accepts. Messages sent faster
+//! than its throttle allows are silently dropped, which shows up as the
+//! chatbox lagging behind or skipping updates.
+
+use std::time::{Duration, Instant};
+
+/// Sends allowed back to back before the refill rate applies.
+const BURST: u32 = 3;
+/// VRChat accepts about one chatbox message per this period on average.
+const REFILL: Duration = Duration::from_millis(1500);
+/// An unchanged message is sent again after this long, so the chatbox
+/// doesn't time out and disappear.
+pub const KEEP_ALIVE: Duration = Duration::from_secs(20);
+
+/// Holds up to `capacity` tokens and regains one every `refill`.
+pub struct TokenBucket {
+    capacity: f64,
+    refill: Duration,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: u32, refill: Duration) -> Self {
+        Self {
+            capacity: f64::from(capacity),
+            refill,
+            tokens: f64::from(capacity),
+            updated: Instant::now(),
+        }
+    }
+
+    /// Takes a token if one is available.
+    pub fn try_take(&mut self) -> bool {
+        let now = Instant::now();
+        if !self.refill.is_zero() {
+            let regained =
+                now.duration_since(self.updated).as_secs_f64() / self.refill.as_secs_f64();
+            self.tokens = (self.tokens + regained).min(self.capacity);
+        } else {
+            self.tokens = self.capacity;
+        }
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Decides which messages go out: only ones that changed or are due for a
+/// keep-alive, and only while the rate limit allows.
+pub struct SendThrottle {
+    bucket: TokenBucket,
+    keep_alive: Duration,
+    last: Option<(String, Instant)>,
+}
+
+impl Default for SendThrottle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SendThrottle {
+    pub fn new() -> Self {
+        Self {
+            bucket: TokenBucket::new(BURST, REFILL),
+            keep_alive: KEEP_ALIVE,
+            last: None,
+        }
+    }
+
+    /// Whether `text` should be sent now, recording it as sent if so.
+    /// `force` skips the check for an unchanged message, but not the rate
+    /// limit.
+    pub fn admit(&mut self, text: &str, force: bool) -> bool {
+        let unchanged = self
+            .last
+            .as_ref()
+            .is_some_and(|(last, at)| last == text && at.elapsed() < self.keep_alive);
+        if unchanged && !force {
+            return false;
+        }
+        if !self.bucket.try_take() {
+            return false;
+        }
+        self.last = Some((text.to_string(), Instant::now()));
+        true
+    }
+
+    /// Forgets the last message, e.g. after sending it failed, so it is
+    /// sent again on the next chance.
+    pub fn forget(&mut self) {
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Moves the bucket's last refill `by` into the past.
+    fn wait(throttle: &mut SendThrottle, by: Duration) {
+        throttle.bucket.updated -= by;
+    }
+
+    /// Moves when the last message was sent `by` into the past.
+    fn age(throttle: &mut SendThrottle, by: Duration) {
+        if let Some((_, at)) = throttle.last.as_mut() {
+            *at -= by;
+        }
+    }
+
+    #[test]
+    fn allows_a_burst_then_refills() {
+        let mut bucket = TokenBucket::new(BURST, REFILL);
+        assert!((0..BURST).all(|_| bucket.try_take()));
+        assert!(!bucket.try_take());
+
+        bucket.updated -= REFILL / 2;
+        assert!(!bucket.try_take());
+        bucket.updated -= REFILL / 2;
+        assert!(bucket.try_take());
+        assert!(!bucket.try_take());
+    }
+
+    #[test]
+    fn skips_unchanged_messages_until_the_keep_alive() {
+        let mut throttle = SendThrottle::new();
+        assert!(throttle.admit("hello", false));
+        assert!(!throttle.admit("hello", false));
+        assert!(throttle.admit("hello there", false));
+
+        wait(&mut throttle, REFILL);
+        age(&mut throttle, KEEP_ALIVE - Duration::from_secs(1));
+        assert!(!throttle.admit("hello there", false));
+        age(&mut throttle, Duration::from_secs(1));
+        assert!(throttle.admit("hello there", false));
+    }
+
+    #[test]
+    fn force_sends_unchanged_messages_within_the_rate_limit() {
+        let mut throttle = SendThrottle::new();
+        assert!(throttle.admit("hello", false));
+        assert!(throttle.admit("hello", true));
+        assert!(throttle.admit("hello", true));
+        // The burst is used up, forcing doesn't get past it
+        assert!(!throttle.admit("hello", true));
+        wait(&mut throttle, REFILL);
+        assert!(throttle.admit("hello", true));
+    }
+
+    #[test]
+    fn forget_sends_the_last_message_again() {
+        let mut throttle = SendThrottle::new();
+        assert!(throttle.admit("hello", false));
+        throttle.forget();
+        assert!(throttle.admit("hello", false));
+        assert!(!throttle.admit("hello", false));
+    }
+}
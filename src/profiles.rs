@@ -0,0 +1,152 @@
+//! Named profiles stored as one TOML file each under
+//! `$XDG_CONFIG_HOME/osc-chatbox/profiles/`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, config_dir, ConfigError, Profile};
+
+const PROFILE_DIR: &str = "profiles";
+const PROFILE_EXT: &str = "toml";
+
+pub struct ProfileStore {
+    dir: PathBuf,
+}
+
+impl Default for ProfileStore {
+    fn default() -> Self {
+        Self::new(config_dir().join(PROFILE_DIR))
+    }
+}
+
+impl ProfileStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Names of every stored profile, sorted.
+    pub fn list(&self) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+
+        let mut names: Vec<String> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == PROFILE_EXT))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+            .filter(|name| validate_name(name).is_ok())
+            .collect();
+        names.sort_by_key(|name| name.to_lowercase());
+        names
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        validate_name(name).is_ok() && self.path(name).exists()
+    }
+
+    pub fn load(&self, name: &str) -> Result<Profile, ConfigError> {
+        check_name(name)?;
+        read_profile(&self.path(name))
+    }
+
+    pub fn save(&self, name: &str, profile: &Profile) -> Result<(), ConfigError> {
+        check_name(name)?;
+        write_profile(&self.path(name), profile)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), ConfigError> {
+        check_name(name)?;
+        let path = self.path(name);
+        fs::remove_file(&path).map_err(|source| ConfigError::Io { path, source })
+    }
+
+    /// Copies a profile file into the store, named after the file. Returns
+    /// the name it was stored under.
+    pub fn import(&self, path: &Path) -> Result<String, ConfigError> {
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        check_name(&name)?;
+
+        let profile = read_profile(path)?;
+        self.save(&name, &profile)?;
+        Ok(name)
+    }
+
+    pub fn export(&self, name: &str, path: &Path) -> Result<(), ConfigError> {
+        let profile = self.load(name)?;
+        write_profile(path, &profile)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.{PROFILE_EXT}"))
+    }
+}
+
+/// Profile names double as file names, so they can't contain path
+/// separators or start with a dot.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("name can't be empty".to_string());
+    }
+    if name != name.trim() {
+        return Err("name can't start or end with spaces".to_string());
+    }
+    if name.starts_with('.') {
+        return Err("name can't start with '.'".to_string());
+    }
+    if name
+        .chars()
+        .any(|c| matches!(c, '/' | '\\') || c.is_control())
+    {
+        return Err("name can't contain slashes or control characters".to_string());
+    }
+    Ok(())
+}
+
+fn check_name(name: &str) -> Result<(), ConfigError> {
+    validate_name(name).map_err(|message| ConfigError::Invalid {
+        field: "profile",
+        message,
+    })
+}
+
+fn read_profile(path: &Path) -> Result<Profile, ConfigError> {
+    let text = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let parse_error = |message: String| ConfigError::Parse {
+        path: path.to_path_buf(),
+        message,
+    };
+    let mut value: toml::Table = toml::from_str(&text).map_err(|e| parse_error(e.to_string()))?;
+    config::migrate_profile(&mut value);
+    let profile: Profile = value
+        .try_into()
+        .map_err(|e: toml::de::Error| parse_error(e.to_string()))?;
+    profile.validate()?;
+    Ok(profile)
+}
+
+fn write_profile(path: &Path, profile: &Profile) -> Result<(), ConfigError> {
+    profile.validate()?;
+
+    let io_error = |source| ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let text =
+        toml::to_string_pretty(profile).map_err(|e| ConfigError::Serialize(e.to_string()))?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(io_error)?;
+    }
+    let tmp = path.with_extension("toml.tmp");
+    fs::write(&tmp, text).map_err(io_error)?;
+    fs::rename(&tmp, path).map_err(io_error)
+}
@@ -0,0 +1,385 @@
+//! Switching profiles automatically from what is happening right now.
+
+use std::time::{Duration, Instant};
+
+use chrono::NaiveTime;
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, RefreshKind, System};
+
+use crate::config::RuleConfig;
+
+/// Listing processes is comparatively slow, so it is done at most this often.
+const PROCESS_REFRESH: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// Local time within `start..end`, wrapping past midnight when `end`
+    /// comes first.
+    TimeOfDay {
+        start: NaiveTime,
+        end: NaiveTime,
+    },
+    MediaPlaying(bool),
+    Afk(bool),
+    Avatar(String),
+    Process(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub profile: String,
+    pub conditions: Vec<Condition>,
+}
+
+impl Rule {
+    pub fn from_config(config: &RuleConfig) -> Result<Self, String> {
+        let mut conditions = Vec::new();
+        if let Some(range) = &config.time {
+            let (start, end) = parse_time_range(range)?;
+            conditions.push(Condition::TimeOfDay { start, end });
+        }
+        if let Some(playing) = config.media_playing {
+            conditions.push(Condition::MediaPlaying(playing));
+        }
+        if let Some(afk) = config.afk {
+            conditions.push(Condition::Afk(afk));
+        }
+        if let Some(avatar) = &config.avatar {
+            conditions.push(Condition::Avatar(avatar.trim().to_string()));
+        }
+        if let Some(process) = &config.process {
+            conditions.push(Condition::Process(normalize_process(process)));
+        }
+
+        if conditions.is_empty() {
+            return Err(format!(
+                "rule for '{}' needs at least one condition",
+                config.profile
+            ));
+        }
+        Ok(Self {
+            profile: config.profile.clone(),
+            conditions,
+        })
+    }
+
+    fn matches(&self, context: &Context) -> bool {
+        self.conditions.iter().all(|c| match c {
+            Condition::TimeOfDay { start, end } if start <= end => {
+                (*start..*end).contains(&context.time)
+            }
+            Condition::TimeOfDay { start, end } => context.time >= *start || context.time < *end,
+            Condition::MediaPlaying(playing) => context.media_playing == *playing,
+            Condition::Afk(afk) => context.afk == *afk,
+            Condition::Avatar(id) => context.avatar_id.as_deref() == Some(id.as_str()),
+            Condition::Process(name) => context.processes.iter().any(|p| p == name),
+        })
+    }
+
+    fn needs_processes(&self) -> bool {
+        self.conditions
+            .iter()
+            .any(|c| matches!(c, Condition::Process(_)))
+    }
+}
+
+/// Parses `HH:MM-HH:MM`.
+fn parse_time_range(range: &str) -> Result<(NaiveTime, NaiveTime), String> {
+    let parse = |time: &str| {
+        NaiveTime::parse_from_str(time.trim(), "%H:%M")
+            .map_err(|_| format!("'{}' is not a time like 18:30", time.trim()))
+    };
+    let (start, end) = range
+        .split_once('-')
+        .ok_or_else(|| format!("'{range}' is not a range like 22:00-06:00"))?;
+    Ok((parse(start)?, parse(end)?))
+}
+
+/// Process names are compared case-insensitively and without `.exe`, so
+/// rules work the same for native and Proton processes.
+fn normalize_process(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    match name.strip_suffix(".exe") {
+        Some(stem) => stem.to_string(),
+        None => name,
+    }
+}
+
+/// What the rules are evaluated against.
+pub struct Context {
+    pub time: NaiveTime,
+    pub media_playing: bool,
+    pub afk: bool,
+    pub avatar_id: Option<String>,
+    /// Normalized names of running processes; empty unless a rule needs them.
+    pub processes: Vec<String>,
+}
+
+/// Evaluates rules and decides when to switch. A different outcome has to
+/// persist for the hold time before it takes effect, so conditions that
+/// flicker, like a track change or a brief AFK, don't make the chatbox flap.
+pub struct RuleEngine {
+    rules: Vec<Rule>,
+    hold: Duration,
+    current: Option<String>,
+    pending: Option<(Option<String>, Instant)>,
+    system: Option<System>,
+    processes: Vec<String>,
+    processes_at: Option<Instant>,
+}
+
+impl Default for RuleEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RuleEngine {
+    pub fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            hold: Duration::ZERO,
+            current: None,
+            pending: None,
+            system: None,
+            processes: Vec::new(),
+            processes_at: None,
+        }
+    }
+
+    /// Replaces the rules. The current profile is kept while some rule still
+    /// switches to it, so editing rules doesn't bounce back to the base.
+    pub fn set_rules(&mut self, rules: Vec<Rule>, hold: Duration) {
+        if let Some(current) = &self.current {
+            if !rules.iter().any(|r| &r.profile == current) {
+                self.current = None;
+            }
+        }
+        self.pending = None;
+        self.hold = hold;
+        self.rules = rules;
+        if !self.needs_processes() {
+            self.system = None;
+            self.processes.clear();
+            self.processes_at = None;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Profile chosen by the rules, or `None` for the base settings.
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    pub fn needs_processes(&self) -> bool {
+        self.rules.iter().any(Rule::needs_processes)
+    }
+
+    /// Names of running processes, refreshed at most every few seconds.
+    pub fn processes(&mut self) -> Vec<String> {
+        if self
+            .processes_at
+            .is_some_and(|at| at.elapsed() < PROCESS_REFRESH)
+        {
+            return self.processes.clone();
+        }
+
+        let system = self.system.get_or_insert_with(|| {
+            System::new_with_specifics(
+                RefreshKind::nothing().with_processes(ProcessRefreshKind::nothing()),
+            )
+        });
+        system.refresh_processes_specifics(
+            ProcessesToUpdate::All,
+            true,
+            ProcessRefreshKind::nothing(),
+        );
+        self.processes = system
+            .processes()
+            .values()
+            .map(|p| normalize_process(&p.name().to_string_lossy()))
+            .collect();
+        self.processes.sort();
+        self.processes.dedup();
+        self.processes_at = Some(Instant::now());
+        self.processes.clone()
+    }
+
+    /// Evaluates the rules and returns the new target when it changes: a
+    /// profile name, or `Some(None)` to return to the base settings.
+    pub fn evaluate(&mut self, context: &Context) -> Option<Option<String>> {
+        let target = self
+            .rules
+            .iter()
+            .find(|rule| rule.matches(context))
+            .map(|rule| rule.profile.clone());
+
+        if target == self.current {
+            self.pending = None;
+            return None;
+        }
+
+        let since = match &self.pending {
+            Some((pending, since)) if *pending == target => *since,
+            _ => {
+                let now = Instant::now();
+                self.pending = Some((target.clone(), now));
+                now
+            }
+        };
+        if since.elapsed() < self.hold {
+            return None;
+        }
+
+        self.pending = None;
+        self.current.clone_from(&target);
+        Some(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOLD: Duration = Duration::from_secs(10);
+
+    fn rule(profile: &str, config: RuleConfig) -> Rule {
+        Rule::from_config(&RuleConfig {
+            profile: profile.to_string(),
+            ..config
+        })
+        .unwrap()
+    }
+
+    fn context(time: &str) -> Context {
+        Context {
+            time: NaiveTime::parse_from_str(time, "%H:%M").unwrap(),
+            media_playing: false,
+            afk: false,
+            avatar_id: None,
+            processes: Vec::new(),
+        }
+    }
+
+    /// Moves the start of the pending change `by` into the past.
+    fn wait(engine: &mut RuleEngine, by: Duration) {
+        if let Some((_, since)) = engine.pending.as_mut() {
+            *since -= by;
+        }
+    }
+
+    fn night() -> Rule {
+        rule(
+            "night",
+            RuleConfig {
+                time: Some("22:00-06:00".to_string()),
+                ..RuleConfig::default()
+            },
+        )
+    }
+
+    #[test]
+    fn parses_time_ranges() {
+        let time = |s| NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+        assert_eq!(
+            parse_time_range(" 22:00 - 06:30 "),
+            Ok((time("22:00"), time("06:30")))
+        );
+        assert!(parse_time_range("22:00").is_err());
+        assert!(parse_time_range("22:00-25:00").is_err());
+        assert!(Rule::from_config(&RuleConfig::default()).is_err());
+    }
+
+    #[test]
+    fn time_ranges_wrap_past_midnight() {
+        let night = night();
+        assert!(night.matches(&context("23:30")));
+        assert!(night.matches(&context("00:00")));
+        assert!(night.matches(&context("05:59")));
+        assert!(!night.matches(&context("06:00")));
+        assert!(!night.matches(&context("12:00")));
+
+        let day = rule(
+            "day",
+            RuleConfig {
+                time: Some("09:00-17:00".to_string()),
+                ..RuleConfig::default()
+            },
+        );
+        assert!(day.matches(&context("09:00")));
+        assert!(!day.matches(&context("17:00")));
+        assert!(!day.matches(&context("23:30")));
+    }
+
+    #[test]
+    fn normalizes_process_names() {
+        assert_eq!(normalize_process(" VRChat.exe "), "vrchat");
+        assert_eq!(normalize_process("OBS"), "obs");
+        assert_eq!(normalize_process("notes.EXE"), "notes");
+
+        let streaming = rule(
+            "streaming",
+            RuleConfig {
+                process: Some("OBS.exe".to_string()),
+                ..RuleConfig::default()
+            },
+        );
+        assert!(streaming.needs_processes());
+        let mut running = context("12:00");
+        running.processes = vec!["obs".to_string()];
+        assert!(streaming.matches(&running));
+        assert!(!streaming.matches(&context("12:00")));
+    }
+
+    #[test]
+    fn waits_for_the_hold_time() {
+        let mut engine = RuleEngine::new();
+        engine.set_rules(vec![night()], HOLD);
+
+        assert_eq!(engine.evaluate(&context("23:00")), None);
+        wait(&mut engine, HOLD - Duration::from_secs(1));
+        assert_eq!(engine.evaluate(&context("23:00")), None);
+        wait(&mut engine, Duration::from_secs(1));
+        assert_eq!(
+            engine.evaluate(&context("23:00")),
+            Some(Some("night".to_string()))
+        );
+        assert_eq!(engine.current(), Some("night"));
+        assert_eq!(engine.evaluate(&context("23:00")), None);
+
+        // Flickering back restarts the hold
+        assert_eq!(engine.evaluate(&context("12:00")), None);
+        wait(&mut engine, HOLD - Duration::from_secs(1));
+        assert_eq!(engine.evaluate(&context("23:00")), None);
+        assert_eq!(engine.evaluate(&context("12:00")), None);
+        wait(&mut engine, HOLD - Duration::from_secs(1));
+        assert_eq!(engine.evaluate(&context("12:00")), None);
+        wait(&mut engine, Duration::from_secs(1));
+        assert_eq!(engine.evaluate(&context("12:00")), Some(None));
+        assert_eq!(engine.current(), None);
+    }
+
+    #[test]
+    fn set_rules_keeps_a_profile_still_in_use() {
+        let mut engine = RuleEngine::new();
+        engine.set_rules(vec![night()], Duration::ZERO);
+        engine.evaluate(&context("23:00"));
+        assert_eq!(engine.current(), Some("night"));
+
+        let away = rule(
+            "away",
+            RuleConfig {
+                afk: Some(true),
+                ..RuleConfig::default()
+            },
+        );
+        engine.set_rules(vec![away.clone(), night()], HOLD);
+        assert_eq!(engine.current(), Some("night"));
+
+        engine.set_rules(vec![away], HOLD);
+        assert_eq!(engine.current(), None);
+        assert!(!engine.needs_processes());
+    }
+}
@@ -0,0 +1,377 @@
+//! A small template language for laying out chatbox text.
+//!
+//! - `{name}` or `{name.field}` inserts a value, e.g. `{time}` or `{media.artist}`
+//! - `{name|superscript|upper}` pipes the value through filters
+//! - `{?name}...{/}` renders its body only when `name` is non-empty,
+//!   `{!name}...{/}` only when it is empty
+//! - `{{`, `}}` and `\n` produce literal braces and line breaks
+//!
+//! Lines that end up blank after rendering are dropped.
+
+use crate::smalltext::{to_smallcaps, to_superscript};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateError {
+    /// Character offset into the template source.
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Filter {
+    Superscript,
+    Smallcaps,
+    Upper,
+    Lower,
+    Trim,
+}
+
+impl Filter {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "superscript" | "sup" => Some(Filter::Superscript),
+            "smallcaps" => Some(Filter::Smallcaps),
+            "upper" => Some(Filter::Upper),
+            "lower" => Some(Filter::Lower),
+            "trim" => Some(Filter::Trim),
+            _ => None,
+        }
+    }
+
+    fn apply(self, value: String) -> String {
+        match self {
+            Filter::Superscript => to_superscript(&value),
+            Filter::Smallcaps => to_smallcaps(&value),
+            Filter::Upper => value.to_uppercase(),
+            Filter::Lower => value.to_lowercase(),
+            Filter::Trim => value.trim().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Value {
+        key: String,
+        filters: Vec<Filter>,
+    },
+    Section {
+        key: String,
+        negated: bool,
+        body: Vec<Node>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        let chars: Vec<char> = source.chars().collect();
+        let mut parser = Parser { chars, pos: 0 };
+        let nodes = parser.parse_nodes(None)?;
+        Ok(Self { nodes })
+    }
+
+    /// Renders the template, looking up each key through `resolve`. Unknown
+    /// keys render as empty.
+    pub fn render<F>(&self, resolve: F) -> String
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut out = String::new();
+        render_nodes(&self.nodes, &resolve, &mut out);
+
+        out.lines()
+            .map(str::trim_end)
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Every key referenced by the template, in order of appearance.
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys = Vec::new();
+        collect_keys(&self.nodes, &mut keys);
+        keys
+    }
+}
+
+fn render_nodes<F>(nodes: &[Node], resolve: &F, out: &mut String)
+where
+    F: Fn(&str) -> Option<String>,
+{
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Value { key, filters } => {
+                let value = resolve(key).unwrap_or_default();
+                out.push_str(&filters.iter().fold(value, |v, f| f.apply(v)));
+            }
+            Node::Section { key, negated, body } => {
+                let present = resolve(key).is_some_and(|v| !v.trim().is_empty());
+                if present != *negated {
+                    render_nodes(body, resolve, out);
+                }
+            }
+        }
+    }
+}
+
+fn collect_keys<'a>(nodes: &'a [Node], keys: &mut Vec<&'a str>) {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Value { key, .. } => keys.push(key),
+            Node::Section { key, body, .. } => {
+                keys.push(key);
+                collect_keys(body, keys);
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, position: usize, message: impl Into<String>) -> TemplateError {
+        TemplateError {
+            position,
+            message: message.into(),
+        }
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    /// Parses until the end of input, or until a `{/}` closing the section
+    /// opened at `open`.
+    fn parse_nodes(&mut self, open: Option<usize>) -> Result<Vec<Node>, TemplateError> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+
+        while let Some(c) = self.peek(0) {
+            match (c, self.peek(1)) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    text.push(c);
+                    self.pos += 2;
+                }
+                ('\\', Some('n')) => {
+                    text.push('\n');
+                    self.pos += 2;
+                }
+                ('{', Some('/')) => {
+                    let start = self.pos;
+                    if self.peek(2) != Some('}') {
+                        return Err(self.error(start, "expected '{/}'"));
+                    }
+                    if open.is_none() {
+                        return Err(self.error(start, "'{/}' without an open section"));
+                    }
+                    self.pos += 3;
+                    flush(&mut text, &mut nodes);
+                    return Ok(nodes);
+                }
+                ('{', _) => {
+                    flush(&mut text, &mut nodes);
+                    nodes.push(self.parse_tag()?);
+                }
+                ('}', _) => return Err(self.error(self.pos, "unmatched '}'")),
+                _ => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+
+        if let Some(start) = open {
+            return Err(self.error(start, "section is never closed with '{/}'"));
+        }
+
+        flush(&mut text, &mut nodes);
+        Ok(nodes)
+    }
+
+    fn parse_tag(&mut self) -> Result<Node, TemplateError> {
+        let start = self.pos;
+        self.pos += 1;
+
+        let close = self.chars[self.pos..]
+            .iter()
+            .position(|&c| c == '}')
+            .map(|i| self.pos + i)
+            .ok_or_else(|| self.error(start, "unclosed '{'"))?;
+        let inner: String = self.chars[self.pos..close].iter().collect();
+        self.pos = close + 1;
+
+        if inner.contains('{') {
+            return Err(self.error(start, "unexpected '{' inside placeholder"));
+        }
+
+        if let Some(key) = inner.strip_prefix('?').or_else(|| inner.strip_prefix('!')) {
+            let negated = inner.starts_with('!');
+            let key = self.parse_key(key, start)?;
+            let body = self.parse_nodes(Some(start))?;
+            return Ok(Node::Section { key, negated, body });
+        }
+
+        let mut parts = inner.split('|');
+        let key = self.parse_key(parts.next().unwrap_or_default(), start)?;
+        let filters = parts
+            .map(|name| {
+                let name = name.trim();
+                Filter::parse(name)
+                    .ok_or_else(|| self.error(start, format!("unknown filter '{name}'")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Node::Value { key, filters })
+    }
+
+    fn parse_key(&self, key: &str, start: usize) -> Result<String, TemplateError> {
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(self.error(start, "empty placeholder"));
+        }
+        if !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            return Err(self.error(start, format!("invalid placeholder name '{key}'")));
+        }
+        Ok(key.to_string())
+    }
+}
+
+fn flush(text: &mut String, nodes: &mut Vec<Node>) {
+    if !text.is_empty() {
+        nodes.push(Node::Text(std::mem::take(text)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str, values: &[(&str, &str)]) -> String {
+        Template::parse(source).unwrap().render(|key| {
+            values
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.to_string())
+        })
+    }
+
+    fn error(source: &str) -> (usize, String) {
+        let e = Template::parse(source).unwrap_err();
+        (e.position, e.message)
+    }
+
+    #[test]
+    fn inserts_values() {
+        let values = [("time", "12:30"), ("media.artist", "Artist")];
+        assert_eq!(render("{time} - {media.artist}", &values), "12:30 - Artist");
+        assert_eq!(render("{ time }", &values), "12:30");
+        // Unknown keys render as empty
+        assert_eq!(render("[{missing}]", &values), "[]");
+    }
+
+    #[test]
+    fn renders_sections() {
+        let values = [("media", "Song"), ("blank", "  ")];
+        assert_eq!(render("{?media}Now: {media}{/}", &values), "Now: Song");
+        assert_eq!(render("{?afk}AFK{/}", &values), "");
+        assert_eq!(render("{?blank}shown{/}", &values), "");
+        assert_eq!(render("{!afk}here{/}", &values), "here");
+        assert_eq!(render("{!media}nothing playing{/}", &values), "");
+        assert_eq!(render("{?media}{!afk}<{media}>{/}{/}", &values), "<Song>");
+    }
+
+    #[test]
+    fn applies_filters_in_order() {
+        let values = [("name", "  Mixed Case  ")];
+        assert_eq!(render("{name|trim|upper}", &values), "MIXED CASE");
+        assert_eq!(render("{name | trim | lower}", &values), "mixed case");
+        assert_eq!(
+            render("{name|trim|lower|sup}", &[("name", "ab")]),
+            to_superscript("ab")
+        );
+        assert_eq!(
+            render("{name|smallcaps}", &[("name", "ab")]),
+            to_smallcaps("ab")
+        );
+    }
+
+    #[test]
+    fn handles_escapes() {
+        assert_eq!(render("{{literal}}", &[]), "{literal}");
+        assert_eq!(render("a\\nb", &[]), "a\nb");
+        assert_eq!(render("\\x", &[]), "\\x");
+    }
+
+    #[test]
+    fn drops_blank_lines() {
+        let values = [("time", "12:30")];
+        assert_eq!(
+            render("{time}\\n{media}\\n  \\n{?afk}AFK{/}\\nend  ", &values),
+            "12:30\nend"
+        );
+        assert_eq!(render("{media}\\n{afk}", &values), "");
+    }
+
+    #[test]
+    fn lists_keys() {
+        let template = Template::parse("{?media}{media.title|upper}{/} {time}").unwrap();
+        assert_eq!(template.keys(), ["media", "media.title", "time"]);
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        assert_eq!(error("ab {time"), (3, "unclosed '{'".to_string()));
+        assert_eq!(error("ab}"), (2, "unmatched '}'".to_string()));
+        assert_eq!(
+            error("x {/}"),
+            (2, "'{/}' without an open section".to_string())
+        );
+        assert_eq!(error("{/ }"), (0, "expected '{/}'".to_string()));
+        assert_eq!(
+            error("x {?media}open"),
+            (2, "section is never closed with '{/}'".to_string())
+        );
+        assert_eq!(error("{}"), (0, "empty placeholder".to_string()));
+        assert_eq!(
+            error("{a {b}"),
+            (0, "unexpected '{' inside placeholder".to_string())
+        );
+        assert_eq!(
+            error("{a-b}"),
+            (0, "invalid placeholder name 'a-b'".to_string())
+        );
+        assert_eq!(
+            error("{time|bold}"),
+            (0, "unknown filter 'bold'".to_string())
+        );
+        // Positions count characters, not bytes
+        assert_eq!(error("\u{1F3B5}é {x"), (3, "unclosed '{'".to_string()));
+        assert_eq!(
+            Template::parse("{x").unwrap_err().to_string(),
+            "unclosed '{' at position 0"
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! Runs the orchestrator on its own thread so slow modules and blocking
+//! sends never stall the caller, e.g. the GUI thread.
+
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::orchestrator::{Orchestrator, SendStatus};
+
+/// Shortest accepted send interval; VRChat rate limits chatbox messages.
+pub const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Requests from the owner of the worker.
+pub enum Command {
+    Config(Box<Config>),
+    ChatMessage(String),
+    Typing(bool),
+}
+
+/// What the worker reports back when something changed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Update {
+    /// Messages composed and sent, and errors so far.
+    Status(SendStatus),
+    /// Profile the automation rules switched to, `None` for the base one.
+    RuleProfile(Option<String>),
+}
+
+pub struct Worker {
+    commands: Option<Sender<Command>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    /// Creates the orchestrator from `config` and starts ticking it every
+    /// `osc.interval_ms`. `on_update` is called from the worker thread. A
+    /// layout that fails to parse is logged and shown in the send status
+    /// rather than failing the start.
+    pub fn start<F>(config: &Config, on_update: F) -> Result<Self, Error>
+    where
+        F: Fn(Update) + Send + 'static,
+    {
+        let mut orch = Orchestrator::new(&config.osc.target, &config.osc.listen)?;
+        if let Err(e) = orch.apply_config(config) {
+            warn!("{}", Error::from(e));
+        }
+        let interval = send_interval(config);
+        let (commands, receiver) = mpsc::channel();
+
+        let thread = thread::Builder::new()
+            .name("orchestrator".to_string())
+            .spawn(move || run(orch, interval, receiver, on_update))
+            .map_err(Error::Spawn)?;
+
+        Ok(Self {
+            commands: Some(commands),
+            thread: Some(thread),
+        })
+    }
+
+    pub fn send(&self, command: Command) {
+        if let Some(commands) = &self.commands {
+            // Only fails once the thread is gone, which Drop reports
+            let _ = commands.send(command);
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // Closing the channel ends the loop after the current tick
+        self.commands = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Time between sends from `config`, never below `MIN_INTERVAL`.
+pub fn send_interval(config: &Config) -> Duration {
+    Duration::from_millis(config.osc.interval_ms).max(MIN_INTERVAL)
+}
+
+fn run<F>(
+    mut orch: Orchestrator,
+    mut interval: Duration,
+    commands: mpsc::Receiver<Command>,
+    on_update: F,
+) where
+    F: Fn(Update),
+{
+    let mut next_tick = Instant::now();
+    let mut rule_profile: Option<String> = None;
+    let mut status = SendStatus::default();
+
+    loop {
+        // Checked before waiting so a steady stream of commands can't
+        // starve the ticks
+        let now = Instant::now();
+        if now >= next_tick {
+            tick(&mut orch, &mut rule_profile, &on_update);
+            report_status(&orch, &mut status, &on_update);
+            // Skip ticks that were missed rather than sending a burst
+            next_tick = (next_tick + interval).max(Instant::now());
+            continue;
+        }
+
+        match commands.recv_timeout(next_tick - now) {
+            Ok(Command::Config(config)) => {
+                if let Err(e) = orch.apply_config(&config) {
+                    warn!("{}", Error::from(e));
+                }
+                let new_interval = send_interval(&config);
+                if new_interval != interval {
+                    info!("send interval changed to {new_interval:?}");
+                    next_tick = Instant::now() + new_interval;
+                    interval = new_interval;
+                }
+            }
+            Ok(Command::ChatMessage(text)) => orch.set_chat_message(text),
+            Ok(Command::Typing(typing)) => {
+                if let Err(e) = orch.send_typing(typing) {
+                    error!("{e}");
+                }
+                report_status(&orch, &mut status, &on_update);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+fn tick<F>(orch: &mut Orchestrator, rule_profile: &mut Option<String>, on_update: &F)
+where
+    F: Fn(Update),
+{
+    // Errors are also counted in the send status
+    if let Err(e) = orch.tick() {
+        error!("{e}");
+    }
+
+    let current = orch.rule_profile().map(str::to_string);
+    if current != *rule_profile {
+        rule_profile.clone_from(&current);
+        on_update(Update::RuleProfile(current));
+    }
+}
+
+fn report_status<F>(orch: &Orchestrator, reported: &mut SendStatus, on_update: &F)
+where
+    F: Fn(Update),
+{
+    let status = orch.send_status();
+    if status != reported {
+        reported.clone_from(status);
+        on_update(Update::Status(status.clone()));
+    }
+}